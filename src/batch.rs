@@ -0,0 +1,398 @@
+//! Batched rendering: many shapes, one draw call.
+//!
+//! Every ``SketchDrawCommand`` is a separate draw call with its own color uniform, which is
+//! fine for a handful of shapes but way too slow for generative sketches drawing thousands
+//! of them. A ``Batch`` instead accumulates the triangles of all of its shapes on the CPU,
+//! storing the color in each vertex, and sends everything to the GPU in a single draw.
+//! Gradients are painted by the same shaders: each vertex also knows where it lies
+//! within its gradient, whose colors are looked up in the ``paint_texture`` uniform.
+
+use std::ops::Range;
+use std::rc::Rc;
+
+use glium::backend::{Context, Facade};
+use glium::implement_vertex;
+
+use crate::color::Color;
+use crate::color_pipeline::ColorPipeline;
+use crate::path::Path;
+use crate::shapes::{fill_mesh, stroke_mesh, Paint, ShapePrimitive, ShapeStyle, Vertex};
+use crate::tessellation::Mesh;
+
+/// Like ``Vertex``, but carrying its own paint
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ColoredVertex {
+    pub position: [f32; 2],
+    /// Multiplied by the color of the gradient, if any
+    pub color: [f32; 4],
+    /// The position in the unit space of the gradient (see ``Gradient::unit_transform``)
+    pub paint_position: [f32; 2],
+    /// The kind of gradient (0 for solid colors) and its spread mode,
+    /// see ``Gradient::shader_paint``
+    pub paint: [f32; 2],
+}
+
+implement_vertex!(ColoredVertex, position, color, paint_position, paint);
+
+impl ColoredVertex {
+    /// A vertex painted with a solid color, uploaded as it is
+    pub fn new(position: [f32; 2], color: Color) -> Self {
+        Self {
+            position,
+            color: [color.r, color.g, color.b, color.a],
+            paint_position: [0.0, 0.0],
+            paint: [0.0, 0.0],
+        }
+    }
+}
+
+pub const BATCH_VERTEX_SHADER_SRC: &str = r#"
+#version 140
+
+in vec2 position;
+in vec4 color;
+in vec2 paint_position;
+in vec2 paint;
+
+out vec4 vertex_color;
+out vec2 gradient_position;
+flat out vec2 gradient;
+
+// Goes from the coordinates used to draw to normalized device coordinates
+uniform mat3 projection;
+
+void main() {
+    vertex_color = color;
+    gradient_position = paint_position;
+    gradient = paint;
+    gl_Position = vec4((projection * vec3(position, 1.0)).xy, 0.0, 1.0);
+}
+"#;
+
+pub const BATCH_FRAGMENT_SHADER_SRC: &str = r#"
+#version 140
+
+in vec4 vertex_color;
+in vec2 gradient_position;
+flat in vec2 gradient;
+out vec4 color;
+
+// The colors of the gradient, see ``Gradient::bake``
+uniform sampler2D paint_texture;
+
+const float TAU = 6.28318530718;
+
+// Same as ``SpreadMode::apply``
+float spread(float offset, float mode) {
+    if (mode < 0.5) {
+        return clamp(offset, 0.0, 1.0);
+    } else if (mode < 1.5) {
+        return fract(offset);
+    }
+    return 1.0 - abs(mod(offset, 2.0) - 1.0);
+}
+
+// Goes from [0, 1] to the centers of the first and the last texels
+vec2 texel_center(vec2 position) {
+    vec2 size = vec2(textureSize(paint_texture, 0));
+    return (clamp(position, 0.0, 1.0) * (size - 1.0) + 0.5) / size;
+}
+
+void main() {
+    vec4 paint_color = vertex_color;
+    int kind = int(gradient.x + 0.5);
+
+    if (kind == 4) {
+        paint_color *= texture(paint_texture, texel_center(gradient_position));
+    } else if (kind > 0) {
+        float offset;
+        if (kind == 1) {
+            offset = gradient_position.x;
+        } else if (kind == 2) {
+            offset = length(gradient_position);
+        } else {
+            offset = fract(atan(gradient_position.y, gradient_position.x) / TAU);
+        }
+        offset = spread(offset, gradient.y);
+        paint_color *= texture(paint_texture, texel_center(vec2(offset, 0.5)));
+    }
+
+    // Premultiplied alpha, see ``blend``
+    color = vec4(paint_color.rgb * paint_color.a, paint_color.a);
+}
+"#;
+
+/// Buffers never shrink below this many vertices (or indices),
+/// so that small batches don't keep reallocating
+const MIN_CAPACITY: usize = 1024;
+
+/// Shapes waiting to be drawn, plus the GPU buffers they are uploaded into.
+/// The buffers are kept across frames and only reallocated when they are too small
+pub struct Batch {
+    context: Rc<Context>,
+    vertices: Vec<ColoredVertex>,
+    indices: Vec<u32>,
+    vertex_buffer: Option<glium::VertexBuffer<ColoredVertex>>,
+    index_buffer: Option<glium::IndexBuffer<u32>>,
+    /// Whether the buffers are out of date
+    changed: bool,
+    color_pipeline: ColorPipeline,
+}
+
+impl Batch {
+    pub fn new(display: &glium::Display) -> Self {
+        Self::with_facade(display, ColorPipeline::for_display(display))
+    }
+
+    /// A batch living in any OpenGL context, like the one of a ``Headless`` renderer
+    pub fn with_facade(facade: &impl Facade, color_pipeline: ColorPipeline) -> Self {
+        Self {
+            context: facade.get_context().clone(),
+            vertices: Vec::new(),
+            indices: Vec::new(),
+            vertex_buffer: None,
+            index_buffer: None,
+            changed: false,
+            color_pipeline,
+        }
+    }
+
+    pub fn color_pipeline(&self) -> ColorPipeline {
+        self.color_pipeline
+    }
+
+    /// How the colors of the shapes added from now on are uploaded.
+    /// It must match the program used to draw the batch (see ``ResourceCache::program_for_pipeline``)
+    pub fn set_color_pipeline(&mut self, color_pipeline: ColorPipeline) {
+        self.color_pipeline = color_pipeline;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// Number of triangles waiting to be drawn
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    /// Number of indices added so far: the shapes added next start from here
+    pub fn index_count(&self) -> usize {
+        self.indices.len()
+    }
+
+    /// Adds all of the triangles of ``mesh``, painted with ``color``
+    pub fn add_mesh(&mut self, mesh: &Mesh, color: Color) {
+        self.add_painted_mesh(mesh, &Paint::Solid(color));
+    }
+
+    /// Adds all of the triangles of ``mesh``, painted with ``paint``.
+    /// Gradients also need their texture to be bound when drawing (see ``Gradient::bake``)
+    pub fn add_painted_mesh(&mut self, mesh: &Mesh, paint: &Paint) {
+        let offset = self.vertices.len() as u32;
+
+        match paint {
+            Paint::Solid(color) => {
+                // Converted once per shape, rather than once per vertex
+                let vertex = ColoredVertex {
+                    color: self.color_pipeline.upload(*color),
+                    ..ColoredVertex::new([0.0, 0.0], *color)
+                };
+                self.vertices.extend(
+                    mesh.vertices
+                        .iter()
+                        .map(|&Vertex { position }| ColoredVertex { position, ..vertex }),
+                );
+            }
+            Paint::Gradient(gradient) => {
+                let to_unit = gradient.unit_transform();
+                let paint = gradient.shader_paint();
+                self.vertices
+                    .extend(
+                        mesh.vertices
+                            .iter()
+                            .map(|&Vertex { position }| ColoredVertex {
+                                position,
+                                color: [1.0, 1.0, 1.0, 1.0],
+                                paint_position: to_unit.apply(position),
+                                paint,
+                            }),
+                    );
+            }
+        }
+
+        self.indices
+            .extend(mesh.indices.iter().map(|index| index + offset));
+        self.changed = true;
+    }
+
+    /// Same as ``generate_draw_commands``, but the fill and the stroke are added to the batch
+    pub fn add_primitive(
+        &mut self,
+        vertices: &[Vertex],
+        primitive: &ShapePrimitive,
+        style: &ShapeStyle,
+    ) {
+        self.add_styled(
+            style,
+            || fill_mesh(vertices, primitive),
+            |style| stroke_mesh(vertices, primitive, &style.stroke_options()),
+        );
+    }
+
+    /// Same as ``generate_path_draw_commands``, but the fill and the stroke are added to the batch
+    pub fn add_path(&mut self, path: &Path, style: &ShapeStyle, tolerance: f32) {
+        self.add_styled(
+            style,
+            || path.fill_mesh(tolerance, style.fill_rule),
+            |style| {
+                let options = style.stroke_options().with_tolerance(tolerance);
+                path.stroke_mesh(&options, tolerance)
+            },
+        );
+    }
+
+    fn add_styled(
+        &mut self,
+        style: &ShapeStyle,
+        fill: impl FnOnce() -> Mesh,
+        stroke: impl FnOnce(&ShapeStyle) -> Mesh,
+    ) {
+        if let Some(fill_paint) = &style.fill {
+            self.add_painted_mesh(&fill(), fill_paint);
+        }
+
+        if let Some(stroke_paint) = &style.stroke {
+            if style.stroke_width > 0.0 {
+                self.add_painted_mesh(&stroke(style), stroke_paint);
+            }
+        }
+    }
+
+    /// Forgets the shapes added so far, without drawing them
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+        self.indices.clear();
+        self.changed = true;
+    }
+
+    /// Draws all of the shapes added so far with a single draw call, then empties the batch.
+    /// ``program`` is usually compiled from ``BATCH_VERTEX_SHADER_SRC``
+    /// and ``BATCH_FRAGMENT_SHADER_SRC``, in which case ``uniforms``
+    /// must contain the ``projection`` matrix (see ``Transform::to_mat3``).
+    /// The whole batch shares the blend of ``draw_parameters``: the blend mode
+    /// of the styles isn't stored with the triangles (see ``BlendMode::blend``)
+    pub fn flush<S: glium::Surface, U: glium::uniforms::Uniforms>(
+        &mut self,
+        surface: &mut S,
+        program: &glium::Program,
+        uniforms: &U,
+        draw_parameters: &glium::DrawParameters,
+    ) -> Result<(), glium::DrawError> {
+        let range = 0..self.indices.len();
+        let result = self.draw_range(surface, program, uniforms, range, draw_parameters);
+        self.clear();
+        result
+    }
+
+    /// Draws the triangles whose indices are in ``range``, keeping the batch as it is.
+    /// Useful to draw the same batch more than once, or to draw parts of it
+    /// with different draw parameters. Nothing is uploaded if the batch didn't change.
+    /// The part of ``range`` past the indices of the batch is ignored
+    pub fn draw_range<S: glium::Surface, U: glium::uniforms::Uniforms>(
+        &mut self,
+        surface: &mut S,
+        program: &glium::Program,
+        uniforms: &U,
+        range: Range<usize>,
+        draw_parameters: &glium::DrawParameters,
+    ) -> Result<(), glium::DrawError> {
+        let range = clamp_range(range, self.indices.len());
+        if range.is_empty() {
+            return Ok(());
+        }
+
+        if self.changed {
+            self.upload();
+        }
+
+        // The buffers have been (re)allocated if needed, so they are big enough
+        // for all of the vertices and indices (and the range is within the indices)
+        let vertex_buffer = self.vertex_buffer.as_ref().unwrap();
+        let index_buffer = self.index_buffer.as_ref().unwrap();
+        surface.draw(
+            vertex_buffer.slice(0..self.vertices.len()).unwrap(),
+            index_buffer.slice(range).unwrap(),
+            program,
+            uniforms,
+            draw_parameters,
+        )
+    }
+
+    /// Writes the vertices and the indices into the GPU buffers,
+    /// growing them (to the next power of two) when they are too small
+    fn upload(&mut self) {
+        let capacity = |len: usize| len.next_power_of_two().max(MIN_CAPACITY);
+
+        match &mut self.vertex_buffer {
+            Some(buffer) if buffer.len() >= self.vertices.len() => {
+                // The previous content isn't needed anymore: this lets the driver
+                // give us fresh memory instead of waiting for the GPU to be done with it
+                buffer.invalidate();
+                buffer
+                    .slice(0..self.vertices.len())
+                    .unwrap()
+                    .write(&self.vertices);
+            }
+            buffer => {
+                let mut vertices = self.vertices.clone();
+                vertices.resize(
+                    capacity(self.vertices.len()),
+                    ColoredVertex::new([0.0, 0.0], Color::new(0.0, 0.0, 0.0, 0.0)),
+                );
+                *buffer = Some(glium::VertexBuffer::dynamic(&self.context, &vertices).unwrap());
+            }
+        }
+
+        match &mut self.index_buffer {
+            Some(buffer) if buffer.len() >= self.indices.len() => {
+                buffer.invalidate();
+                buffer
+                    .slice(0..self.indices.len())
+                    .unwrap()
+                    .write(&self.indices);
+            }
+            buffer => {
+                let mut indices = self.indices.clone();
+                indices.resize(capacity(self.indices.len()), 0);
+                *buffer = Some(
+                    glium::IndexBuffer::dynamic(
+                        &self.context,
+                        glium::index::PrimitiveType::TrianglesList,
+                        &indices,
+                    )
+                    .unwrap(),
+                );
+            }
+        }
+
+        self.changed = false;
+    }
+}
+
+/// The part of ``range`` that is within ``0..len``
+pub(crate) fn clamp_range(range: Range<usize>, len: usize) -> Range<usize> {
+    range.start.min(len)..range.end.min(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranges_are_clamped_to_the_data() {
+        assert_eq!(clamp_range(3..6, 10), 3..6);
+        assert_eq!(clamp_range(3..16, 10), 3..10);
+        assert!(clamp_range(12..16, 10).is_empty());
+    }
+}
@@ -0,0 +1,258 @@
+//! How the color of a shape is combined with whatever is already drawn below it.
+//!
+//! Our shaders output premultiplied colors (the red, green and blue channels are multiplied
+//! by the alpha channel before blending), which is what makes transparent shapes
+//! compose correctly, even when they overlap each other or are blended additively.
+
+use glium::{Blend, BlendingFunction, LinearBlendingFactor};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub enum BlendMode {
+    /// Transparent shapes are painted over the ones below them
+    #[default]
+    Normal,
+    /// The colors are added together, so overlapping shapes get brighter
+    Additive,
+    /// The colors are multiplied together, so overlapping shapes get darker.
+    /// This is only exact over an opaque destination: where the canvas below is
+    /// transparent, the shape disappears instead of keeping its own color
+    Multiply,
+    /// The opposite of ``Multiply``: the inverted colors are multiplied together
+    Screen,
+    /// The color of the shape is subtracted from the one below it
+    Subtract,
+    /// Keeps the darkest of the two colors, channel by channel.
+    /// Like ``Max``, it ignores the alpha channel of the shape
+    Min,
+    /// Keeps the brightest of the two colors, channel by channel
+    Max,
+    /// The shape overwrites whatever is below it, alpha channel included
+    Replace,
+}
+
+impl BlendMode {
+    /// The blending that the GPU should use for this mode, given premultiplied colors
+    pub fn blend(&self) -> Blend {
+        // Regardless of how colors are mixed, the coverage of the shape
+        // accumulates in the alpha channel like it does for normal blending
+        let over = BlendingFunction::Addition {
+            source: LinearBlendingFactor::One,
+            destination: LinearBlendingFactor::OneMinusSourceAlpha,
+        };
+
+        let color = match self {
+            BlendMode::Normal => over,
+            BlendMode::Additive => BlendingFunction::Addition {
+                source: LinearBlendingFactor::One,
+                destination: LinearBlendingFactor::One,
+            },
+            // source * destination, plus what the shape lets through
+            BlendMode::Multiply => BlendingFunction::Addition {
+                source: LinearBlendingFactor::DestinationColor,
+                destination: LinearBlendingFactor::OneMinusSourceAlpha,
+            },
+            // source + destination - source * destination
+            BlendMode::Screen => BlendingFunction::Addition {
+                source: LinearBlendingFactor::OneMinusDestinationColor,
+                destination: LinearBlendingFactor::One,
+            },
+            BlendMode::Subtract => BlendingFunction::ReverseSubtraction {
+                source: LinearBlendingFactor::One,
+                destination: LinearBlendingFactor::One,
+            },
+            BlendMode::Min => BlendingFunction::Min,
+            BlendMode::Max => BlendingFunction::Max,
+            BlendMode::Replace => BlendingFunction::AlwaysReplace,
+        };
+
+        let alpha = match self {
+            BlendMode::Replace => BlendingFunction::AlwaysReplace,
+            _ => over,
+        };
+
+        Blend {
+            color,
+            alpha,
+            constant_value: (0.0, 0.0, 0.0, 0.0),
+        }
+    }
+
+    /// The value of the CSS ``mix-blend-mode`` property closest to this mode, if any
+    pub fn css_name(&self) -> Option<&'static str> {
+        match self {
+            BlendMode::Normal => Some("normal"),
+            BlendMode::Additive => Some("plus-lighter"),
+            BlendMode::Multiply => Some("multiply"),
+            BlendMode::Screen => Some("screen"),
+            BlendMode::Min => Some("darken"),
+            BlendMode::Max => Some("lighten"),
+            BlendMode::Subtract | BlendMode::Replace => None,
+        }
+    }
+
+    /// Parses a value of the CSS ``mix-blend-mode`` property
+    pub fn from_css_name(name: &str) -> Option<Self> {
+        match name {
+            "normal" => Some(BlendMode::Normal),
+            "plus-lighter" => Some(BlendMode::Additive),
+            "multiply" => Some(BlendMode::Multiply),
+            "screen" => Some(BlendMode::Screen),
+            "darken" => Some(BlendMode::Min),
+            "lighten" => Some(BlendMode::Max),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-5;
+
+    fn assert_close(actual: [f32; 4], expected: [f32; 4]) {
+        for (actual, expected) in actual.iter().zip(expected) {
+            assert!(
+                (actual - expected).abs() < EPSILON,
+                "{actual:?} is not {expected:?}"
+            );
+        }
+    }
+
+    fn premultiply([r, g, b, a]: [f32; 4]) -> [f32; 4] {
+        [r * a, g * a, b * a, a]
+    }
+
+    /// What the GPU computes for one channel, following the OpenGL specification
+    fn apply(function: BlendingFunction, source: [f32; 4], destination: [f32; 4], i: usize) -> f32 {
+        let factor = |factor| match factor {
+            LinearBlendingFactor::Zero => 0.0,
+            LinearBlendingFactor::One => 1.0,
+            LinearBlendingFactor::SourceColor => source[i],
+            LinearBlendingFactor::DestinationColor => destination[i],
+            LinearBlendingFactor::OneMinusDestinationColor => 1.0 - destination[i],
+            LinearBlendingFactor::OneMinusSourceAlpha => 1.0 - source[3],
+            other => panic!("{other:?} isn't used by any blend mode"),
+        };
+
+        match function {
+            BlendingFunction::AlwaysReplace => source[i],
+            BlendingFunction::Min => source[i].min(destination[i]),
+            BlendingFunction::Max => source[i].max(destination[i]),
+            BlendingFunction::Addition {
+                source: s,
+                destination: d,
+            } => source[i] * factor(s) + destination[i] * factor(d),
+            BlendingFunction::ReverseSubtraction {
+                source: s,
+                destination: d,
+            } => destination[i] * factor(d) - source[i] * factor(s),
+            other => panic!("{other:?} isn't used by any blend mode"),
+        }
+    }
+
+    /// Blends premultiplied colors like the GPU would, clamping the result to the framebuffer
+    fn blend(mode: BlendMode, source: [f32; 4], destination: [f32; 4]) -> [f32; 4] {
+        let blend = mode.blend();
+        let channel = |i| {
+            let function = if i == 3 { blend.alpha } else { blend.color };
+            apply(function, source, destination, i).clamp(0.0, 1.0)
+        };
+        [channel(0), channel(1), channel(2), channel(3)]
+    }
+
+    const BACKGROUND: [f32; 4] = [0.2, 0.6, 0.8, 1.0];
+    const SHAPE: [f32; 4] = [0.9, 0.5, 0.1, 0.5];
+
+    #[test]
+    fn normal_paints_over_the_destination() {
+        let result = blend(BlendMode::Normal, premultiply(SHAPE), BACKGROUND);
+        assert_close(result, [0.55, 0.55, 0.45, 1.0]);
+
+        // Two half transparent layers over nothing cover three quarters
+        let once = blend(BlendMode::Normal, premultiply(SHAPE), [0.0; 4]);
+        let twice = blend(BlendMode::Normal, premultiply(SHAPE), once);
+        assert_close(twice, premultiply([0.9, 0.5, 0.1, 0.75]));
+    }
+
+    #[test]
+    fn additive_adds_the_weighted_color() {
+        let result = blend(BlendMode::Additive, premultiply(SHAPE), BACKGROUND);
+        assert_close(result, [0.65, 0.85, 0.85, 1.0]);
+    }
+
+    #[test]
+    fn multiply_matches_the_css_formula_over_opaque_destinations() {
+        // (1 - alpha) * backdrop + alpha * backdrop * color
+        let expected = [0, 1, 2].map(|i| 0.5 * BACKGROUND[i] + 0.5 * BACKGROUND[i] * SHAPE[i]);
+        let result = blend(BlendMode::Multiply, premultiply(SHAPE), BACKGROUND);
+        assert_close(result, [expected[0], expected[1], expected[2], 1.0]);
+
+        // An opaque white shape changes nothing, a black one gives black
+        let white = blend(BlendMode::Multiply, [1.0; 4], BACKGROUND);
+        assert_close(white, BACKGROUND);
+        let black = blend(BlendMode::Multiply, [0.0, 0.0, 0.0, 1.0], BACKGROUND);
+        assert_close(black, [0.0, 0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn multiply_loses_the_color_over_transparent_destinations() {
+        // The documented limitation: only the coverage is kept
+        let result = blend(BlendMode::Multiply, premultiply(SHAPE), [0.0; 4]);
+        assert_close(result, [0.0, 0.0, 0.0, 0.5]);
+    }
+
+    #[test]
+    fn screen_is_the_inverse_of_multiply() {
+        // color + backdrop - color * backdrop, with the color weighted by its alpha
+        let expected = [0, 1, 2].map(|i| {
+            let color = SHAPE[i] * SHAPE[3];
+            color + BACKGROUND[i] - color * BACKGROUND[i]
+        });
+        let result = blend(BlendMode::Screen, premultiply(SHAPE), BACKGROUND);
+        assert_close(result, [expected[0], expected[1], expected[2], 1.0]);
+
+        let black = blend(BlendMode::Screen, [0.0, 0.0, 0.0, 1.0], BACKGROUND);
+        assert_close(black, BACKGROUND);
+    }
+
+    #[test]
+    fn subtract_min_and_max_work_channel_by_channel() {
+        let source = premultiply(SHAPE);
+
+        let subtract = blend(BlendMode::Subtract, source, BACKGROUND);
+        assert_close(subtract, [0.0, 0.35, 0.75, 1.0]);
+
+        let min = blend(BlendMode::Min, source, BACKGROUND);
+        assert_close(min, [0.2, 0.25, 0.05, 1.0]);
+
+        let max = blend(BlendMode::Max, source, BACKGROUND);
+        assert_close(max, [0.45, 0.6, 0.8, 1.0]);
+    }
+
+    #[test]
+    fn replace_overwrites_the_alpha_too() {
+        let source = premultiply(SHAPE);
+        assert_close(blend(BlendMode::Replace, source, BACKGROUND), source);
+    }
+
+    #[test]
+    fn css_names_round_trip() {
+        for mode in [
+            BlendMode::Normal,
+            BlendMode::Additive,
+            BlendMode::Multiply,
+            BlendMode::Screen,
+            BlendMode::Subtract,
+            BlendMode::Min,
+            BlendMode::Max,
+            BlendMode::Replace,
+        ] {
+            match mode.css_name() {
+                Some(name) => assert_eq!(BlendMode::from_css_name(name), Some(mode)),
+                None => assert!(matches!(mode, BlendMode::Subtract | BlendMode::Replace)),
+            }
+        }
+        assert_eq!(BlendMode::from_css_name("overlay"), None);
+    }
+}
@@ -0,0 +1,1036 @@
+//! The surface sketches draw on.
+//!
+//! Drawing on a ``Canvas`` doesn't talk to the GPU right away: shapes are tessellated into
+//! a ``Batch`` (and copies of a mesh into a list of instances) and the whole frame is
+//! sent to the GPU by ``Canvas::render``, with as few draw calls as possible.
+//! Everything drawn is also recorded, so the frame can be exported as vectors.
+//!
+//! Besides drawing shapes with an explicit ``ShapeStyle``, the canvas offers an API modeled
+//! after Processing and p5.js: set the current style with ``fill``, ``stroke`` and friends,
+//! then draw with ``rect``, ``ellipse``, ``line``, or ``begin_shape``/``vertex``/``end_shape``.
+//! Shapes can be moved around with ``translate``, ``rotate``, ``scale`` and ``shear``:
+//! the current matrix is applied on the CPU, before tessellating, so transformed shapes
+//! still end up in the same batch (and curves are flattened at the right resolution).
+//! Each shape can have its own blend mode: the batch is split into one draw call
+//! per run of consecutive shapes sharing the same mode.
+
+use std::ops::Range;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use glium::backend::{Context, Facade};
+use glium::texture::Texture2d;
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter, SamplerWrapFunction};
+
+use crate::batch::{Batch, BATCH_FRAGMENT_SHADER_SRC, BATCH_VERTEX_SHADER_SRC};
+use crate::blend::BlendMode;
+use crate::capture::frame_path;
+use crate::color::Color;
+use crate::color_pipeline::ColorPipeline;
+use crate::coordinates::CoordinateMode;
+use crate::instancing::{
+    Instances, InstancingError, INSTANCED_FRAGMENT_SHADER_SRC, INSTANCED_VERTEX_SHADER_SRC,
+};
+use crate::offscreen::{OffscreenError, OffscreenTarget, RgbaImage};
+use crate::path::Path;
+use crate::recording::{Recorder, RecordingSettings};
+use crate::resources::{CacheKey, GpuMesh, ResourceCache};
+use crate::shapes::{Instance, Paint, Shape, ShapePrimitive, ShapeStyle, Vertex};
+use crate::svg::SvgRecording;
+use crate::tessellation::ellipse;
+use crate::tessellation::polygon::FillRule;
+use crate::tessellation::stroke::{LineCap, LineJoin};
+use crate::transform::Transform;
+
+/// Where the stroke weight of the Processing-like style comes from
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum StrokeWeight {
+    /// One pixel, in whatever the coordinate mode is
+    Default,
+    /// Set by the sketch, in the units of the coordinate mode it was set in
+    Explicit,
+}
+
+/// What has to be drawn, in order
+enum DrawItem {
+    Triangles {
+        /// Indices of the batch
+        range: Range<usize>,
+        blend_mode: BlendMode,
+        /// Where the colors of the gradients come from, if there are any
+        paint_texture: Option<Rc<Texture2d>>,
+    },
+    Instances {
+        mesh: Rc<GpuMesh>,
+        range: Range<usize>,
+        blend_mode: BlendMode,
+    },
+}
+
+/// How ``Canvas::end_shape`` finishes the outline of the shape
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EndShape {
+    /// The last vertex isn't connected back to the first one.
+    /// The fill still covers the shape as if it was closed
+    Open,
+    Close,
+}
+
+/// The shape being built between ``begin_shape`` and ``end_shape``
+struct ShapeBuilder {
+    path: Path,
+    /// The next vertex starts a new contour
+    new_contour: bool,
+}
+
+pub struct Canvas {
+    resources: ResourceCache,
+    batch_program: Rc<glium::Program>,
+    instanced_program: Rc<glium::Program>,
+    /// Bound when there aren't any gradients to draw
+    blank_texture: Rc<Texture2d>,
+    batch: Batch,
+    instances: Instances,
+    items: Vec<DrawItem>,
+    background: Option<Color>,
+    recording: SvgRecording,
+    /// Used by the Processing-like API, and kept from one frame to the next
+    style: ShapeStyle,
+    stroke_weight: StrokeWeight,
+    style_stack: Vec<(ShapeStyle, StrokeWeight)>,
+    /// Applied to everything drawn, reset at the beginning of each frame
+    matrix: Transform,
+    matrix_stack: Vec<Transform>,
+    shape: Option<ShapeBuilder>,
+    coordinate_mode: CoordinateMode,
+    /// Size in logical pixels
+    width: f32,
+    height: f32,
+    /// Physical pixels per logical pixel
+    scale_factor: f32,
+    color_pipeline: ColorPipeline,
+    /// Whether the surfaces we render to convert linear colors to sRGB
+    srgb_framebuffer: bool,
+    /// Samples used for anti-aliasing when rendering offscreen
+    multisampling: u16,
+    /// Set while the frames are being written to disk
+    recorder: Option<Recorder>,
+    /// Frames per second of the sketch, which recordings play back at
+    frame_rate: f32,
+    frame_count: u64,
+}
+
+impl Canvas {
+    pub fn new(display: &glium::Display) -> Self {
+        let (width, height) = display.get_framebuffer_dimensions();
+        let scale_factor = display.gl_window().window().scale_factor() as f32;
+        let srgb_framebuffer = display.gl_window().get_pixel_format().srgb;
+        Self::with_facade(display, [width, height], scale_factor, srgb_framebuffer)
+    }
+
+    /// A canvas drawing in any OpenGL context, like the one of a ``Headless`` renderer.
+    /// ``size`` is in physical pixels, and ``srgb_framebuffer`` tells whether the surfaces
+    /// passed to ``render`` convert linear colors to sRGB (see ``ColorPipeline``)
+    pub fn with_facade(
+        facade: &impl Facade,
+        size: [u32; 2],
+        scale_factor: f32,
+        srgb_framebuffer: bool,
+    ) -> Self {
+        let color_pipeline = ColorPipeline::for_framebuffer(srgb_framebuffer);
+        let mut resources = ResourceCache::with_facade(facade, color_pipeline);
+        let (batch_program, instanced_program) = load_programs(&mut resources, color_pipeline);
+        let blank_texture =
+            resources.texture(CacheKey::new("blank"), || glium::texture::RawImage2d {
+                data: vec![1.0; 4].into(),
+                width: 1,
+                height: 1,
+                format: glium::texture::ClientFormat::F32F32F32F32,
+            });
+
+        let mut canvas = Self {
+            resources,
+            batch_program,
+            instanced_program,
+            blank_texture,
+            batch: Batch::with_facade(facade, color_pipeline),
+            instances: Instances::with_facade(facade, color_pipeline),
+            items: Vec::new(),
+            background: None,
+            recording: SvgRecording::for_normalized_device_coordinates(0.0, 0.0),
+            style: ShapeStyle::default(),
+            stroke_weight: StrokeWeight::Default,
+            style_stack: Vec::new(),
+            matrix: Transform::identity(),
+            matrix_stack: Vec::new(),
+            shape: None,
+            coordinate_mode: CoordinateMode::default(),
+            width: 0.0,
+            height: 0.0,
+            scale_factor: 1.0,
+            color_pipeline,
+            srgb_framebuffer,
+            multisampling: 0,
+            recorder: None,
+            frame_rate: 60.0,
+            frame_count: 0,
+        };
+        canvas.resize(size[0] as f32, size[1] as f32, scale_factor);
+
+        // White fill and a black stroke of one pixel, like Processing
+        canvas.style = ShapeStyle::filled(Color::new(1.0, 1.0, 1.0, 1.0))
+            .with_stroke(Color::new(0.0, 0.0, 0.0, 1.0), canvas.pixels(1.0));
+        canvas
+    }
+
+    /// Width of the canvas, in logical pixels
+    pub fn width(&self) -> f32 {
+        self.width
+    }
+
+    /// Height of the canvas, in logical pixels
+    pub fn height(&self) -> f32 {
+        self.height
+    }
+
+    /// How many physical pixels of the window make a logical pixel
+    pub fn scale_factor(&self) -> f32 {
+        self.scale_factor
+    }
+
+    /// Must be called when the window changes size or moves to a screen with a different
+    /// scale factor. ``width`` and ``height`` are in physical pixels
+    pub fn resize(&mut self, width: f32, height: f32, scale_factor: f32) {
+        self.scale_factor = scale_factor.max(f32::EPSILON);
+        self.width = width / self.scale_factor;
+        self.height = height / self.scale_factor;
+    }
+
+    pub fn coordinate_mode(&self) -> CoordinateMode {
+        self.coordinate_mode
+    }
+
+    /// Changes the coordinates used to draw from now on.
+    /// The default stroke weight stays one pixel wide, in the current style and in the
+    /// ones saved by ``push_style``. Weights set with ``stroke_weight`` (or ``set_style``)
+    /// are kept as they are
+    pub fn set_coordinate_mode(&mut self, coordinate_mode: CoordinateMode) {
+        self.coordinate_mode = coordinate_mode;
+        let default_width = self.pixels(1.0);
+
+        let saved = self
+            .style_stack
+            .iter_mut()
+            .map(|(style, weight)| (style, *weight));
+        for (style, weight) in std::iter::once((&mut self.style, self.stroke_weight)).chain(saved) {
+            if weight == StrokeWeight::Default {
+                style.stroke_width = default_width;
+            }
+        }
+    }
+
+    /// Goes from drawing coordinates to normalized device coordinates
+    pub fn projection(&self) -> Transform {
+        self.coordinate_mode.projection(self.width, self.height)
+    }
+
+    /// How many (logical) pixels one unit of the drawing coordinates covers on each axis
+    pub fn pixels_per_unit(&self) -> [f32; 2] {
+        let projection = self.projection();
+        [
+            projection.a.abs() * self.width / 2.0,
+            projection.d.abs() * self.height / 2.0,
+        ]
+    }
+
+    /// Converts a length in (vertical) pixels into drawing units, handy for stroke widths
+    pub fn pixels(&self, amount: f32) -> f32 {
+        amount / self.pixels_per_unit()[1]
+    }
+
+    /// Converts a position in the window (in physical pixels, from the top left corner,
+    /// like the ones in window events) into drawing coordinates
+    pub fn window_to_canvas(&self, [x, y]: [f32; 2]) -> [f32; 2] {
+        let width = self.width * self.scale_factor;
+        let height = self.height * self.scale_factor;
+        let normalized = [x / width * 2.0 - 1.0, 1.0 - y / height * 2.0];
+
+        match self.projection().inverse() {
+            Some(inverse) => inverse.apply(normalized),
+            None => normalized,
+        }
+    }
+
+    pub fn color_pipeline(&self) -> ColorPipeline {
+        self.color_pipeline
+    }
+
+    /// Chooses whether blending happens on linear or sRGB values, usually in ``setup``.
+    /// ``ColorPipeline::Linear`` falls back to ``ColorPipeline::Srgb`` if the framebuffer
+    /// isn't an sRGB one
+    pub fn set_color_pipeline(&mut self, color_pipeline: ColorPipeline) {
+        self.color_pipeline = color_pipeline.supported_by(self.srgb_framebuffer);
+        (self.batch_program, self.instanced_program) =
+            load_programs(&mut self.resources, self.color_pipeline);
+        self.batch.set_color_pipeline(self.color_pipeline);
+        self.instances.set_color_pipeline(self.color_pipeline);
+    }
+
+    /// Samples used for anti-aliasing by ``render_image`` and ``save_frame``.
+    /// ``run_sketch`` sets it to the one of the window
+    pub fn set_multisampling(&mut self, multisampling: u16) {
+        self.multisampling = multisampling;
+    }
+
+    pub fn multisampling(&self) -> u16 {
+        self.multisampling
+    }
+
+    /// Frames per second of the sketch. ``run_sketch`` sets it from the settings
+    pub fn frame_rate(&self) -> f32 {
+        self.frame_rate
+    }
+
+    pub fn set_frame_rate(&mut self, frame_rate: f32) {
+        self.frame_rate = frame_rate.max(1.0);
+    }
+
+    /// Number of frames drawn before the current one
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    /// The OpenGL context the canvas draws in, which can be used as a ``Facade``
+    /// to create buffers and textures
+    pub fn context(&self) -> &Rc<Context> {
+        self.resources.context()
+    }
+
+    /// The programs and the meshes living on the GPU
+    pub fn resources(&mut self) -> &mut ResourceCache {
+        &mut self.resources
+    }
+
+    /// All of the shapes drawn so far in this frame, as vectors
+    pub fn recording(&self) -> &SvgRecording {
+        &self.recording
+    }
+
+    pub fn recording_mut(&mut self) -> &mut SvgRecording {
+        &mut self.recording
+    }
+
+    /// Forgets whatever was drawn before, and fills the whole canvas with ``color``
+    pub fn clear(&mut self, color: Color) {
+        self.discard();
+        self.background = Some(color);
+        self.recording.record_clear(color);
+    }
+
+    /// Draws the same shape passed to ``generate_draw_commands``
+    pub fn draw_primitive(
+        &mut self,
+        vertices: &[Vertex],
+        primitive: &ShapePrimitive,
+        style: &ShapeStyle,
+    ) {
+        if !self.matrix.is_identity() {
+            let vertices: Vec<Vertex> = vertices
+                .iter()
+                .map(|vertex| Vertex {
+                    position: self.matrix.apply(vertex.position),
+                })
+                .collect();
+            let style = self.transformed_style(style);
+            return self.add_primitive(&vertices, primitive, &style);
+        }
+
+        self.add_primitive(vertices, primitive, style);
+    }
+
+    fn add_primitive(
+        &mut self,
+        vertices: &[Vertex],
+        primitive: &ShapePrimitive,
+        style: &ShapeStyle,
+    ) {
+        self.add_to_batch(style, |batch, style| {
+            batch.add_primitive(vertices, primitive, style)
+        });
+        self.recording.record_primitive(vertices, primitive, style);
+    }
+
+    /// Draws a path, flattening its curves to a quarter of a pixel
+    pub fn draw_path(&mut self, path: &Path, style: &ShapeStyle) {
+        if !self.matrix.is_identity() {
+            let mut path = path.clone();
+            path.transform(&self.matrix);
+            let style = self.transformed_style(style);
+            return self.add_path(&path, &style);
+        }
+
+        self.add_path(path, style);
+    }
+
+    fn add_path(&mut self, path: &Path, style: &ShapeStyle) {
+        // The tolerance is in physical pixels, since those are the ones we see
+        let tolerance = self.pixels(ellipse::DEFAULT_TOLERANCE / self.scale_factor);
+        self.add_to_batch(style, |batch, style| batch.add_path(path, style, tolerance));
+        self.recording.record_path(path, style);
+    }
+
+    /// Each gradient needs its texture to be bound when drawing, so the fill and the stroke
+    /// of shapes painted with gradients are added one after the other
+    fn add_to_batch(&mut self, style: &ShapeStyle, add: impl Fn(&mut Batch, &ShapeStyle)) {
+        let has_gradients = [&style.fill, &style.stroke]
+            .into_iter()
+            .any(|paint| matches!(paint, Some(Paint::Gradient(_))));
+
+        if !has_gradients {
+            let start = self.batch.index_count();
+            add(&mut self.batch, style);
+            return self.push_triangles(start, style.blend_mode, None);
+        }
+
+        let fill = ShapeStyle {
+            stroke: None,
+            ..style.clone()
+        };
+        let stroke = ShapeStyle {
+            fill: None,
+            ..style.clone()
+        };
+        for (style, paint) in [(fill, &style.fill), (stroke, &style.stroke)] {
+            if let Some(paint) = paint {
+                let paint_texture = self.paint_texture(paint);
+                let start = self.batch.index_count();
+                add(&mut self.batch, &style);
+                self.push_triangles(start, style.blend_mode, paint_texture);
+            }
+        }
+    }
+
+    /// The texture the colors of ``paint`` are baked into, if it needs one
+    fn paint_texture(&mut self, paint: &Paint) -> Option<Rc<Texture2d>> {
+        let Paint::Gradient(gradient) = paint else {
+            return None;
+        };
+
+        let color_pipeline = self.color_pipeline;
+        Some(
+            self.resources
+                .texture(gradient.texture_key(color_pipeline), || {
+                    gradient.bake(color_pipeline)
+                }),
+        )
+    }
+
+    /// Strokes are scaled along with the shapes (and gradients moved with them).
+    /// They can't be stretched, so non uniform scales use their average
+    fn transformed_style(&self, style: &ShapeStyle) -> ShapeStyle {
+        let mut style = style.clone();
+        style.transform(&self.matrix);
+        style
+    }
+
+    pub fn draw_shape(&mut self, shape: &Shape) {
+        self.draw_path(&shape.path, &shape.style);
+    }
+
+    /// Draws one copy of ``mesh`` per instance (see ``instancing``), using the blend mode
+    /// of the current style. The recording gets the outline of each copy, filled with
+    /// the color of its instance, if the mesh has one (see ``GpuMesh::with_outline``)
+    pub fn draw_instances(&mut self, mesh: &Rc<GpuMesh>, instances: &[Instance]) {
+        let start = self.instances.len();
+        if self.matrix.is_identity() {
+            self.instances.extend_from_slice(instances);
+        } else {
+            for instance in instances {
+                self.instances.push(self.transformed_instance(instance));
+            }
+        }
+        self.items.push(DrawItem::Instances {
+            mesh: mesh.clone(),
+            range: start..self.instances.len(),
+            blend_mode: self.style.blend_mode,
+        });
+
+        if let Some(outline) = &mesh.outline {
+            let copies = self.instances.as_slice()[start..].iter().map(|instance| {
+                let [r, g, b, a] = instance.color;
+                (instance.transform(), Color::new(r, g, b, a))
+            });
+            self.recording
+                .record_instances(outline, copies, self.style.blend_mode);
+        }
+    }
+
+    /// The current matrix is applied after the transform of the instance
+    fn transformed_instance(&self, instance: &Instance) -> Instance {
+        let [[a, b], [c, d]] = instance.matrix;
+        let [e, f] = instance.translation;
+        let transform = self.matrix.multiply(&Transform::new(a, b, c, d, e, f));
+
+        Instance {
+            matrix: [[transform.a, transform.b], [transform.c, transform.d]],
+            translation: [transform.e, transform.f],
+            ..*instance
+        }
+    }
+
+    /// The transform applied to everything drawn
+    pub fn matrix(&self) -> &Transform {
+        &self.matrix
+    }
+
+    /// Saves the current matrix, to be restored by ``pop_matrix``
+    pub fn push_matrix(&mut self) {
+        self.matrix_stack.push(self.matrix);
+    }
+
+    /// Restores the matrix saved by the last ``push_matrix``
+    pub fn pop_matrix(&mut self) {
+        if let Some(matrix) = self.matrix_stack.pop() {
+            self.matrix = matrix;
+        }
+    }
+
+    /// Goes back to drawing without any transform
+    pub fn reset_matrix(&mut self) {
+        self.matrix = Transform::identity();
+    }
+
+    /// Like in Processing, ``transform`` applies to the shapes drawn next,
+    /// before the transforms set so far
+    pub fn apply_matrix(&mut self, transform: &Transform) {
+        self.matrix = self.matrix.multiply(transform);
+    }
+
+    pub fn translate(&mut self, x: f32, y: f32) {
+        self.apply_matrix(&Transform::translation(x, y));
+    }
+
+    /// Rotates by ``angle`` radians around the origin
+    pub fn rotate(&mut self, angle: f32) {
+        self.apply_matrix(&Transform::rotation(angle));
+    }
+
+    pub fn scale(&mut self, x: f32, y: f32) {
+        self.apply_matrix(&Transform::scaling(x, y));
+    }
+
+    /// Shears X by ``x_angle`` radians and Y by ``y_angle`` radians
+    pub fn shear(&mut self, x_angle: f32, y_angle: f32) {
+        self.apply_matrix(&Transform::shearing(x_angle, y_angle));
+    }
+
+    /// Saves both the style and the matrix
+    pub fn push(&mut self) {
+        self.push_style();
+        self.push_matrix();
+    }
+
+    /// Restores both the style and the matrix
+    pub fn pop(&mut self) {
+        self.pop_style();
+        self.pop_matrix();
+    }
+
+    /// The style used by ``rect``, ``ellipse``, ``end_shape`` and the other shapes below
+    pub fn style(&self) -> &ShapeStyle {
+        &self.style
+    }
+
+    pub fn set_style(&mut self, style: ShapeStyle) {
+        self.style = style;
+        self.stroke_weight = StrokeWeight::Explicit;
+    }
+
+    /// Saves the current style, to be restored by ``pop_style``
+    pub fn push_style(&mut self) {
+        self.style_stack
+            .push((self.style.clone(), self.stroke_weight));
+    }
+
+    /// Restores the style saved by the last ``push_style``
+    pub fn pop_style(&mut self) {
+        if let Some((style, stroke_weight)) = self.style_stack.pop() {
+            self.style = style;
+            self.stroke_weight = stroke_weight;
+        }
+    }
+
+    /// Same as ``clear``
+    pub fn background(&mut self, color: Color) {
+        self.clear(color);
+    }
+
+    /// Paints the shapes drawn next with a color or a gradient
+    pub fn fill(&mut self, paint: impl Into<Paint>) {
+        self.style.fill = Some(paint.into());
+    }
+
+    pub fn no_fill(&mut self) {
+        self.style.fill = None;
+    }
+
+    /// Outlines the shapes drawn next with a color or a gradient
+    pub fn stroke(&mut self, paint: impl Into<Paint>) {
+        self.style.stroke = Some(paint.into());
+    }
+
+    pub fn no_stroke(&mut self) {
+        self.style.stroke = None;
+    }
+
+    /// Width of the strokes, in drawing units (see ``pixels`` to convert from pixels)
+    pub fn stroke_weight(&mut self, weight: f32) {
+        self.style.stroke_width = weight;
+        self.stroke_weight = StrokeWeight::Explicit;
+    }
+
+    pub fn stroke_join(&mut self, line_join: LineJoin) {
+        self.style.line_join = line_join;
+    }
+
+    pub fn stroke_cap(&mut self, line_cap: LineCap) {
+        self.style.line_cap = line_cap;
+    }
+
+    /// Which parts of the paths drawn next are holes, when their sub-paths overlap
+    pub fn fill_rule(&mut self, fill_rule: FillRule) {
+        self.style.fill_rule = fill_rule;
+    }
+
+    /// How the shapes drawn next are combined with the ones below them
+    pub fn blend_mode(&mut self, blend_mode: BlendMode) {
+        self.style.blend_mode = blend_mode;
+    }
+
+    /// Rectangle with a corner in (``x``, ``y``)
+    pub fn rect(&mut self, x: f32, y: f32, width: f32, height: f32) {
+        let mut path = Path::new();
+        path.rect([x, y], [width, height]);
+        let style = self.style.clone();
+        self.draw_path(&path, &style);
+    }
+
+    /// Ellipse centered in (``x``, ``y``). Like in Processing, the size is the diameter
+    pub fn ellipse(&mut self, x: f32, y: f32, width: f32, height: f32) {
+        let mut path = Path::new();
+        path.ellipse([x, y], [width * 0.5, height * 0.5]);
+        let style = self.style.clone();
+        self.draw_path(&path, &style);
+    }
+
+    pub fn circle(&mut self, x: f32, y: f32, diameter: f32) {
+        self.ellipse(x, y, diameter, diameter);
+    }
+
+    /// Lines are never filled
+    pub fn line(&mut self, x1: f32, y1: f32, x2: f32, y2: f32) {
+        let mut path = Path::new();
+        path.polyline(&[[x1, y1], [x2, y2]]);
+        let style = ShapeStyle {
+            fill: None,
+            ..self.style.clone()
+        };
+        self.draw_path(&path, &style);
+    }
+
+    pub fn triangle(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x3: f32, y3: f32) {
+        let mut path = Path::new();
+        path.polygon(&[[x1, y1], [x2, y2], [x3, y3]]);
+        let style = self.style.clone();
+        self.draw_path(&path, &style);
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn quad(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x3: f32, y3: f32, x4: f32, y4: f32) {
+        let mut path = Path::new();
+        path.polygon(&[[x1, y1], [x2, y2], [x3, y3], [x4, y4]]);
+        let style = self.style.clone();
+        self.draw_path(&path, &style);
+    }
+
+    /// A dot as big as the stroke weight, painted with the stroke color
+    pub fn point(&mut self, x: f32, y: f32) {
+        let Some(stroke) = self.style.stroke.clone() else {
+            return;
+        };
+
+        let mut path = Path::new();
+        path.circle([x, y], self.style.stroke_width * 0.5);
+        let style = ShapeStyle::filled(stroke).with_blend_mode(self.style.blend_mode);
+        self.draw_path(&path, &style);
+    }
+
+    /// Starts a shape made of the vertices added with ``vertex`` (and the other
+    /// ``*_vertex`` methods), which is drawn by ``end_shape``
+    pub fn begin_shape(&mut self) {
+        self.shape = Some(ShapeBuilder {
+            path: Path::new(),
+            new_contour: true,
+        });
+    }
+
+    pub fn vertex(&mut self, x: f32, y: f32) {
+        if let Some(shape) = &mut self.shape {
+            if std::mem::take(&mut shape.new_contour) {
+                shape.path.move_to([x, y]);
+            } else {
+                shape.path.line_to([x, y]);
+            }
+        }
+    }
+
+    /// Quadratic bezier curve from the previous vertex to (``x``, ``y``)
+    pub fn quadratic_vertex(&mut self, control_x: f32, control_y: f32, x: f32, y: f32) {
+        if let Some(shape) = &mut self.shape {
+            shape.new_contour = false;
+            shape.path.quad_to([control_x, control_y], [x, y]);
+        }
+    }
+
+    /// Cubic bezier curve from the previous vertex to (``x``, ``y``)
+    pub fn bezier_vertex(
+        &mut self,
+        control_1_x: f32,
+        control_1_y: f32,
+        control_2_x: f32,
+        control_2_y: f32,
+        x: f32,
+        y: f32,
+    ) {
+        if let Some(shape) = &mut self.shape {
+            shape.new_contour = false;
+            shape.path.cubic_to(
+                [control_1_x, control_1_y],
+                [control_2_x, control_2_y],
+                [x, y],
+            );
+        }
+    }
+
+    /// The vertices that follow describe a new contour of the same shape.
+    /// Contours overlapping the first one become holes
+    pub fn begin_contour(&mut self) {
+        if let Some(shape) = &mut self.shape {
+            shape.new_contour = true;
+        }
+    }
+
+    /// Contours are always closed
+    pub fn end_contour(&mut self) {
+        if let Some(shape) = &mut self.shape {
+            shape.path.close();
+            shape.new_contour = true;
+        }
+    }
+
+    /// Draws the shape started by ``begin_shape``
+    pub fn end_shape(&mut self, end: EndShape) {
+        let Some(mut shape) = self.shape.take() else {
+            return;
+        };
+
+        if end == EndShape::Close && !shape.new_contour {
+            shape.path.close();
+        }
+        let style = self.style.clone();
+        self.draw_path(&shape.path, &style);
+    }
+
+    /// The triangles added to the batch from ``start`` onwards get drawn after
+    /// everything else. Consecutive shapes with the same blend mode end up
+    /// in the same draw call, as long as they don't need different gradient textures
+    fn push_triangles(
+        &mut self,
+        start: usize,
+        blend_mode: BlendMode,
+        paint_texture: Option<Rc<Texture2d>>,
+    ) {
+        let end = self.batch.index_count();
+        if start == end {
+            return;
+        }
+
+        if let Some(DrawItem::Triangles {
+            range,
+            blend_mode: previous_blend_mode,
+            paint_texture: previous_paint_texture,
+        }) = self.items.last_mut()
+        {
+            let compatible_textures = match (&previous_paint_texture, &paint_texture) {
+                (Some(previous), Some(texture)) => Rc::ptr_eq(previous, texture),
+                // Solid colors don't look at the texture
+                _ => true,
+            };
+            if range.end == start && *previous_blend_mode == blend_mode && compatible_textures {
+                range.end = end;
+                if previous_paint_texture.is_none() {
+                    *previous_paint_texture = paint_texture;
+                }
+                return;
+            }
+        }
+
+        self.items.push(DrawItem::Triangles {
+            range: start..end,
+            blend_mode,
+            paint_texture,
+        });
+    }
+
+    /// Forgets everything drawn in this frame
+    fn discard(&mut self) {
+        self.items.clear();
+        self.batch.clear();
+        self.instances.clear();
+        self.background = None;
+        self.recording.clear();
+    }
+
+    /// Gets the canvas ready to draw a new frame
+    pub fn begin_frame(&mut self) {
+        self.discard();
+        self.matrix = Transform::identity();
+        self.matrix_stack.clear();
+
+        // The recording is in logical pixels, with Y pointing down
+        let to_pixels = Transform::new(
+            self.width * 0.5,
+            0.0,
+            0.0,
+            -self.height * 0.5,
+            self.width * 0.5,
+            self.height * 0.5,
+        )
+        .multiply(&self.projection());
+        self.recording = SvgRecording::new(self.width, self.height, to_pixels);
+    }
+
+    /// Sends everything drawn in this frame to ``surface``.
+    /// The frame isn't lost, so it can be rendered again on other surfaces
+    pub fn render<S: glium::Surface>(&mut self, surface: &mut S) -> Result<(), InstancingError> {
+        self.render_viewport(surface, &Transform::identity())
+    }
+
+    /// Like ``render``, with ``viewport`` applied after the projection: it maps the part
+    /// of the frame ``surface`` should show to normalized device coordinates
+    pub(crate) fn render_viewport<S: glium::Surface>(
+        &mut self,
+        surface: &mut S,
+        viewport: &Transform,
+    ) -> Result<(), InstancingError> {
+        if let Some(background) = self.background {
+            // Like everything else, the framebuffer holds premultiplied colors
+            let [r, g, b, a] = self.color_pipeline.upload(background);
+            match self.color_pipeline {
+                ColorPipeline::Linear => surface.clear_color_srgb(r * a, g * a, b * a, a),
+                ColorPipeline::Srgb => surface.clear_color(r * a, g * a, b * a, a),
+            }
+        }
+
+        let draw_parameters = |blend_mode: &BlendMode| glium::DrawParameters {
+            multisampling: true,
+            blend: blend_mode.blend(),
+            ..Default::default()
+        };
+        let projection = viewport.multiply(&self.projection()).to_mat3();
+        let uniforms = glium::uniform! {
+            projection: projection,
+        };
+
+        for item in &self.items {
+            match item {
+                DrawItem::Triangles {
+                    range,
+                    blend_mode,
+                    paint_texture,
+                } => {
+                    let paint_texture = paint_texture
+                        .as_deref()
+                        .unwrap_or(&self.blank_texture)
+                        .sampled()
+                        .wrap_function(SamplerWrapFunction::Clamp)
+                        .minify_filter(MinifySamplerFilter::Linear)
+                        .magnify_filter(MagnifySamplerFilter::Linear);
+                    let uniforms = glium::uniform! {
+                        projection: projection,
+                        paint_texture: paint_texture,
+                    };
+                    self.batch.draw_range(
+                        surface,
+                        &self.batch_program,
+                        &uniforms,
+                        range.clone(),
+                        &draw_parameters(blend_mode),
+                    )?
+                }
+                DrawItem::Instances {
+                    mesh,
+                    range,
+                    blend_mode,
+                } => self.instances.draw_range(
+                    surface,
+                    &self.instanced_program,
+                    &uniforms,
+                    mesh,
+                    range.clone(),
+                    &draw_parameters(blend_mode),
+                )?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Renders the frame into a texture the size of the window (in physical pixels,
+    /// with the anti-aliasing set by ``set_multisampling``) and reads it back.
+    /// Without a background, the pixels that weren't drawn are transparent
+    pub fn render_image(&mut self) -> Result<RgbaImage, OffscreenError> {
+        let width = (self.width * self.scale_factor).round().max(1.0) as u32;
+        let height = (self.height * self.scale_factor).round().max(1.0) as u32;
+        let target = OffscreenTarget::new(self.context(), width, height, self.multisampling)?;
+
+        target.draw(|surface| {
+            glium::Surface::clear_color(surface, 0.0, 0.0, 0.0, 0.0);
+            self.render(surface)
+        })?;
+        Ok(target.read_pixels(self.color_pipeline))
+    }
+
+    /// Saves the frame as a PNG, at the path described by ``pattern``
+    /// (see ``capture::frame_path``), and returns that path.
+    /// Call it at the end of ``Sketch::draw``, or between two frames
+    pub fn save_frame(&mut self, pattern: &str) -> Result<PathBuf, OffscreenError> {
+        let path = frame_path(pattern, self.frame_count);
+        self.render_image()?.save_png(&path)?;
+        Ok(path)
+    }
+
+    /// Starts writing every frame to disk, see ``recording``. A recording in progress
+    /// is replaced: call ``stop_recording`` first to know whether it completed
+    pub fn start_recording(&mut self, settings: RecordingSettings) {
+        self.recorder = Some(Recorder::new(settings, self.frame_rate));
+    }
+
+    /// Stops the recording and completes its video or animation, if any.
+    /// Returns it to know how many frames were written
+    pub fn stop_recording(&mut self) -> std::io::Result<Option<Recorder>> {
+        let Some(mut recorder) = self.recorder.take() else {
+            return Ok(None);
+        };
+        recorder.finish()?;
+        Ok(Some(recorder))
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    pub fn recorder(&self) -> Option<&Recorder> {
+        self.recorder.as_ref()
+    }
+
+    /// Renders the frame offscreen and writes it as the next frame of the recording.
+    /// Returns the path of the file, or ``None`` when not recording
+    pub fn record_frame(&mut self) -> Result<Option<PathBuf>, OffscreenError> {
+        if !self.is_recording() {
+            return Ok(None);
+        }
+
+        let image = self.render_image()?;
+        Ok(self.record_image(&image)?)
+    }
+
+    /// Writes ``image`` (the frame, rendered by the caller) to the recording,
+    /// which stops once it has enough frames
+    pub(crate) fn record_image(&mut self, image: &RgbaImage) -> std::io::Result<Option<PathBuf>> {
+        let Some(recorder) = &mut self.recorder else {
+            return Ok(None);
+        };
+
+        let path = recorder.save(image)?;
+        if recorder.is_finished() {
+            self.stop_recording()?;
+        }
+        Ok(Some(path))
+    }
+
+    /// Must be called once the frame has been rendered
+    pub fn end_frame(&mut self) {
+        self.resources.end_frame();
+        self.frame_count += 1;
+    }
+}
+
+/// The programs used to draw the batch and the instances.
+/// The shaders are tiny and written by us, so failing to compile them is a bug
+fn load_programs(
+    resources: &mut ResourceCache,
+    color_pipeline: ColorPipeline,
+) -> (Rc<glium::Program>, Rc<glium::Program>) {
+    let batch_program = resources
+        .program_for_pipeline(
+            "batch",
+            BATCH_VERTEX_SHADER_SRC,
+            BATCH_FRAGMENT_SHADER_SRC,
+            color_pipeline,
+        )
+        .unwrap();
+    let instanced_program = resources
+        .program_for_pipeline(
+            "instanced",
+            INSTANCED_VERTEX_SHADER_SRC,
+            INSTANCED_FRAGMENT_SHADER_SRC,
+            color_pipeline,
+        )
+        .unwrap();
+
+    (batch_program, instanced_program)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless::create_context;
+
+    /// A 200x100 canvas, or ``None`` where OpenGL isn't available
+    fn canvas() -> Option<Canvas> {
+        let context = create_context([200, 100]).ok()?;
+        Some(Canvas::with_facade(&context, [200, 100], 1.0, true))
+    }
+
+    #[test]
+    fn default_stroke_weight_follows_the_coordinate_mode() {
+        let Some(mut canvas) = canvas() else {
+            return;
+        };
+        // Normalized coordinates are 2 units high, so a pixel is 2 / 100 units
+        assert_eq!(canvas.style().stroke_width, 0.02);
+
+        canvas.push_style();
+        canvas.set_coordinate_mode(CoordinateMode::Pixels);
+        assert_eq!(canvas.style().stroke_width, 1.0);
+        canvas.pop_style();
+        assert_eq!(canvas.style().stroke_width, 1.0);
+    }
+
+    #[test]
+    fn explicit_stroke_weight_is_kept_when_the_coordinate_mode_changes() {
+        let Some(mut canvas) = canvas() else {
+            return;
+        };
+        // One pixel on purpose, which isn't the default anymore
+        canvas.stroke_weight(canvas.pixels(1.0));
+        canvas.push_style();
+        canvas.stroke_weight(0.5);
+
+        canvas.set_coordinate_mode(CoordinateMode::Pixels);
+        assert_eq!(canvas.style().stroke_width, 0.5);
+        canvas.pop_style();
+        assert_eq!(canvas.style().stroke_width, 0.02);
+    }
+}
@@ -0,0 +1,115 @@
+//! Naming the images saved from a sketch.
+//!
+//! Paths passed to ``Canvas::save_frame`` are patterns, like in Processing:
+//! runs of ``#`` are replaced by the frame number (padded with zeros to the length
+//! of the run), and ``{timestamp}`` by the current date and time, so saving
+//! the same sketch over and over doesn't overwrite the previous images.
+
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Used by the S, V, H and G keys of ``run_sketch`` (with the extension of each format)
+pub const DEFAULT_SCREENSHOT_PATTERN: &str = "frame-{timestamp}-####.png";
+
+/// The path described by ``pattern`` for the frame number ``frame``.
+/// Numbers longer than a run of ``#`` are written in full, never cut
+pub fn frame_path(pattern: &str, frame: u64) -> PathBuf {
+    let pattern = pattern.replace("{timestamp}", &timestamp());
+
+    let mut path = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '#' {
+            path.push(c);
+            continue;
+        }
+
+        let mut width = 1;
+        while chars.next_if_eq(&'#').is_some() {
+            width += 1;
+        }
+        path += &format!("{frame:0width$}");
+    }
+
+    PathBuf::from(path)
+}
+
+/// The current date and time as ``YYYYMMDD-HHMMSS``, in UTC
+pub fn timestamp() -> String {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    timestamp_at(seconds)
+}
+
+/// The date and time ``seconds`` after the Unix epoch, formatted like ``timestamp``
+fn timestamp_at(seconds: u64) -> String {
+    let (days, seconds) = (seconds / 86_400, seconds % 86_400);
+
+    // Days since the epoch to a date of the proleptic Gregorian calendar, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{year:04}{month:02}{day:02}-{:02}{:02}{:02}",
+        seconds / 3_600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_of_hashes_are_padded_to_their_length() {
+        assert_eq!(
+            frame_path("frame-####.png", 42),
+            PathBuf::from("frame-0042.png")
+        );
+        assert_eq!(frame_path("#.png", 7), PathBuf::from("7.png"));
+        assert_eq!(frame_path("#-##-###", 5), PathBuf::from("5-05-005"));
+        assert_eq!(frame_path("still.png", 3), PathBuf::from("still.png"));
+    }
+
+    #[test]
+    fn numbers_wider_than_the_padding_are_kept_whole() {
+        assert_eq!(
+            frame_path("frame-##.png", 12345),
+            PathBuf::from("frame-12345.png")
+        );
+    }
+
+    #[test]
+    fn timestamps_replace_their_placeholder() {
+        let path = frame_path("{timestamp}-#", 1);
+        let name = path.to_str().unwrap();
+        assert_eq!(name.len(), "YYYYMMDD-HHMMSS-1".len());
+        assert!(!name.contains('{'));
+        assert!(name.ends_with("-1"));
+    }
+
+    #[test]
+    fn timestamps_are_utc_dates() {
+        assert_eq!(timestamp_at(0), "19700101-000000");
+        assert_eq!(timestamp_at(86_399), "19700101-235959");
+        // 2000 was a leap year, unlike 1900 and 2100
+        assert_eq!(timestamp_at(951_782_400), "20000229-000000");
+        assert_eq!(timestamp_at(1_709_210_096), "20240229-123456");
+        assert_eq!(timestamp_at(4_107_542_400), "21000301-000000");
+    }
+}
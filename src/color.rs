@@ -0,0 +1,601 @@
+//! Colors, and the color spaces sketches like to work in.
+//!
+//! A ``Color`` stores sRGB channels (the ones found in hex codes and color pickers),
+//! plus an alpha channel, all of them between 0 and 1. Conversions from other
+//! color spaces can go out of that range when the color can't be displayed:
+//! ``clamped`` brings it back.
+
+mod named;
+
+use std::fmt;
+use std::str::FromStr;
+
+// Custom structs required to provide a more friendly
+// abstraction on top of the inner working of OpenGL
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseColorError {
+    /// The string that couldn't be parsed
+    pub input: String,
+}
+
+impl fmt::Display for ParseColorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid color: {:?}", self.input)
+    }
+}
+
+impl std::error::Error for ParseColorError {}
+
+impl Color {
+    pub fn new(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self { r, g, b, a }
+    }
+
+    pub fn as_tuple(&self) -> (f32, f32, f32, f32) {
+        (self.r, self.g, self.b, self.a)
+    }
+
+    /// The red, green and blue channels multiplied by the alpha channel
+    pub fn premultiplied(&self) -> Self {
+        Self::new(self.r * self.a, self.g * self.a, self.b * self.a, self.a)
+    }
+
+    pub fn with_alpha(mut self, alpha: f32) -> Self {
+        self.a = alpha;
+        self
+    }
+
+    /// Every channel brought back between 0 and 1
+    pub fn clamped(&self) -> Self {
+        Self::new(
+            self.r.clamp(0.0, 1.0),
+            self.g.clamp(0.0, 1.0),
+            self.b.clamp(0.0, 1.0),
+            self.a.clamp(0.0, 1.0),
+        )
+    }
+
+    /// Channels between 0 and 255, like the ones of most image formats
+    pub fn from_rgba8(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self::new(
+            r as f32 / 255.0,
+            g as f32 / 255.0,
+            b as f32 / 255.0,
+            a as f32 / 255.0,
+        )
+    }
+
+    pub fn to_rgba8(&self) -> [u8; 4] {
+        let to_u8 = |channel: f32| (channel.clamp(0.0, 1.0) * 255.0).round() as u8;
+        [to_u8(self.r), to_u8(self.g), to_u8(self.b), to_u8(self.a)]
+    }
+
+    /// Parses ``#rgb``, ``#rgba``, ``#rrggbb`` or ``#rrggbbaa`` (the ``#`` is optional)
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        let hex = hex.trim();
+        let hex = hex.strip_prefix('#').unwrap_or(hex);
+        let digits: Vec<u8> = hex
+            .chars()
+            .map(|digit| digit.to_digit(16).map(|digit| digit as u8))
+            .collect::<Option<_>>()?;
+
+        let channels: Vec<u8> = match digits.len() {
+            3 | 4 => digits.iter().map(|digit| digit * 17).collect(),
+            6 | 8 => digits
+                .chunks_exact(2)
+                .map(|pair| pair[0] * 16 + pair[1])
+                .collect(),
+            _ => return None,
+        };
+        let alpha = channels.get(3).copied().unwrap_or(255);
+
+        Some(Self::from_rgba8(
+            channels[0],
+            channels[1],
+            channels[2],
+            alpha,
+        ))
+    }
+
+    /// ``#rrggbb``, or ``#rrggbbaa`` if the color isn't opaque
+    pub fn to_hex(&self) -> String {
+        let [r, g, b, a] = self.to_rgba8();
+        if a == 255 {
+            format!("#{r:02x}{g:02x}{b:02x}")
+        } else {
+            format!("#{r:02x}{g:02x}{b:02x}{a:02x}")
+        }
+    }
+
+    /// One of the named colors of CSS, like ``"tomato"``
+    pub fn named(name: &str) -> Option<Self> {
+        let [r, g, b] = named::lookup(&name.trim().to_ascii_lowercase())?;
+        Some(Self::from_rgba8(r, g, b, 255))
+    }
+
+    /// ``hue`` is in degrees, ``saturation`` and ``value`` between 0 and 1
+    pub fn from_hsv(hue: f32, saturation: f32, value: f32, alpha: f32) -> Self {
+        let chroma = value * saturation;
+        let [r, g, b] = hue_to_rgb(hue, chroma);
+        let lightest = value - chroma;
+        Self::new(r + lightest, g + lightest, b + lightest, alpha)
+    }
+
+    /// Hue (in degrees), saturation and value. Grays have a hue of 0
+    pub fn to_hsv(&self) -> [f32; 3] {
+        let (hue, chroma, max, _) = self.hue_chroma();
+        let saturation = if max > 0.0 { chroma / max } else { 0.0 };
+        [hue, saturation, max]
+    }
+
+    /// ``hue`` is in degrees, ``saturation`` and ``lightness`` between 0 and 1
+    pub fn from_hsl(hue: f32, saturation: f32, lightness: f32, alpha: f32) -> Self {
+        let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+        let [r, g, b] = hue_to_rgb(hue, chroma);
+        let lightest = lightness - chroma * 0.5;
+        Self::new(r + lightest, g + lightest, b + lightest, alpha)
+    }
+
+    /// Hue (in degrees), saturation and lightness. Grays have a hue of 0
+    pub fn to_hsl(&self) -> [f32; 3] {
+        let (hue, chroma, max, min) = self.hue_chroma();
+        let lightness = (max + min) * 0.5;
+        let saturation = if lightness > 0.0 && lightness < 1.0 {
+            chroma / (1.0 - (2.0 * lightness - 1.0).abs())
+        } else {
+            0.0
+        };
+        [hue, saturation, lightness]
+    }
+
+    /// Hue in degrees, chroma, largest and smallest channel
+    fn hue_chroma(&self) -> (f32, f32, f32, f32) {
+        let max = self.r.max(self.g).max(self.b);
+        let min = self.r.min(self.g).min(self.b);
+        let chroma = max - min;
+
+        let sector = if chroma == 0.0 {
+            0.0
+        } else if max == self.r {
+            ((self.g - self.b) / chroma).rem_euclid(6.0)
+        } else if max == self.g {
+            (self.b - self.r) / chroma + 2.0
+        } else {
+            (self.r - self.g) / chroma + 4.0
+        };
+
+        (sector * 60.0, chroma, max, min)
+    }
+
+    /// Channels proportional to the amount of light, which is what blending
+    /// and lighting math expects (the alpha channel is left untouched)
+    pub fn from_linear_rgb(r: f32, g: f32, b: f32, alpha: f32) -> Self {
+        Self::new(
+            linear_to_srgb(r),
+            linear_to_srgb(g),
+            linear_to_srgb(b),
+            alpha,
+        )
+    }
+
+    pub fn to_linear_rgb(&self) -> [f32; 3] {
+        [
+            srgb_to_linear(self.r),
+            srgb_to_linear(self.g),
+            srgb_to_linear(self.b),
+        ]
+    }
+
+    /// OKLab is a perceptual color space: ``lightness`` goes from 0 (black) to 1 (white),
+    /// ``a`` from green to red and ``b`` from blue to yellow (both roughly within ±0.4).
+    /// See https://bottosson.github.io/posts/oklab/
+    pub fn from_oklab(lightness: f32, a: f32, b: f32, alpha: f32) -> Self {
+        let l = (lightness + 0.396_337_78 * a + 0.215_803_76 * b).powi(3);
+        let m = (lightness - 0.105_561_346 * a - 0.063_854_17 * b).powi(3);
+        let s = (lightness - 0.089_484_18 * a - 1.291_485_5 * b).powi(3);
+
+        Self::from_linear_rgb(
+            4.076_741_7 * l - 3.307_711_6 * m + 0.230_969_94 * s,
+            -1.268_438 * l + 2.609_757_4 * m - 0.341_319_38 * s,
+            -0.004_196_086_3 * l - 0.703_418_6 * m + 1.707_614_7 * s,
+            alpha,
+        )
+    }
+
+    /// Lightness, a and b (see ``from_oklab``)
+    pub fn to_oklab(&self) -> [f32; 3] {
+        let [r, g, b] = self.to_linear_rgb();
+        let l = (0.412_221_46 * r + 0.536_332_55 * g + 0.051_445_995 * b).cbrt();
+        let m = (0.211_903_5 * r + 0.680_699_5 * g + 0.107_396_96 * b).cbrt();
+        let s = (0.088_302_46 * r + 0.281_718_85 * g + 0.629_978_7 * b).cbrt();
+
+        [
+            0.210_454_26 * l + 0.793_617_8 * m - 0.004_072_047 * s,
+            1.977_998_5 * l - 2.428_592_2 * m + 0.450_593_7 * s,
+            0.025_904_037 * l + 0.782_771_77 * m - 0.808_675_77 * s,
+        ]
+    }
+
+    /// OKLab in polar coordinates: ``chroma`` is the distance from the grays
+    /// (up to about 0.4) and ``hue`` an angle in degrees
+    pub fn from_oklch(lightness: f32, chroma: f32, hue: f32, alpha: f32) -> Self {
+        let (sin, cos) = hue.to_radians().sin_cos();
+        Self::from_oklab(lightness, chroma * cos, chroma * sin, alpha)
+    }
+
+    /// Lightness, chroma and hue (in degrees, between 0 and 360)
+    pub fn to_oklch(&self) -> [f32; 3] {
+        let [lightness, a, b] = self.to_oklab();
+        let hue = b.atan2(a).to_degrees().rem_euclid(360.0);
+        [lightness, a.hypot(b), hue]
+    }
+
+    /// Mixes the sRGB channels: ``t`` = 0 gives ``self``, 1 gives ``other``
+    pub fn lerp(&self, other: &Color, t: f32) -> Self {
+        Self::new(
+            lerp(self.r, other.r, t),
+            lerp(self.g, other.g, t),
+            lerp(self.b, other.b, t),
+            lerp(self.a, other.a, t),
+        )
+    }
+
+    /// Mixes the colors in OKLab, which avoids the muddy grays found halfway
+    /// between complementary colors when mixing them in sRGB
+    pub fn lerp_oklab(&self, other: &Color, t: f32) -> Self {
+        let [l1, a1, b1] = self.to_oklab();
+        let [l2, a2, b2] = other.to_oklab();
+        Self::from_oklab(
+            lerp(l1, l2, t),
+            lerp(a1, a2, t),
+            lerp(b1, b2, t),
+            lerp(self.a, other.a, t),
+        )
+    }
+}
+
+impl FromStr for Color {
+    type Err = ParseColorError;
+
+    /// Parses CSS colors: hex codes, named colors, ``transparent``, and the
+    /// ``rgb()``, ``rgba()``, ``hsl()``, ``hsla()``, ``oklab()`` and ``oklch()`` functions
+    /// (with either commas or spaces between the arguments)
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let value = input.trim().to_ascii_lowercase();
+
+        let color = if value.starts_with('#') {
+            Color::from_hex(&value)
+        } else if value == "transparent" {
+            Some(Color::new(0.0, 0.0, 0.0, 0.0))
+        } else if let Some((function, arguments)) = value
+            .strip_suffix(')')
+            .and_then(|rest| rest.split_once('('))
+        {
+            parse_color_function(function.trim(), arguments)
+        } else {
+            Color::named(&value)
+        };
+
+        color.map(|color| color.clamped()).ok_or(ParseColorError {
+            input: input.to_string(),
+        })
+    }
+}
+
+/// The channels of a color of the given ``hue`` (in degrees) and ``chroma``,
+/// before adding the gray that gives it its lightness
+fn hue_to_rgb(hue: f32, chroma: f32) -> [f32; 3] {
+    let sector = hue.rem_euclid(360.0) / 60.0;
+    let x = chroma * (1.0 - (sector.rem_euclid(2.0) - 1.0).abs());
+
+    match sector as u32 {
+        0 => [chroma, x, 0.0],
+        1 => [x, chroma, 0.0],
+        2 => [0.0, chroma, x],
+        3 => [0.0, x, chroma],
+        4 => [x, 0.0, chroma],
+        _ => [chroma, 0.0, x],
+    }
+}
+
+fn lerp(from: f32, to: f32, t: f32) -> f32 {
+    from + (to - from) * t
+}
+
+/// The sRGB transfer function, undone. Negative values are mirrored
+pub fn srgb_to_linear(channel: f32) -> f32 {
+    let magnitude = channel.abs();
+    let linear = if magnitude <= 0.040_45 {
+        magnitude / 12.92
+    } else {
+        ((magnitude + 0.055) / 1.055).powf(2.4)
+    };
+    linear.copysign(channel)
+}
+
+/// The sRGB transfer function. Negative values are mirrored
+pub fn linear_to_srgb(channel: f32) -> f32 {
+    let magnitude = channel.abs();
+    let srgb = if magnitude <= 0.003_130_8 {
+        magnitude * 12.92
+    } else {
+        1.055 * magnitude.powf(1.0 / 2.4) - 0.055
+    };
+    srgb.copysign(channel)
+}
+
+/// ``arguments`` are the ones between the parentheses of ``function``
+fn parse_color_function(function: &str, arguments: &str) -> Option<Color> {
+    // Both the legacy "rgb(255, 0, 0, 0.5)" and the modern "rgb(255 0 0 / 50%)" syntaxes
+    let arguments: Vec<&str> = if arguments.contains(',') {
+        arguments.split(',').map(str::trim).collect()
+    } else {
+        arguments
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .filter(|argument| !argument.is_empty())
+            .collect()
+    };
+
+    let (channels, alpha) = match arguments.as_slice() {
+        [x, y, z] => ([*x, *y, *z], 1.0),
+        [x, y, z, alpha] => ([*x, *y, *z], parse_number(alpha, 1.0)?),
+        _ => return None,
+    };
+    let [x, y, z] = channels;
+
+    match function {
+        "rgb" | "rgba" => Some(Color::new(
+            parse_number(x, 255.0)? / 255.0,
+            parse_number(y, 255.0)? / 255.0,
+            parse_number(z, 255.0)? / 255.0,
+            alpha,
+        )),
+        "hsl" | "hsla" => Some(Color::from_hsl(
+            parse_angle(x)?,
+            parse_number(y, 100.0)? / 100.0,
+            parse_number(z, 100.0)? / 100.0,
+            alpha,
+        )),
+        "oklab" => Some(Color::from_oklab(
+            parse_number(x, 1.0)?,
+            parse_number(y, 0.4)?,
+            parse_number(z, 0.4)?,
+            alpha,
+        )),
+        "oklch" => Some(Color::from_oklch(
+            parse_number(x, 1.0)?,
+            parse_number(y, 0.4)?,
+            parse_angle(z)?,
+            alpha,
+        )),
+        _ => None,
+    }
+}
+
+/// A number, or a percentage of ``full``
+fn parse_number(value: &str, full: f32) -> Option<f32> {
+    match value.strip_suffix('%') {
+        Some(percentage) => percentage.parse::<f32>().ok().map(|p| p / 100.0 * full),
+        None => value.parse().ok(),
+    }
+}
+
+/// An angle in degrees, unless it has a unit
+fn parse_angle(value: &str) -> Option<f32> {
+    let units = [
+        ("deg", 1.0),
+        ("grad", 0.9),
+        ("rad", 180.0 / std::f32::consts::PI),
+        ("turn", 360.0),
+    ];
+
+    for (suffix, scale) in units {
+        if let Some(number) = value.strip_suffix(suffix) {
+            return number.parse::<f32>().ok().map(|n| n * scale);
+        }
+    }
+
+    value.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-4;
+
+    fn assert_close(actual: &[f32], expected: &[f32]) {
+        for (a, e) in actual.iter().zip(expected) {
+            assert!(
+                (a - e).abs() < EPSILON,
+                "expected {expected:?}, got {actual:?}"
+            );
+        }
+    }
+
+    fn assert_same_color(actual: Color, expected: Color) {
+        let channels = |color: Color| [color.r, color.g, color.b, color.a];
+        assert_close(&channels(actual), &channels(expected));
+    }
+
+    /// A few colors covering every hue sector, plus the extremes
+    fn samples() -> Vec<Color> {
+        vec![
+            Color::new(0.0, 0.0, 0.0, 1.0),
+            Color::new(1.0, 1.0, 1.0, 1.0),
+            Color::new(0.5, 0.5, 0.5, 0.5),
+            Color::new(1.0, 0.0, 0.0, 1.0),
+            Color::new(0.9, 0.7, 0.1, 1.0),
+            Color::new(0.2, 0.8, 0.3, 0.25),
+            Color::new(0.1, 0.6, 0.9, 1.0),
+            Color::new(0.3, 0.1, 0.7, 1.0),
+            Color::new(0.8, 0.2, 0.6, 0.0),
+        ]
+    }
+
+    #[test]
+    fn hex_round_trip() {
+        for color in samples() {
+            let [r, g, b, a] = color.to_rgba8();
+            let quantized = Color::from_rgba8(r, g, b, a);
+            assert_eq!(Color::from_hex(&quantized.to_hex()), Some(quantized));
+        }
+    }
+
+    #[test]
+    fn hex_parsing() {
+        assert_eq!(
+            Color::from_hex("#ff8000"),
+            Some(Color::from_rgba8(255, 128, 0, 255))
+        );
+        assert_eq!(
+            Color::from_hex("F80"),
+            Some(Color::from_rgba8(255, 136, 0, 255))
+        );
+        assert_eq!(
+            Color::from_hex("#ff800080"),
+            Some(Color::from_rgba8(255, 128, 0, 128))
+        );
+        assert_eq!(
+            Color::from_hex("#f808"),
+            Some(Color::from_rgba8(255, 136, 0, 136))
+        );
+        assert_eq!(
+            Color::from_hex("#ff80"),
+            Some(Color::from_rgba8(255, 255, 136, 0))
+        );
+        assert_eq!(Color::from_hex("#ff80z0"), None);
+        assert_eq!(Color::from_hex("#ff800"), None);
+    }
+
+    #[test]
+    fn hsv_round_trip() {
+        for color in samples() {
+            let [h, s, v] = color.to_hsv();
+            assert_same_color(Color::from_hsv(h, s, v, color.a), color);
+        }
+        assert_close(&Color::new(1.0, 0.0, 0.0, 1.0).to_hsv(), &[0.0, 1.0, 1.0]);
+        assert_close(&Color::new(0.0, 0.5, 0.5, 1.0).to_hsv(), &[180.0, 1.0, 0.5]);
+    }
+
+    #[test]
+    fn hsl_round_trip() {
+        for color in samples() {
+            let [h, s, l] = color.to_hsl();
+            assert_same_color(Color::from_hsl(h, s, l, color.a), color);
+        }
+        assert_close(&Color::new(0.0, 0.0, 1.0, 1.0).to_hsl(), &[240.0, 1.0, 0.5]);
+        assert_same_color(
+            Color::from_hsl(-60.0, 1.0, 0.5, 1.0),
+            Color::new(1.0, 0.0, 1.0, 1.0),
+        );
+    }
+
+    #[test]
+    fn linear_round_trip() {
+        for color in samples() {
+            let [r, g, b] = color.to_linear_rgb();
+            assert_same_color(Color::from_linear_rgb(r, g, b, color.a), color);
+        }
+        assert_close(
+            &Color::new(0.5, 0.5, 0.5, 1.0).to_linear_rgb(),
+            &[0.21404; 3],
+        );
+    }
+
+    #[test]
+    fn oklab_round_trip() {
+        for color in samples() {
+            let [l, a, b] = color.to_oklab();
+            assert_same_color(Color::from_oklab(l, a, b, color.a), color);
+        }
+        // Reference values from https://bottosson.github.io/posts/oklab/
+        assert_close(&Color::new(1.0, 1.0, 1.0, 1.0).to_oklab(), &[1.0, 0.0, 0.0]);
+        assert_close(
+            &Color::new(1.0, 0.0, 0.0, 1.0).to_oklab(),
+            &[0.627_955, 0.224_863, 0.125_846],
+        );
+    }
+
+    #[test]
+    fn oklch_round_trip() {
+        for color in samples() {
+            let [l, c, h] = color.to_oklch();
+            assert_same_color(Color::from_oklch(l, c, h, color.a), color);
+        }
+        let [_, chroma, _] = Color::new(0.5, 0.5, 0.5, 1.0).to_oklch();
+        assert!(chroma < EPSILON);
+    }
+
+    #[test]
+    fn oklab_interpolation() {
+        let red = Color::new(1.0, 0.0, 0.0, 1.0);
+        let blue = Color::new(0.0, 0.0, 1.0, 0.0);
+        assert_same_color(red.lerp_oklab(&blue, 0.0), red);
+        assert_same_color(red.lerp_oklab(&blue, 1.0), blue);
+
+        // Halfway, the lightness is halfway too
+        let [l_red, ..] = red.to_oklab();
+        let [l_blue, ..] = blue.to_oklab();
+        let middle = red.lerp_oklab(&blue, 0.5);
+        assert_close(
+            &[middle.to_oklab()[0], middle.a],
+            &[(l_red + l_blue) * 0.5, 0.5],
+        );
+    }
+
+    #[test]
+    fn css_parsing() {
+        let parse = |value: &str| value.parse::<Color>().unwrap();
+
+        assert_same_color(parse("#ff0000"), Color::new(1.0, 0.0, 0.0, 1.0));
+        assert_same_color(
+            parse(" RebeccaPurple "),
+            Color::from_rgba8(102, 51, 153, 255),
+        );
+        assert_same_color(parse("transparent"), Color::new(0.0, 0.0, 0.0, 0.0));
+        assert_same_color(parse("rgb(255, 0, 51)"), Color::new(1.0, 0.0, 0.2, 1.0));
+        assert_same_color(
+            parse("rgba(255, 0, 51, 0.5)"),
+            Color::new(1.0, 0.0, 0.2, 0.5),
+        );
+        assert_same_color(
+            parse("rgb(100% 0% 20% / 50%)"),
+            Color::new(1.0, 0.0, 0.2, 0.5),
+        );
+        assert_same_color(parse("rgb(300, -5, 0)"), Color::new(1.0, 0.0, 0.0, 1.0));
+        assert_same_color(parse("hsl(120, 100%, 50%)"), Color::new(0.0, 1.0, 0.0, 1.0));
+        assert_same_color(
+            parse("hsl(0.5turn 100% 25%)"),
+            Color::new(0.0, 0.5, 0.5, 1.0),
+        );
+        assert_same_color(parse("oklab(1 0 0)"), Color::new(1.0, 1.0, 1.0, 1.0));
+        assert_same_color(
+            parse("oklch(62.7955% 0.257683 29.2339deg)"),
+            Color::new(1.0, 0.0, 0.0, 1.0),
+        );
+
+        assert!("#12".parse::<Color>().is_err());
+        assert!("notacolor".parse::<Color>().is_err());
+        assert!("rgb(1, 2)".parse::<Color>().is_err());
+        assert!("cmyk(1, 2, 3, 4)".parse::<Color>().is_err());
+    }
+
+    #[test]
+    fn named_colors() {
+        assert_eq!(Color::named("black"), Some(Color::new(0.0, 0.0, 0.0, 1.0)));
+        assert_eq!(Color::named("aliceblue"), Color::from_hex("#f0f8ff"));
+        assert_eq!(Color::named("yellowgreen"), Color::from_hex("#9acd32"));
+        assert_eq!(Color::named("Gray"), Color::from_hex("#808080"));
+        assert_eq!(Color::named("grey"), Color::named("gray"));
+        assert_eq!(Color::named("blurple"), None);
+    }
+}
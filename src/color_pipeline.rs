@@ -0,0 +1,114 @@
+//! How colors travel from the sketch to the screen.
+//!
+//! Colors are picked in sRGB, but light adds up linearly: blending two sRGB values
+//! (or interpolating between them) gives edges and gradients that look too dark.
+//! With the ``Linear`` pipeline, colors are converted to linear values when they are
+//! uploaded to the GPU, blending happens on linear values, and the sRGB framebuffer
+//! converts the result back to sRGB when writing it. Either way, a solid color ends up
+//! on screen with the exact value it was picked with.
+
+use crate::color::Color;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub enum ColorPipeline {
+    /// Blending happens in linear light. Needs an sRGB framebuffer
+    #[default]
+    Linear,
+    /// Colors are written to the framebuffer untouched, and blending happens
+    /// on sRGB values, like in most 2D drawing programs
+    Srgb,
+}
+
+impl ColorPipeline {
+    /// ``Linear`` if the framebuffer of ``display`` is sRGB, ``Srgb`` otherwise.
+    /// Whether the framebuffer is sRGB is up to the platform, see ``SketchSettings``
+    pub fn for_display(display: &glium::Display) -> Self {
+        Self::for_framebuffer(display.gl_window().get_pixel_format().srgb)
+    }
+
+    /// ``Linear`` for sRGB framebuffers, ``Srgb`` otherwise
+    pub fn for_framebuffer(srgb_framebuffer: bool) -> Self {
+        if srgb_framebuffer {
+            ColorPipeline::Linear
+        } else {
+            ColorPipeline::Srgb
+        }
+    }
+
+    /// ``self``, unless it can't work with the framebuffer
+    pub fn supported_by(self, srgb_framebuffer: bool) -> Self {
+        match self {
+            ColorPipeline::Linear => Self::for_framebuffer(srgb_framebuffer),
+            ColorPipeline::Srgb => ColorPipeline::Srgb,
+        }
+    }
+
+    /// The channels of ``color`` as the shaders expect them
+    pub fn upload(&self, color: Color) -> [f32; 4] {
+        match self {
+            ColorPipeline::Linear => {
+                let [r, g, b] = color.to_linear_rgb();
+                [r, g, b, color.a]
+            }
+            ColorPipeline::Srgb => [color.r, color.g, color.b, color.a],
+        }
+    }
+
+    /// Whether the shaders write sRGB values, which must not be converted again
+    /// by the framebuffer (see ``glium::program::ProgramCreationInput``)
+    pub fn outputs_srgb(&self) -> bool {
+        *self == ColorPipeline::Srgb
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::linear_to_srgb;
+
+    const EPSILON: f32 = 1e-5;
+
+    #[test]
+    fn the_framebuffer_picks_the_pipeline() {
+        assert_eq!(ColorPipeline::for_framebuffer(true), ColorPipeline::Linear);
+        assert_eq!(ColorPipeline::for_framebuffer(false), ColorPipeline::Srgb);
+    }
+
+    #[test]
+    fn linear_falls_back_to_srgb_without_an_srgb_framebuffer() {
+        assert_eq!(
+            ColorPipeline::Linear.supported_by(true),
+            ColorPipeline::Linear
+        );
+        assert_eq!(
+            ColorPipeline::Linear.supported_by(false),
+            ColorPipeline::Srgb
+        );
+        // sRGB blending works everywhere
+        assert_eq!(ColorPipeline::Srgb.supported_by(true), ColorPipeline::Srgb);
+        assert_eq!(ColorPipeline::Srgb.supported_by(false), ColorPipeline::Srgb);
+    }
+
+    #[test]
+    fn uploaded_colors_come_back_unchanged_on_screen() {
+        let color = Color::new(0.2, 0.5, 0.9, 0.4);
+
+        // The sRGB framebuffer converts linear values back when writing them
+        let [r, g, b, a] = ColorPipeline::Linear.upload(color);
+        let on_screen = [linear_to_srgb(r), linear_to_srgb(g), linear_to_srgb(b), a];
+        for (actual, expected) in on_screen.iter().zip([0.2, 0.5, 0.9, 0.4]) {
+            assert!((actual - expected).abs() < EPSILON);
+        }
+        // The alpha channel is never converted
+        assert_eq!(a, 0.4);
+        assert!(r < 0.2 && g < 0.5 && b < 0.9);
+
+        assert_eq!(ColorPipeline::Srgb.upload(color), [0.2, 0.5, 0.9, 0.4]);
+    }
+
+    #[test]
+    fn only_the_srgb_pipeline_outputs_srgb() {
+        assert!(ColorPipeline::Srgb.outputs_srgb());
+        assert!(!ColorPipeline::Linear.outputs_srgb());
+    }
+}
@@ -0,0 +1,42 @@
+//! The coordinate systems shapes can be drawn in.
+//!
+//! Sizes are expressed in logical pixels: on HiDPI screens (where the scale factor is
+//! bigger than 1) a logical pixel covers more than one physical pixel of the window.
+
+use crate::transform::Transform;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum CoordinateMode {
+    /// Pixels, with the origin in the top left corner and Y pointing down (like SVG)
+    Pixels,
+    /// Pixels, with the origin in the center of the window and Y pointing up
+    CenteredPixels,
+    /// Normalized device coordinates: (-1, -1) is the bottom left corner of the window
+    /// and (1, 1) the top right one, so shapes get stretched along with the window
+    #[default]
+    Normalized,
+    /// Like ``Normalized``, but only the shortest side of the window goes from -1 to 1:
+    /// both axes use the same units, and shapes keep their proportions
+    NormalizedFixedAspect,
+}
+
+impl CoordinateMode {
+    /// The transform from drawing coordinates to normalized device coordinates,
+    /// for a window of ``width`` by ``height`` logical pixels
+    pub fn projection(&self, width: f32, height: f32) -> Transform {
+        let width = width.max(1.0);
+        let height = height.max(1.0);
+
+        match self {
+            CoordinateMode::Pixels => {
+                Transform::new(2.0 / width, 0.0, 0.0, -2.0 / height, -1.0, 1.0)
+            }
+            CoordinateMode::CenteredPixels => Transform::scaling(2.0 / width, 2.0 / height),
+            CoordinateMode::Normalized => Transform::identity(),
+            CoordinateMode::NormalizedFixedAspect => {
+                let shortest_side = width.min(height);
+                Transform::scaling(shortest_side / width, shortest_side / height)
+            }
+        }
+    }
+}
@@ -0,0 +1,682 @@
+//! Gradient paints: colors changing across a shape.
+//!
+//! Linear, radial and conic gradients go through a list of color stops, while mesh
+//! gradients interpolate a grid of colors. Gradients are placed in drawing coordinates,
+//! and follow the transforms of the canvas like the shapes they paint.
+//! To draw them, the colors are baked into a small texture (see ``Gradient::bake``)
+//! that the batch shaders sample for each pixel, so even a gradient spanning a single
+//! huge triangle is smooth.
+
+use crate::color::Color;
+use crate::color_pipeline::ColorPipeline;
+use crate::resources::CacheKey;
+use crate::transform::Transform;
+
+/// Number of colors baked for linear, radial and conic gradients
+pub const RAMP_RESOLUTION: u32 = 256;
+
+/// Number of colors baked between two rows (or columns) of a mesh gradient
+const MESH_RESOLUTION_PER_CELL: u32 = 32;
+
+/// What happens to the parts of the shape beyond the first and the last stops
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub enum SpreadMode {
+    /// The colors of the first and the last stops go on forever
+    #[default]
+    Pad = 0,
+    /// The gradient starts over
+    Repeat = 1,
+    /// The gradient starts over in the opposite direction, like in a mirror
+    Reflect = 2,
+}
+
+impl SpreadMode {
+    /// Brings ``offset`` between 0 and 1
+    pub fn apply(&self, offset: f32) -> f32 {
+        match self {
+            SpreadMode::Pad => offset.clamp(0.0, 1.0),
+            SpreadMode::Repeat => offset.rem_euclid(1.0),
+            SpreadMode::Reflect => 1.0 - (offset.rem_euclid(2.0) - 1.0).abs(),
+        }
+    }
+}
+
+/// The color space used to mix the colors of two neighboring stops
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub enum ColorInterpolation {
+    /// Like CSS and SVG
+    #[default]
+    Srgb,
+    /// Like light mixing in the real world: brighter halfway through
+    Linear,
+    /// Perceptually even steps, without going through gray between complementary colors
+    Oklab,
+}
+
+impl ColorInterpolation {
+    /// Mixes the colors with premultiplied alpha, so that fading to a transparent
+    /// color doesn't go through the (invisible) color channels of the transparent one
+    pub fn mix(&self, from: Color, to: Color, t: f32) -> Color {
+        let alpha = lerp(from.a, to.a, t);
+        let [x1, y1, z1] = self.channels(from);
+        let [x2, y2, z2] = self.channels(to);
+
+        let channels = if alpha > 0.0 {
+            [
+                lerp(x1 * from.a, x2 * to.a, t) / alpha,
+                lerp(y1 * from.a, y2 * to.a, t) / alpha,
+                lerp(z1 * from.a, z2 * to.a, t) / alpha,
+            ]
+        } else {
+            [lerp(x1, x2, t), lerp(y1, y2, t), lerp(z1, z2, t)]
+        };
+
+        self.color(channels, alpha)
+    }
+
+    fn channels(&self, color: Color) -> [f32; 3] {
+        match self {
+            ColorInterpolation::Srgb => [color.r, color.g, color.b],
+            ColorInterpolation::Linear => color.to_linear_rgb(),
+            ColorInterpolation::Oklab => color.to_oklab(),
+        }
+    }
+
+    fn color(&self, [x, y, z]: [f32; 3], alpha: f32) -> Color {
+        match self {
+            ColorInterpolation::Srgb => Color::new(x, y, z, alpha),
+            ColorInterpolation::Linear => Color::from_linear_rgb(x, y, z, alpha),
+            ColorInterpolation::Oklab => Color::from_oklab(x, y, z, alpha),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ColorStop {
+    /// Between 0 (the start of the gradient) and 1 (its end)
+    pub offset: f32,
+    pub color: Color,
+}
+
+impl ColorStop {
+    pub fn new(offset: f32, color: Color) -> Self {
+        Self { offset, color }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum GradientKind {
+    /// Offset 0 is on ``start``, offset 1 on ``end``, and the colors
+    /// don't change along the perpendicular lines
+    Linear { start: [f32; 2], end: [f32; 2] },
+    /// Offset 0 is on ``center``, offset 1 on the circle of the given ``radius``
+    Radial { center: [f32; 2], radius: f32 },
+    /// The offset goes around ``center``, from 0 at ``start_angle`` (in radians)
+    /// to 1 after a full turn in the direction of ``Transform::rotation``
+    Conic { center: [f32; 2], start_angle: f32 },
+    /// A grid of colors covering the rectangle with a corner in ``min``.
+    /// ``colors`` are row by row, ``columns`` colors per row, starting from ``min``.
+    /// The stops and the spread mode aren't used: outside of the rectangle,
+    /// the colors of its border go on forever
+    Mesh {
+        min: [f32; 2],
+        size: [f32; 2],
+        columns: usize,
+        colors: Vec<Color>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gradient {
+    pub kind: GradientKind,
+    /// Sorted by offset
+    pub stops: Vec<ColorStop>,
+    pub spread: SpreadMode,
+    pub interpolation: ColorInterpolation,
+    /// Applied to the gradient after placing it, usually by the canvas
+    pub transform: Transform,
+}
+
+impl Gradient {
+    pub fn new(kind: GradientKind) -> Self {
+        Self {
+            kind,
+            stops: Vec::new(),
+            spread: SpreadMode::default(),
+            interpolation: ColorInterpolation::default(),
+            transform: Transform::identity(),
+        }
+    }
+
+    pub fn linear(start: [f32; 2], end: [f32; 2]) -> Self {
+        Self::new(GradientKind::Linear { start, end })
+    }
+
+    pub fn radial(center: [f32; 2], radius: f32) -> Self {
+        Self::new(GradientKind::Radial { center, radius })
+    }
+
+    pub fn conic(center: [f32; 2], start_angle: f32) -> Self {
+        Self::new(GradientKind::Conic {
+            center,
+            start_angle,
+        })
+    }
+
+    /// See ``GradientKind::Mesh``
+    pub fn mesh(min: [f32; 2], size: [f32; 2], columns: usize, colors: Vec<Color>) -> Self {
+        Self::new(GradientKind::Mesh {
+            min,
+            size,
+            columns: columns.max(1),
+            colors,
+        })
+    }
+
+    /// Adds a stop, after the ones with the same offset
+    pub fn with_stop(mut self, offset: f32, color: Color) -> Self {
+        let index = self.stops.partition_point(|stop| stop.offset <= offset);
+        self.stops.insert(index, ColorStop::new(offset, color));
+        self
+    }
+
+    /// Stops spread evenly from 0 to 1
+    pub fn with_colors(mut self, colors: &[Color]) -> Self {
+        let last = colors.len().saturating_sub(1).max(1) as f32;
+        for (index, &color) in colors.iter().enumerate() {
+            self = self.with_stop(index as f32 / last, color);
+        }
+        self
+    }
+
+    pub fn with_spread(mut self, spread: SpreadMode) -> Self {
+        self.spread = spread;
+        self
+    }
+
+    pub fn with_interpolation(mut self, interpolation: ColorInterpolation) -> Self {
+        self.interpolation = interpolation;
+        self
+    }
+
+    /// Moves the gradient around, after the transforms applied so far
+    pub fn transform(&mut self, transform: &Transform) {
+        self.transform = transform.multiply(&self.transform);
+    }
+
+    /// Goes from drawing coordinates to the space where the gradient is the simplest:
+    /// the offset is X for linear gradients, the distance from the origin for radial ones,
+    /// and the angle around the origin for conic ones. Mesh gradients cover the unit square
+    pub fn unit_transform(&self) -> Transform {
+        // Degenerate gradients send everything to offset 1, where the last stop is
+        let degenerate = Transform::new(0.0, 0.0, 0.0, 0.0, 1.0, 0.0);
+
+        let to_unit = match self.kind {
+            GradientKind::Linear { start, end } => {
+                let [dx, dy] = [end[0] - start[0], end[1] - start[1]];
+                let length_squared = dx * dx + dy * dy;
+                if length_squared <= f32::EPSILON {
+                    return degenerate;
+                }
+                // Projected on the direction of the gradient (and on its perpendicular)
+                Transform::new(
+                    dx / length_squared,
+                    -dy / length_squared,
+                    dy / length_squared,
+                    dx / length_squared,
+                    -(dx * start[0] + dy * start[1]) / length_squared,
+                    (dy * start[0] - dx * start[1]) / length_squared,
+                )
+            }
+            GradientKind::Radial { center, radius } => {
+                if radius <= 0.0 {
+                    return degenerate;
+                }
+                Transform::scaling(1.0 / radius, 1.0 / radius)
+                    .multiply(&Transform::translation(-center[0], -center[1]))
+            }
+            GradientKind::Conic {
+                center,
+                start_angle,
+            } => Transform::rotation(-start_angle)
+                .multiply(&Transform::translation(-center[0], -center[1])),
+            GradientKind::Mesh { min, size, .. } => {
+                if size[0] == 0.0 || size[1] == 0.0 {
+                    return degenerate;
+                }
+                Transform::scaling(1.0 / size[0], 1.0 / size[1])
+                    .multiply(&Transform::translation(-min[0], -min[1]))
+            }
+        };
+
+        match self.transform.inverse() {
+            Some(inverse) => to_unit.multiply(&inverse),
+            None => degenerate,
+        }
+    }
+
+    /// The color of the stops at ``offset``, which is expected to be between 0 and 1
+    pub fn sample(&self, offset: f32) -> Color {
+        let (Some(first), Some(last)) = (self.stops.first(), self.stops.last()) else {
+            return Color::new(0.0, 0.0, 0.0, 0.0);
+        };
+        if offset <= first.offset {
+            return first.color;
+        }
+        if offset >= last.offset {
+            return last.color;
+        }
+
+        let index = self.stops.partition_point(|stop| stop.offset <= offset);
+        let (from, to) = (self.stops[index - 1], self.stops[index]);
+        let t = (offset - from.offset) / (to.offset - from.offset);
+        self.interpolation.mix(from.color, to.color, t)
+    }
+
+    /// The color painted on ``point``, in drawing coordinates
+    pub fn color_at(&self, point: [f32; 2]) -> Color {
+        let [x, y] = self.unit_transform().apply(point);
+
+        let offset = match &self.kind {
+            GradientKind::Linear { .. } => x,
+            GradientKind::Radial { .. } => x.hypot(y),
+            GradientKind::Conic { .. } => (y.atan2(x) / std::f32::consts::TAU).rem_euclid(1.0),
+            GradientKind::Mesh {
+                columns, colors, ..
+            } => return self.mesh_color(*columns, colors, [x, y]),
+        };
+
+        self.sample(self.spread.apply(offset))
+    }
+
+    /// Bilinear interpolation of the colors, ``[u, v]`` being in the unit square
+    fn mesh_color(&self, columns: usize, colors: &[Color], [u, v]: [f32; 2]) -> Color {
+        let rows = colors.len() / columns;
+        if rows == 0 {
+            return Color::new(0.0, 0.0, 0.0, 0.0);
+        }
+
+        let cell = |position: f32, count: usize| {
+            let scaled = position.clamp(0.0, 1.0) * (count - 1) as f32;
+            let index = (scaled.floor() as usize).min(count.saturating_sub(2));
+            let next = (index + 1).min(count - 1);
+            (index, next, scaled - index as f32)
+        };
+        let (column, next_column, tx) = cell(u, columns);
+        let (row, next_row, ty) = cell(v, rows);
+        let color = |column: usize, row: usize| colors[row * columns + column];
+
+        let mix = |from, to, t| self.interpolation.mix(from, to, t);
+        mix(
+            mix(color(column, row), color(next_column, row), tx),
+            mix(color(column, next_row), color(next_column, next_row), tx),
+            ty,
+        )
+    }
+
+    /// A single color standing for the whole gradient, for outputs that can't do better
+    pub fn average_color(&self) -> Color {
+        let samples: Vec<Color> = match &self.kind {
+            GradientKind::Mesh { colors, .. } => colors.clone(),
+            _ => (0..=16).map(|i| self.sample(i as f32 / 16.0)).collect(),
+        };
+        if samples.is_empty() {
+            return Color::new(0.0, 0.0, 0.0, 0.0);
+        }
+
+        let count = samples.len() as f32;
+        let alpha = samples.iter().map(|color| color.a).sum::<f32>() / count;
+        let channel = |channel: fn(&Color) -> f32| {
+            let sum: f32 = samples.iter().map(|color| channel(color) * color.a).sum();
+            if alpha > 0.0 {
+                sum / count / alpha
+            } else {
+                0.0
+            }
+        };
+
+        Color::new(
+            channel(|color| color.r),
+            channel(|color| color.g),
+            channel(|color| color.b),
+            alpha,
+        )
+    }
+
+    /// How the batch shaders find the offset and apply the spread mode,
+    /// see ``BATCH_FRAGMENT_SHADER_SRC``
+    pub fn shader_paint(&self) -> [f32; 2] {
+        [self.shader_kind(), self.spread as u32 as f32]
+    }
+
+    fn shader_kind(&self) -> f32 {
+        match self.kind {
+            GradientKind::Linear { .. } => 1.0,
+            GradientKind::Radial { .. } => 2.0,
+            GradientKind::Conic { .. } => 3.0,
+            GradientKind::Mesh { .. } => 4.0,
+        }
+    }
+
+    /// Identifies the texture returned by ``bake``. Gradients that only differ by their
+    /// position (or their spread mode) share the same texture
+    pub fn texture_key(&self, color_pipeline: ColorPipeline) -> CacheKey {
+        let mut key = CacheKey::new("gradient");
+        key.add(color_pipeline).add(self.interpolation);
+
+        let add_color = |key: &mut CacheKey, color: Color| {
+            key.add_f32(color.r)
+                .add_f32(color.g)
+                .add_f32(color.b)
+                .add_f32(color.a);
+        };
+
+        match &self.kind {
+            GradientKind::Mesh {
+                columns, colors, ..
+            } => {
+                key.add("mesh").add(columns);
+                for &color in colors {
+                    add_color(&mut key, color);
+                }
+            }
+            _ => {
+                key.add("ramp");
+                for stop in &self.stops {
+                    key.add_f32(stop.offset);
+                    add_color(&mut key, stop.color);
+                }
+            }
+        }
+
+        key
+    }
+
+    /// The colors of the gradient, converted by ``color_pipeline``, ready to be sampled
+    /// by the batch shaders. Linear, radial and conic gradients go from offset 0 (the left)
+    /// to offset 1 (the right) of a single row, mesh gradients cover the whole texture
+    pub fn bake(&self, color_pipeline: ColorPipeline) -> glium::texture::RawImage2d<'static, f32> {
+        let (width, height, colors): (u32, u32, Vec<Color>) = match &self.kind {
+            GradientKind::Mesh {
+                columns, colors, ..
+            } => {
+                let rows = colors.len() / columns;
+                let resolution = |count: usize| {
+                    ((count.max(2) as u32 - 1) * MESH_RESOLUTION_PER_CELL + 1).min(1024)
+                };
+                let (width, height) = (resolution(*columns), resolution(rows));
+
+                let mut baked = Vec::with_capacity((width * height) as usize);
+                for y in 0..height {
+                    for x in 0..width {
+                        let u = x as f32 / (width - 1) as f32;
+                        let v = y as f32 / (height - 1) as f32;
+                        baked.push(self.mesh_color(*columns, colors, [u, v]));
+                    }
+                }
+                (width, height, baked)
+            }
+            _ => {
+                let last = (RAMP_RESOLUTION - 1) as f32;
+                let baked = (0..RAMP_RESOLUTION)
+                    .map(|i| self.sample(i as f32 / last))
+                    .collect();
+                (RAMP_RESOLUTION, 1, baked)
+            }
+        };
+
+        let data = colors
+            .into_iter()
+            .flat_map(|color| color_pipeline.upload(color))
+            .collect();
+
+        glium::texture::RawImage2d {
+            data: std::borrow::Cow::Owned(data),
+            width,
+            height,
+            format: glium::texture::ClientFormat::F32F32F32F32,
+        }
+    }
+}
+
+fn lerp(from: f32, to: f32, t: f32) -> f32 {
+    from + (to - from) * t
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-3;
+
+    fn assert_close(actual: &[f32], expected: &[f32]) {
+        for (a, e) in actual.iter().zip(expected) {
+            assert!(
+                (a - e).abs() < EPSILON,
+                "expected {expected:?}, got {actual:?}"
+            );
+        }
+    }
+
+    fn assert_same_color(actual: Color, expected: Color) {
+        let channels = |color: Color| [color.r, color.g, color.b, color.a];
+        assert_close(&channels(actual), &channels(expected));
+    }
+
+    const RED: Color = Color {
+        r: 1.0,
+        g: 0.0,
+        b: 0.0,
+        a: 1.0,
+    };
+    const GREEN: Color = Color {
+        r: 0.0,
+        g: 1.0,
+        b: 0.0,
+        a: 1.0,
+    };
+    const BLUE: Color = Color {
+        r: 0.0,
+        g: 0.0,
+        b: 1.0,
+        a: 1.0,
+    };
+    const BLACK: Color = Color {
+        r: 0.0,
+        g: 0.0,
+        b: 0.0,
+        a: 1.0,
+    };
+    const WHITE: Color = Color {
+        r: 1.0,
+        g: 1.0,
+        b: 1.0,
+        a: 1.0,
+    };
+
+    #[test]
+    fn linear_unit_transform_projects_on_the_gradient() {
+        let gradient = Gradient::linear([1.0, 1.0], [3.0, 1.0]);
+        let unit = gradient.unit_transform();
+        assert_close(&unit.apply([1.0, 1.0]), &[0.0, 0.0]);
+        assert_close(&unit.apply([3.0, 1.0]), &[1.0, 0.0]);
+        // Along the perpendicular, the offset doesn't change
+        assert_close(&unit.apply([2.0, 5.0])[..1], &[0.5]);
+        assert_close(&unit.apply([5.0, 1.0]), &[2.0, 0.0]);
+    }
+
+    #[test]
+    fn radial_unit_transform_follows_the_canvas() {
+        let mut gradient = Gradient::radial([1.0, 2.0], 2.0);
+        let unit = gradient.unit_transform();
+        assert_close(&unit.apply([1.0, 2.0]), &[0.0, 0.0]);
+        assert_close(&unit.apply([1.0, 4.0]), &[0.0, 1.0]);
+
+        // Scaled by the canvas, the circle gets bigger
+        gradient.transform(&Transform::scaling(2.0, 2.0));
+        let unit = gradient.unit_transform();
+        assert_close(&unit.apply([2.0, 4.0]), &[0.0, 0.0]);
+        assert_close(&unit.apply([6.0, 4.0]), &[1.0, 0.0]);
+    }
+
+    #[test]
+    fn degenerate_gradients_paint_their_last_stop() {
+        let gradient = Gradient::linear([1.0, 1.0], [1.0, 1.0]).with_colors(&[RED, BLUE]);
+        assert_same_color(gradient.color_at([5.0, -3.0]), BLUE);
+
+        let gradient = Gradient::radial([0.0, 0.0], 0.0).with_colors(&[RED, BLUE]);
+        assert_same_color(gradient.color_at([0.0, 0.0]), BLUE);
+
+        let mut gradient = Gradient::radial([0.0, 0.0], 1.0).with_colors(&[RED, BLUE]);
+        gradient.transform(&Transform::scaling(0.0, 1.0));
+        assert_same_color(gradient.color_at([0.0, 0.0]), BLUE);
+    }
+
+    #[test]
+    fn spread_modes_bring_offsets_back_between_0_and_1() {
+        let offsets = [-0.25, 0.0, 0.25, 1.0, 1.75, 2.0];
+        let spread = |spread: SpreadMode| offsets.map(|offset| spread.apply(offset));
+
+        assert_close(&spread(SpreadMode::Pad), &[0.0, 0.0, 0.25, 1.0, 1.0, 1.0]);
+        // The end of each repetition is the start of the next one
+        assert_close(
+            &spread(SpreadMode::Repeat),
+            &[0.75, 0.0, 0.25, 0.0, 0.75, 0.0],
+        );
+        assert_close(
+            &spread(SpreadMode::Reflect),
+            &[0.25, 0.0, 0.25, 1.0, 0.25, 0.0],
+        );
+    }
+
+    #[test]
+    fn sample_with_uneven_stops() {
+        let gradient = Gradient::linear([0.0, 0.0], [1.0, 0.0])
+            .with_stop(0.2, RED)
+            .with_stop(1.0, BLUE)
+            .with_stop(0.4, GREEN);
+
+        // Before the first stop and after the last one, their colors go on
+        assert_same_color(gradient.sample(0.0), RED);
+        assert_same_color(gradient.sample(0.2), RED);
+        assert_same_color(gradient.sample(0.3), Color::new(0.5, 0.5, 0.0, 1.0));
+        assert_same_color(gradient.sample(0.4), GREEN);
+        assert_same_color(gradient.sample(0.7), Color::new(0.0, 0.5, 0.5, 1.0));
+        assert_same_color(gradient.sample(1.0), BLUE);
+
+        // Without stops, there's nothing to paint
+        let empty = Gradient::linear([0.0, 0.0], [1.0, 0.0]);
+        assert_same_color(empty.sample(0.5), Color::new(0.0, 0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn stops_at_the_same_offset_make_a_hard_edge() {
+        let gradient = Gradient::linear([0.0, 0.0], [1.0, 0.0])
+            .with_stop(0.0, BLACK)
+            .with_stop(0.5, RED)
+            .with_stop(0.5, BLUE)
+            .with_stop(1.0, WHITE);
+
+        assert_same_color(
+            gradient.sample(0.4999),
+            gradient.interpolation.mix(BLACK, RED, 0.9998),
+        );
+        assert_same_color(gradient.sample(0.5), BLUE);
+        assert_same_color(gradient.sample(0.75), Color::new(0.5, 0.5, 1.0, 1.0));
+    }
+
+    #[test]
+    fn mix_depends_on_the_interpolation() {
+        let gray = |interpolation: ColorInterpolation| interpolation.mix(BLACK, WHITE, 0.5).r;
+        assert_close(&[gray(ColorInterpolation::Srgb)], &[0.5]);
+        // Half the light, which looks brighter than halfway
+        assert_close(&[gray(ColorInterpolation::Linear)], &[0.735_357]);
+        // Half the perceived lightness: Oklab L = 0.5 is 0.125 of the light
+        assert_close(&[gray(ColorInterpolation::Oklab)], &[0.388_572]);
+
+        // Halfway in Oklab is halfway in perceived lightness, which sRGB misses
+        let srgb = ColorInterpolation::Srgb.mix(RED, BLUE, 0.5);
+        let oklab = ColorInterpolation::Oklab.mix(RED, BLUE, 0.5);
+        let lightness = (RED.to_oklab()[0] + BLUE.to_oklab()[0]) / 2.0;
+        assert_same_color(srgb, Color::new(0.5, 0.0, 0.5, 1.0));
+        assert_close(&[oklab.to_oklab()[0]], &[lightness]);
+        assert!((srgb.to_oklab()[0] - lightness).abs() > 0.05);
+
+        for interpolation in [
+            ColorInterpolation::Srgb,
+            ColorInterpolation::Linear,
+            ColorInterpolation::Oklab,
+        ] {
+            assert_same_color(interpolation.mix(RED, BLUE, 0.0), RED);
+            assert_same_color(interpolation.mix(RED, BLUE, 1.0), BLUE);
+        }
+    }
+
+    #[test]
+    fn mix_fades_to_transparent_without_its_color() {
+        let transparent_blue = BLUE.with_alpha(0.0);
+        for interpolation in [
+            ColorInterpolation::Srgb,
+            ColorInterpolation::Linear,
+            ColorInterpolation::Oklab,
+        ] {
+            assert_same_color(
+                interpolation.mix(RED, transparent_blue, 0.5),
+                RED.with_alpha(0.5),
+            );
+            // Nothing to divide by: the channels are mixed as they are
+            let mixed = interpolation.mix(RED.with_alpha(0.0), transparent_blue, 0.5);
+            assert_eq!(mixed.a, 0.0);
+            assert!([mixed.r, mixed.g, mixed.b].iter().all(|c| c.is_finite()));
+        }
+    }
+
+    #[test]
+    fn baked_ramps_keep_straight_alpha() {
+        // The texture isn't premultiplied: a color fading out keeps its channels
+        let gradient = Gradient::linear([0.0, 0.0], [1.0, 0.0])
+            .with_colors(&[RED.with_alpha(1.0), RED.with_alpha(0.0)]);
+        let baked = gradient.bake(ColorPipeline::Srgb);
+        assert_eq!((baked.width, baked.height), (RAMP_RESOLUTION, 1));
+
+        for texel in baked.data.chunks_exact(4) {
+            assert_close(&texel[..3], &[1.0, 0.0, 0.0]);
+        }
+        assert_close(
+            &[baked.data[3], baked.data[baked.data.len() - 1]],
+            &[1.0, 0.0],
+        );
+    }
+
+    #[test]
+    fn mesh_colors_are_interpolated_bilinearly() {
+        let colors = vec![BLACK, RED, GREEN, BLUE];
+        let gradient = Gradient::mesh([0.0, 0.0], [2.0, 4.0], 2, colors.clone());
+
+        assert_same_color(gradient.color_at([0.0, 0.0]), BLACK);
+        assert_same_color(gradient.color_at([2.0, 0.0]), RED);
+        assert_same_color(gradient.color_at([0.0, 4.0]), GREEN);
+        assert_same_color(gradient.color_at([2.0, 4.0]), BLUE);
+        assert_same_color(
+            gradient.color_at([1.0, 0.0]),
+            Color::new(0.5, 0.0, 0.0, 1.0),
+        );
+        assert_same_color(
+            gradient.color_at([1.0, 2.0]),
+            Color::new(0.25, 0.25, 0.25, 1.0),
+        );
+        // The border goes on outside of the rectangle
+        assert_same_color(gradient.color_at([-5.0, 10.0]), GREEN);
+
+        // A single column, and an incomplete last row which is ignored
+        assert_same_color(
+            gradient.mesh_color(1, &[RED, BLUE], [0.7, 0.5]),
+            Color::new(0.5, 0.0, 0.5, 1.0),
+        );
+        assert_same_color(gradient.mesh_color(2, &colors[..3], [1.0, 1.0]), RED);
+        assert_same_color(
+            gradient.mesh_color(2, &colors[..1], [0.5, 0.5]),
+            Color::new(0.0, 0.0, 0.0, 0.0),
+        );
+    }
+}
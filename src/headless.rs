@@ -0,0 +1,231 @@
+//! Running sketches without a window: on CI, on render servers, or anywhere without a screen.
+//!
+//! ``run_headless`` is the counterpart of ``run_sketch``: the same ``Sketch`` is set up,
+//! updated and drawn, but the canvas renders into an ``OffscreenTarget`` whose pixels are
+//! read back after each frame. The OpenGL context comes from surfaceless EGL when Mesa
+//! provides it (falling back to the llvmpipe software renderer without a GPU), or from
+//! OSMesa otherwise. Frames advance by a fixed ``1 / frame_rate`` seconds, so the output
+//! only depends on the sketch, not on how fast the machine is.
+
+mod egl;
+
+use std::fmt;
+use std::rc::Rc;
+
+use glium::backend::{Context, Facade};
+
+use crate::canvas::Canvas;
+use crate::instancing::InstancingError;
+use crate::offscreen::{OffscreenError, OffscreenTarget, RgbaImage};
+use crate::sketch::{draw_frame, Sketch, SketchSettings};
+use crate::tiled::{render_tiled, TiledRender};
+
+/// Size used when ``SketchSettings::size`` is ``None``, since there's no platform to decide
+pub const DEFAULT_SIZE: [u32; 2] = [800, 600];
+
+#[derive(Debug)]
+pub enum HeadlessError {
+    /// None of the headless OpenGL contexts could be created
+    Context(String),
+    Texture(glium::texture::TextureCreationError),
+    Draw(InstancingError),
+    /// A frame of the recording couldn't be written
+    Io(std::io::Error),
+}
+
+impl fmt::Display for HeadlessError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HeadlessError::Context(message) => {
+                write!(f, "could not create a headless OpenGL context: {message}")
+            }
+            HeadlessError::Texture(error) => {
+                write!(f, "could not create the offscreen target: {error}")
+            }
+            HeadlessError::Draw(error) => write!(f, "could not draw the frame: {error}"),
+            HeadlessError::Io(error) => write!(f, "could not record the frame: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for HeadlessError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            HeadlessError::Context(_) => None,
+            HeadlessError::Texture(error) => Some(error),
+            HeadlessError::Draw(error) => Some(error),
+            HeadlessError::Io(error) => Some(error),
+        }
+    }
+}
+
+impl From<glium::texture::TextureCreationError> for HeadlessError {
+    fn from(error: glium::texture::TextureCreationError) -> Self {
+        HeadlessError::Texture(error)
+    }
+}
+
+impl From<InstancingError> for HeadlessError {
+    fn from(error: InstancingError) -> Self {
+        HeadlessError::Draw(error)
+    }
+}
+
+impl From<std::io::Error> for HeadlessError {
+    fn from(error: std::io::Error) -> Self {
+        HeadlessError::Io(error)
+    }
+}
+
+/// Creates an OpenGL context that doesn't need a window:
+/// surfaceless EGL first, then OSMesa
+pub fn create_context(size: [u32; 2]) -> Result<Rc<Context>, HeadlessError> {
+    let egl_error = match egl::create_context((size[0], size[1])) {
+        Ok(context) => return Ok(context),
+        Err(error) => error,
+    };
+
+    osmesa_context(size).map_err(|osmesa_error| {
+        HeadlessError::Context(format!(
+            "{egl_error}, and OSMesa failed too: {osmesa_error}"
+        ))
+    })
+}
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+))]
+fn osmesa_context(size: [u32; 2]) -> Result<Rc<Context>, String> {
+    use glium::glutin::platform::unix::HeadlessContextExt;
+
+    let context = glium::glutin::ContextBuilder::new()
+        .with_gl(glium::glutin::GlRequest::Latest)
+        .build_osmesa(glium::glutin::dpi::PhysicalSize::new(size[0], size[1]))
+        .map_err(|error| error.to_string())?;
+    let renderer = glium::HeadlessRenderer::new(context).map_err(|error| error.to_string())?;
+    // The context keeps the backend of the renderer alive
+    Ok(renderer.get_context().clone())
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+)))]
+fn osmesa_context(_size: [u32; 2]) -> Result<Rc<Context>, String> {
+    Err("OSMesa is not available on this platform".to_string())
+}
+
+/// A canvas rendering into an offscreen target, with no window around it
+pub struct Headless {
+    canvas: Canvas,
+    target: OffscreenTarget,
+    /// Seconds between two frames
+    frame_duration: f32,
+}
+
+impl Headless {
+    /// Creates its own context, see ``create_context``. Besides the size,
+    /// ``settings`` provides the anti-aliasing, the color pipeline, the frame rate
+    /// and the recording
+    pub fn new(settings: &SketchSettings) -> Result<Self, HeadlessError> {
+        let context = create_context(settings.size.unwrap_or(DEFAULT_SIZE))?;
+        Self::with_facade(&context, settings)
+    }
+
+    /// Renders with an existing context, which may have a window (or not)
+    pub fn with_facade(
+        facade: &impl Facade,
+        settings: &SketchSettings,
+    ) -> Result<Self, HeadlessError> {
+        let [width, height] = settings.size.unwrap_or(DEFAULT_SIZE);
+        let target = OffscreenTarget::new(facade, width, height, settings.multisampling)?;
+
+        // Offscreen targets are sRGB, so both pipelines are supported
+        let mut canvas = Canvas::with_facade(facade, [width, height], 1.0, true);
+        canvas.set_color_pipeline(settings.color_pipeline);
+        canvas.set_multisampling(settings.multisampling);
+        canvas.set_frame_rate(settings.frame_rate);
+        if let Some(recording) = &settings.recording {
+            canvas.start_recording(recording.clone());
+        }
+
+        Ok(Self {
+            canvas,
+            target,
+            frame_duration: 1.0 / settings.frame_rate.max(1.0),
+        })
+    }
+
+    pub fn canvas(&mut self) -> &mut Canvas {
+        &mut self.canvas
+    }
+
+    pub fn target(&self) -> &OffscreenTarget {
+        &self.target
+    }
+
+    pub fn setup(&mut self, sketch: &mut impl Sketch) {
+        sketch.setup(&mut self.canvas);
+    }
+
+    /// Advances ``sketch`` by one frame, and renders it into the target.
+    /// The first frame has a ``dt`` of 0, like in ``run_sketch``
+    pub fn frame(&mut self, sketch: &mut impl Sketch) -> Result<(), HeadlessError> {
+        let dt = if self.canvas.frame_count() == 0 {
+            0.0
+        } else {
+            self.frame_duration
+        };
+        let canvas = &mut self.canvas;
+        self.target
+            .draw(|surface| draw_frame(sketch, canvas, surface, dt))?;
+
+        if self.canvas.is_recording() {
+            let image = self.read_pixels();
+            self.canvas.record_image(&image)?;
+        }
+        Ok(())
+    }
+
+    /// Draws ``sketch`` again into a big image, see ``tiled``
+    pub fn render_tiled(
+        &mut self,
+        sketch: &mut impl Sketch,
+        settings: &TiledRender,
+    ) -> Result<(), OffscreenError> {
+        render_tiled(sketch, &mut self.canvas, settings)
+    }
+
+    /// The pixels of the last frame, top row first
+    pub fn read_pixels(&self) -> RgbaImage {
+        self.target.read_pixels(self.canvas.color_pipeline())
+    }
+}
+
+/// Runs ``sketch`` for ``frame_count`` frames without a window,
+/// calling ``on_frame`` with the index and the pixels of each frame
+pub fn run_headless<S: Sketch>(
+    sketch: &mut S,
+    settings: &SketchSettings,
+    frame_count: u64,
+    mut on_frame: impl FnMut(u64, &RgbaImage),
+) -> Result<(), HeadlessError> {
+    let mut headless = Headless::new(settings)?;
+    headless.setup(sketch);
+
+    for index in 0..frame_count {
+        headless.frame(sketch)?;
+        on_frame(index, &headless.read_pixels());
+    }
+
+    // Completes the video or the animation, if any
+    headless.canvas().stop_recording()?;
+    Ok(())
+}
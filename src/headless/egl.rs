@@ -0,0 +1,255 @@
+//! A surfaceless EGL context: OpenGL without a window, nor a display server.
+//!
+//! Mesa implements ``EGL_MESA_platform_surfaceless``, which works on machines with no
+//! screen at all (CI runners, render servers), falling back to llvmpipe without a GPU.
+//! ``libEGL`` is loaded at runtime, so nothing is required to build the crate.
+
+use std::ffi::{c_char, c_void, CString};
+use std::rc::Rc;
+
+use glium::backend::{Backend, Context};
+use glium::debug::DebugCallbackBehavior;
+use glium::SwapBuffersError;
+
+use super::HeadlessError;
+
+type EGLDisplay = *mut c_void;
+type EGLConfig = *mut c_void;
+type EGLContext = *mut c_void;
+type EGLSurface = *mut c_void;
+type EGLint = i32;
+type EGLenum = u32;
+type EGLBoolean = u32;
+
+const EGL_FALSE: EGLBoolean = 0;
+const EGL_NONE: EGLint = 0x3038;
+const EGL_EXTENSIONS: EGLint = 0x3055;
+const EGL_SURFACE_TYPE: EGLint = 0x3033;
+const EGL_PBUFFER_BIT: EGLint = 0x0001;
+const EGL_RENDERABLE_TYPE: EGLint = 0x3040;
+const EGL_OPENGL_BIT: EGLint = 0x0008;
+const EGL_RED_SIZE: EGLint = 0x3024;
+const EGL_GREEN_SIZE: EGLint = 0x3023;
+const EGL_BLUE_SIZE: EGLint = 0x3022;
+const EGL_ALPHA_SIZE: EGLint = 0x3021;
+const EGL_OPENGL_API: EGLenum = 0x30a2;
+const EGL_CONTEXT_MAJOR_VERSION: EGLint = 0x3098;
+const EGL_CONTEXT_MINOR_VERSION: EGLint = 0x30fb;
+const EGL_CONTEXT_OPENGL_PROFILE_MASK: EGLint = 0x30fd;
+const EGL_CONTEXT_OPENGL_CORE_PROFILE_BIT: EGLint = 0x0001;
+const EGL_PLATFORM_SURFACELESS_MESA: EGLenum = 0x31dd;
+
+/// The few EGL functions we need, loaded from ``libEGL``
+struct Egl {
+    // Keeps the functions below alive
+    _library: libloading::Library,
+    get_proc_address: unsafe extern "C" fn(*const c_char) -> *const c_void,
+    initialize: unsafe extern "C" fn(EGLDisplay, *mut EGLint, *mut EGLint) -> EGLBoolean,
+    terminate: unsafe extern "C" fn(EGLDisplay) -> EGLBoolean,
+    query_string: unsafe extern "C" fn(EGLDisplay, EGLint) -> *const c_char,
+    bind_api: unsafe extern "C" fn(EGLenum) -> EGLBoolean,
+    choose_config: unsafe extern "C" fn(
+        EGLDisplay,
+        *const EGLint,
+        *mut EGLConfig,
+        EGLint,
+        *mut EGLint,
+    ) -> EGLBoolean,
+    create_context:
+        unsafe extern "C" fn(EGLDisplay, EGLConfig, EGLContext, *const EGLint) -> EGLContext,
+    destroy_context: unsafe extern "C" fn(EGLDisplay, EGLContext) -> EGLBoolean,
+    make_current:
+        unsafe extern "C" fn(EGLDisplay, EGLSurface, EGLSurface, EGLContext) -> EGLBoolean,
+    get_current_context: unsafe extern "C" fn() -> EGLContext,
+}
+
+impl Egl {
+    fn load() -> Result<Self, HeadlessError> {
+        let error = |message: String| HeadlessError::Context(message);
+
+        // SAFETY: libEGL doesn't run anything when loaded, and the signatures
+        // below are the ones of the EGL 1.4 specification
+        unsafe {
+            let library = libloading::Library::new("libEGL.so.1")
+                .or_else(|_| libloading::Library::new("libEGL.so"))
+                .map_err(|load_error| error(format!("could not load libEGL: {load_error}")))?;
+
+            macro_rules! function {
+                ($name:literal) => {
+                    *library
+                        .get(concat!($name, "\0").as_bytes())
+                        .map_err(|_| error(format!("libEGL lacks {}", $name)))?
+                };
+            }
+
+            Ok(Self {
+                get_proc_address: function!("eglGetProcAddress"),
+                initialize: function!("eglInitialize"),
+                terminate: function!("eglTerminate"),
+                query_string: function!("eglQueryString"),
+                bind_api: function!("eglBindAPI"),
+                choose_config: function!("eglChooseConfig"),
+                create_context: function!("eglCreateContext"),
+                destroy_context: function!("eglDestroyContext"),
+                make_current: function!("eglMakeCurrent"),
+                get_current_context: function!("eglGetCurrentContext"),
+                _library: library,
+            })
+        }
+    }
+
+    fn proc_address(&self, name: &str) -> *const c_void {
+        let name = CString::new(name).unwrap();
+        // SAFETY: ``name`` is a valid, null terminated string
+        unsafe { (self.get_proc_address)(name.as_ptr()) }
+    }
+}
+
+struct EglBackend {
+    egl: Egl,
+    display: EGLDisplay,
+    context: EGLContext,
+    size: (u32, u32),
+}
+
+impl Drop for EglBackend {
+    fn drop(&mut self) {
+        // SAFETY: both were created by ``create_context``, and aren't used after this
+        unsafe {
+            (self.egl.destroy_context)(self.display, self.context);
+            (self.egl.terminate)(self.display);
+        }
+    }
+}
+
+// SAFETY: the context is made current before glium uses it, and the function pointers
+// come from the same libEGL that created it
+unsafe impl Backend for EglBackend {
+    fn swap_buffers(&self) -> Result<(), SwapBuffersError> {
+        // There is nothing to swap: we always draw into textures
+        Ok(())
+    }
+
+    unsafe fn get_proc_address(&self, symbol: &str) -> *const c_void {
+        self.egl.proc_address(symbol)
+    }
+
+    fn get_framebuffer_dimensions(&self) -> (u32, u32) {
+        self.size
+    }
+
+    fn is_current(&self) -> bool {
+        // SAFETY: eglGetCurrentContext has no preconditions
+        unsafe { (self.egl.get_current_context)() == self.context }
+    }
+
+    unsafe fn make_current(&self) {
+        let no_surface = std::ptr::null_mut();
+        (self.egl.make_current)(self.display, no_surface, no_surface, self.context);
+    }
+}
+
+/// Creates an OpenGL 3.3 core context on the surfaceless platform of Mesa.
+/// ``size`` is only reported to glium as the size of the (nonexistent) default framebuffer
+pub fn create_context(size: (u32, u32)) -> Result<Rc<Context>, HeadlessError> {
+    let egl = Egl::load()?;
+    let error = |message: &str| HeadlessError::Context(message.to_string());
+
+    // SAFETY: the attribute lists are terminated by EGL_NONE, and every handle
+    // passed to EGL was returned by EGL
+    unsafe {
+        let client_extensions = (egl.query_string)(std::ptr::null_mut(), EGL_EXTENSIONS);
+        let has_extension = |name: &str| {
+            !client_extensions.is_null()
+                && std::ffi::CStr::from_ptr(client_extensions)
+                    .to_string_lossy()
+                    .split(' ')
+                    .any(|extension| extension == name)
+        };
+        if !has_extension("EGL_MESA_platform_surfaceless") {
+            return Err(error("EGL_MESA_platform_surfaceless is not supported"));
+        }
+
+        let get_platform_display = egl.proc_address("eglGetPlatformDisplayEXT");
+        if get_platform_display.is_null() {
+            return Err(error("EGL_EXT_platform_base is not supported"));
+        }
+        let get_platform_display: unsafe extern "C" fn(
+            EGLenum,
+            *mut c_void,
+            *const EGLint,
+        ) -> EGLDisplay = std::mem::transmute(get_platform_display);
+
+        let display = get_platform_display(
+            EGL_PLATFORM_SURFACELESS_MESA,
+            std::ptr::null_mut(),
+            std::ptr::null(),
+        );
+        if display.is_null() {
+            return Err(error("could not open the surfaceless display"));
+        }
+        let (mut major, mut minor) = (0, 0);
+        if (egl.initialize)(display, &mut major, &mut minor) == EGL_FALSE {
+            return Err(error("could not initialize EGL"));
+        }
+
+        let terminate = |message: &str| {
+            (egl.terminate)(display);
+            Err(error(message))
+        };
+
+        if (egl.bind_api)(EGL_OPENGL_API) == EGL_FALSE {
+            return terminate("desktop OpenGL is not supported");
+        }
+
+        #[rustfmt::skip]
+        let config_attributes = [
+            EGL_SURFACE_TYPE, EGL_PBUFFER_BIT,
+            EGL_RENDERABLE_TYPE, EGL_OPENGL_BIT,
+            EGL_RED_SIZE, 8,
+            EGL_GREEN_SIZE, 8,
+            EGL_BLUE_SIZE, 8,
+            EGL_ALPHA_SIZE, 8,
+            EGL_NONE,
+        ];
+        let mut config = std::ptr::null_mut();
+        let mut config_count = 0;
+        let found = (egl.choose_config)(
+            display,
+            config_attributes.as_ptr(),
+            &mut config,
+            1,
+            &mut config_count,
+        );
+        if found == EGL_FALSE || config_count == 0 {
+            return terminate("no suitable EGL config");
+        }
+
+        #[rustfmt::skip]
+        let context_attributes = [
+            EGL_CONTEXT_MAJOR_VERSION, 3,
+            EGL_CONTEXT_MINOR_VERSION, 3,
+            EGL_CONTEXT_OPENGL_PROFILE_MASK, EGL_CONTEXT_OPENGL_CORE_PROFILE_BIT,
+            EGL_NONE,
+        ];
+        let context = (egl.create_context)(
+            display,
+            config,
+            std::ptr::null_mut(),
+            context_attributes.as_ptr(),
+        );
+        if context.is_null() {
+            return terminate("could not create an OpenGL 3.3 context");
+        }
+
+        let backend = EglBackend {
+            egl,
+            display,
+            context,
+            size,
+        };
+        backend.make_current();
+        Context::new(backend, true, DebugCallbackBehavior::default())
+            .map_err(|incompatible| HeadlessError::Context(incompatible.to_string()))
+    }
+}
@@ -0,0 +1,245 @@
+//! GPU instancing: the same mesh drawn thousands of times with a single draw call.
+//!
+//! The mesh (a circle, a quad, a glyph...) is uploaded once, usually in local coordinates
+//! around the origin. Each copy then gets its own ``Instance`` attributes (transform,
+//! scale and color), which the vertex shader applies to the vertices of the mesh.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::ops::Range;
+use std::rc::Rc;
+
+use glium::backend::{Context, Facade};
+
+use crate::batch::clamp_range;
+use crate::color::Color;
+use crate::color_pipeline::ColorPipeline;
+use crate::resources::GpuMesh;
+use crate::shapes::Instance;
+
+pub const INSTANCED_VERTEX_SHADER_SRC: &str = r#"
+#version 140
+
+in vec2 position;
+
+in mat2 matrix;
+in vec2 translation;
+in vec2 scale;
+in vec4 color;
+
+out vec4 instance_color;
+
+// Goes from the coordinates used to draw to normalized device coordinates
+uniform mat3 projection;
+
+void main() {
+    instance_color = color;
+    vec2 placed = matrix * (position * scale) + translation;
+    gl_Position = vec4((projection * vec3(placed, 1.0)).xy, 0.0, 1.0);
+}
+"#;
+
+pub const INSTANCED_FRAGMENT_SHADER_SRC: &str = r#"
+#version 140
+
+in vec4 instance_color;
+out vec4 color;
+
+void main() {
+    // Premultiplied alpha, see ``blend``
+    color = vec4(instance_color.rgb * instance_color.a, instance_color.a);
+}
+"#;
+
+#[derive(Debug)]
+pub enum InstancingError {
+    /// The OpenGL context is older than 3.3 and lacks ``GL_ARB_instanced_arrays``
+    NotSupported,
+    Draw(glium::DrawError),
+}
+
+impl fmt::Display for InstancingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InstancingError::NotSupported => {
+                write!(f, "instancing is not supported by this OpenGL context")
+            }
+            InstancingError::Draw(error) => write!(f, "could not draw the instances: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for InstancingError {}
+
+impl From<glium::DrawError> for InstancingError {
+    fn from(error: glium::DrawError) -> Self {
+        InstancingError::Draw(error)
+    }
+}
+
+/// The per-instance attributes, plus the GPU buffer they live in.
+/// The buffer is only written again when the instances change,
+/// so static patterns (like grids) are uploaded only once
+pub struct Instances {
+    context: Rc<Context>,
+    instances: Vec<Instance>,
+    buffer: Option<glium::VertexBuffer<Instance>>,
+    changed: bool,
+    color_pipeline: ColorPipeline,
+}
+
+impl Instances {
+    pub fn new(display: &glium::Display) -> Self {
+        Self::with_facade(display, ColorPipeline::for_display(display))
+    }
+
+    /// Instances living in any OpenGL context, like the one of a ``Headless`` renderer
+    pub fn with_facade(facade: &impl Facade, color_pipeline: ColorPipeline) -> Self {
+        Self {
+            context: facade.get_context().clone(),
+            instances: Vec::new(),
+            buffer: None,
+            changed: false,
+            color_pipeline,
+        }
+    }
+
+    pub fn color_pipeline(&self) -> ColorPipeline {
+        self.color_pipeline
+    }
+
+    /// How the colors of the instances are uploaded.
+    /// It must match the program used to draw them (see ``ResourceCache::program_for_pipeline``)
+    pub fn set_color_pipeline(&mut self, color_pipeline: ColorPipeline) {
+        self.color_pipeline = color_pipeline;
+        self.changed = true;
+    }
+
+    pub fn len(&self) -> usize {
+        self.instances.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instances.is_empty()
+    }
+
+    pub fn push(&mut self, instance: Instance) {
+        self.instances.push(instance);
+        self.changed = true;
+    }
+
+    pub fn extend_from_slice(&mut self, instances: &[Instance]) {
+        self.instances.extend_from_slice(instances);
+        self.changed = true;
+    }
+
+    pub fn clear(&mut self) {
+        self.instances.clear();
+        self.changed = true;
+    }
+
+    pub fn as_slice(&self) -> &[Instance] {
+        &self.instances
+    }
+
+    /// Gives access to the instances, to animate them.
+    /// They will be uploaded again before the next draw
+    pub fn as_mut_slice(&mut self) -> &mut [Instance] {
+        self.changed = true;
+        &mut self.instances
+    }
+
+    /// Draws one copy of ``mesh`` for each instance.
+    /// ``program`` is usually compiled from ``INSTANCED_VERTEX_SHADER_SRC``
+    /// and ``INSTANCED_FRAGMENT_SHADER_SRC``, in which case ``uniforms``
+    /// must contain the ``projection`` matrix (see ``Transform::to_mat3``)
+    pub fn draw<S: glium::Surface, U: glium::uniforms::Uniforms>(
+        &mut self,
+        surface: &mut S,
+        program: &glium::Program,
+        uniforms: &U,
+        mesh: &GpuMesh,
+        draw_parameters: &glium::DrawParameters,
+    ) -> Result<(), InstancingError> {
+        let range = 0..self.instances.len();
+        self.draw_range(surface, program, uniforms, mesh, range, draw_parameters)
+    }
+
+    /// Like ``draw``, but only for the instances in ``range``.
+    /// The part of ``range`` past the last instance is ignored
+    pub fn draw_range<S: glium::Surface, U: glium::uniforms::Uniforms>(
+        &mut self,
+        surface: &mut S,
+        program: &glium::Program,
+        uniforms: &U,
+        mesh: &GpuMesh,
+        range: Range<usize>,
+        draw_parameters: &glium::DrawParameters,
+    ) -> Result<(), InstancingError> {
+        let range = clamp_range(range, self.instances.len());
+        if range.is_empty() {
+            return Ok(());
+        }
+
+        self.upload();
+
+        // The buffer has just been uploaded, and it's at least as big as the instances
+        // (the range being within them)
+        let buffer = self.buffer.as_ref().unwrap();
+        let instances = buffer.slice(range).unwrap();
+        let per_instance = instances
+            .per_instance()
+            .map_err(|_| InstancingError::NotSupported)?;
+        surface.draw(
+            (&mesh.vertex_buffer, per_instance),
+            &mesh.index_buffer,
+            program,
+            uniforms,
+            draw_parameters,
+        )?;
+
+        Ok(())
+    }
+
+    fn upload(&mut self) {
+        if !self.changed {
+            debug_assert!(self.buffer.as_ref().map_or(0, |buffer| buffer.len()) >= self.len());
+            return;
+        }
+
+        // The instances keep the colors they were given: the converted ones only live on the GPU
+        let instances: Cow<[Instance]> = match self.color_pipeline {
+            ColorPipeline::Srgb => Cow::Borrowed(&self.instances),
+            ColorPipeline::Linear => Cow::Owned(
+                self.instances
+                    .iter()
+                    .map(|instance| {
+                        let [r, g, b, a] = instance.color;
+                        Instance {
+                            color: self.color_pipeline.upload(Color::new(r, g, b, a)),
+                            ..*instance
+                        }
+                    })
+                    .collect(),
+            ),
+        };
+
+        match &mut self.buffer {
+            Some(buffer) if buffer.len() >= instances.len() => {
+                buffer.invalidate();
+                buffer.slice(0..instances.len()).unwrap().write(&instances);
+            }
+            buffer => {
+                // Some room to grow, so that adding a few instances doesn't reallocate
+                let mut instances = instances.into_owned();
+                instances.resize(
+                    self.instances.len().next_power_of_two(),
+                    Instance::new([0.0, 0.0], Color::new(0.0, 0.0, 0.0, 0.0)),
+                );
+                *buffer = Some(glium::VertexBuffer::dynamic(&self.context, &instances).unwrap());
+            }
+        }
+
+        self.changed = false;
+    }
+}
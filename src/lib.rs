@@ -0,0 +1,48 @@
+//! A tiny creative coding toolkit built on top of glium.
+//!
+//! The binary in ``main.rs`` is a small sketch that uses the helpers defined here.
+
+pub mod batch;
+pub mod blend;
+pub mod canvas;
+pub mod capture;
+pub mod color;
+pub mod color_pipeline;
+pub mod coordinates;
+pub mod gradient;
+pub mod headless;
+pub mod instancing;
+pub mod offscreen;
+pub mod palette;
+pub mod path;
+pub mod plotter;
+pub mod recording;
+pub mod resources;
+pub mod shapes;
+pub mod sketch;
+pub mod svg;
+pub mod tessellation;
+pub mod tiled;
+pub mod transform;
+pub mod video;
+
+pub use batch::Batch;
+pub use blend::BlendMode;
+pub use canvas::{Canvas, EndShape};
+pub use color::{Color, ParseColorError};
+pub use color_pipeline::ColorPipeline;
+pub use coordinates::CoordinateMode;
+pub use gradient::{ColorInterpolation, Gradient, SpreadMode};
+pub use headless::{run_headless, Headless, HeadlessError};
+pub use offscreen::{OffscreenError, OffscreenTarget, RgbaImage};
+pub use palette::{Palette, PaletteError};
+pub use path::Path;
+pub use recording::{FrameFormat, Recorder, RecordingOutput, RecordingSettings};
+pub use resources::ResourceCache;
+pub use shapes::{
+    draw_commands, generate_draw_commands, generate_path_draw_commands, DrawCommandError, Instance,
+    Shape, ShapePrimitive, ShapeStyle, SketchDrawCommand, Vertex,
+};
+pub use sketch::{run_sketch, SaveError, Saved, Sketch, SketchSettings};
+pub use tiled::{render_tiled, TiledRender};
+pub use video::VideoSettings;
@@ -1,242 +1,226 @@
-use glium::{glutin, implement_vertex, Surface};
-
-#[derive(Copy, Clone)]
-struct Vertex {
-    position: [f32; 2],
-}
-
-const VERTEX_SHADER_SRC: &str = r#"
-#version 140
-
-in vec2 position;
-
-void main() {
-    vec2 pos = position;
-    gl_Position = vec4(pos, 0.0, 1.0);
+use std::rc::Rc;
+
+use glium_101::resources::GpuMesh;
+use glium_101::sketch::{run_sketch, SaveError, Saved, Sketch, SketchSettings};
+use glium_101::tessellation::ellipse::{self, Ellipse};
+use glium_101::tessellation::polygon;
+use glium_101::tessellation::stroke::{LineCap, LineJoin};
+use glium_101::tessellation::Mesh;
+use glium_101::transform::Transform;
+use glium_101::{
+    BlendMode, Canvas, Color, ColorInterpolation, CoordinateMode, EndShape, Gradient, Instance,
+    ShapePrimitive, ShapeStyle, Vertex,
+};
+
+/// The corners of a square of side 1, centered on the origin
+const UNIT_SQUARE: [[f32; 2]; 4] = [[-0.5, -0.5], [0.5, -0.5], [0.5, 0.5], [-0.5, 0.5]];
+
+#[derive(Default)]
+struct Demo {
+    /// Seconds since the sketch started
+    time: f32,
+    /// Uploaded once, and drawn many times via instancing
+    square: Option<Rc<GpuMesh>>,
 }
-"#;
 
-const FRAGMENT_SHADER_SRC: &str = r#"
-#version 140
+impl Sketch for Demo {
+    fn setup(&mut self, canvas: &mut Canvas) {
+        // Like normalized device coordinates, but with the same units on both axes,
+        // so that circles stay round whatever the shape of the window
+        canvas.set_coordinate_mode(CoordinateMode::NormalizedFixedAspect);
+
+        let mesh = Mesh {
+            vertices: UNIT_SQUARE
+                .iter()
+                .map(|&[x, y]| Vertex::new(x, y))
+                .collect(),
+            indices: vec![0, 1, 2, 0, 2, 3],
+        };
+        // The outline is what SVG exports and plots get for each copy
+        let square = GpuMesh::new(canvas.context(), &mesh).with_outline(mesh.outline());
+        self.square = Some(Rc::new(square));
+    }
 
-out vec4 color;
-uniform vec4 requested_rgba_color;
+    fn update(&mut self, dt: f32) {
+        self.time += dt;
+    }
 
-void main() {
-    color = requested_rgba_color;
-}
-"#;
-
-// Custom structs required to provide a more friendly
-// abstraction on top of the inner working of OpenGL
-struct Color {
-    r: f32,
-    g: f32,
-    b: f32,
-    a: f32,
-}
+    fn draw(&mut self, canvas: &mut Canvas) {
+        // Clear the background
+        canvas.clear(Color::new(1.0, 1.0, 1.0, 1.0));
 
-impl Color {
-    fn new(r: f32, g: f32, b: f32, a: f32) -> Self {
-        Self { r, g, b, a }
+        draw_shapes(canvas, self.time);
+        draw_confetti(canvas);
+        self.draw_tiles(canvas);
     }
 
-    fn as_tuple(self) -> (f32, f32, f32, f32) {
-        return (self.r, self.g, self.b, self.a);
+    fn saved(&mut self, result: Result<Saved, SaveError>) {
+        match result {
+            Ok(Saved::File(path)) => println!("Saved {}", path.display()),
+            Ok(Saved::RecordingStarted(path)) => println!("Recording in {}", path.display()),
+            Ok(Saved::Recording { path, frames }) => {
+                println!("Recorded {frames} frames in {}", path.display())
+            }
+            Err(error) => eprintln!("Error: {error}"),
+        }
     }
 }
 
-#[non_exhaustive]
-enum ShapePrimitive {
-    Circle,
-    Triangle,
-}
+impl Demo {
+    /// Two rows of spinning squares: a single square mesh, drawn once per tile via instancing
+    fn draw_tiles(&self, canvas: &mut Canvas) {
+        let mut tiles = Vec::with_capacity(48);
+        for row in 0..2 {
+            for column in 0..24 {
+                let t = column as f32 / 23.0;
+                let size = 0.03;
+                let color = Color::new(0.9 - 0.6 * t, 0.3 + 0.2 * row as f32, 0.2 + 0.6 * t, 1.0);
+
+                let placement = Transform::rotation(t * std::f32::consts::PI + self.time).then(
+                    &Transform::translation(0.1 + 0.8 * t, -0.8 - 0.08 * row as f32),
+                );
+                tiles.push(Instance::from_transform(&placement, color).with_scale(size, size));
+            }
+        }
 
-struct SketchDrawCommand<'a> {
-    vertex_buffer: glium::VertexBuffer<Vertex>,
-    indices: glium::index::NoIndices,
-    uniforms:
-        glium::uniforms::UniformsStorage<'a, (f32, f32, f32, f32), glium::uniforms::EmptyUniforms>,
-    draw_parameters: glium::draw_parameters::DrawParameters<'a>,
+        if let Some(square) = &self.square {
+            canvas.draw_instances(square, &tiles);
+        }
+    }
 }
 
-/// ``color`` will be the fill color of our shape
-/// ``vertices`` should contain the exact number of vertices
-/// that will be composing our shape
-fn generate_draw_command(
-    display: &glium::Display,
-    vertices: Vec<Vertex>,
-    primitive: ShapePrimitive,
-    color: Color,
-    add_fill: bool,
-    stroke_width: Option<f32>,
-) -> SketchDrawCommand {
-    // FIXME: return the commands needed for both the fill and the stroke
-
-    let rgba_color = color.as_tuple();
-
-    // Vertex buffers are the basic ingredients that will be uploaded to the GPU
-    let vertex_buffer = glium::VertexBuffer::new(display, &vertices).unwrap();
-
-    // Tell OpenGL how to link together the vertices that we will pass
-    let primitive_type = match primitive {
-        ShapePrimitive::Circle => glium::index::PrimitiveType::LineLoop,
-        ShapePrimitive::Triangle => glium::index::PrimitiveType::TrianglesList,
-    };
-
-    let indices = glium::index::NoIndices(primitive_type);
-
-    // A uniform that will be passed to our shader
-    let uniforms = glium::uniform! {
-        requested_rgba_color: rgba_color,
-    };
-
-    let draw_parameters = glium::draw_parameters::DrawParameters {
-        multisampling: true,
-        polygon_mode: match add_fill {
-            true => glium::PolygonMode::Fill,
-            false => glium::PolygonMode::Line,
+/// A triangle, a circle, a spinning star with a hole and a curve, each with its own style
+fn draw_shapes(canvas: &mut Canvas, time: f32) {
+    /*
+    When we defined the Vertex struct in our shape, we created a field named 'position'
+    which contains the position of our vertex. But contrary to what I let you think,
+    this struct doesn't contain the actual position of the vertex but only an attribute
+    whose value is passed to the vertex shader. OpenGL doesn't care about the name of
+    the attribute, all it does is passing its value to the vertex shader.
+    The 'in vec2 position;' line of our shader is here to declare that we are expected
+    to be passed an attribute named position whose type is vec2
+    (which corresponds to [f32; 2] in Rust).
+    The main function of our shader is called once per vertex, which means three times
+    for our triangle. The first time, the value of position will be [-0.5, -0.5], the
+    second time it will be [0, 0.5], and the third time [0.5, -0.25]. It is in this
+    function that we actually tell OpenGL what the position of our vertex is, thanks
+    to the gl_Position = vec4(position, 0.0, 1.0); line.
+    We need to do a small conversion because OpenGL doesn't expect two-dimensional
+    coordinates, but four-dimensional coordinates (the reason for this will be covered in
+    a later tutorial).
+    */
+    let vertices = vec![
+        Vertex {
+            position: [-0.5, -0.5],
         },
-        line_width: stroke_width,
-        ..Default::default()
-    };
-
-    SketchDrawCommand {
-        vertex_buffer,
-        indices,
-        uniforms,
-        draw_parameters,
-    }
+        Vertex {
+            position: [0.0, 0.5],
+        },
+        Vertex {
+            position: [0.5, -0.25],
+        },
+    ];
+
+    // Strokes are real geometry, so their width is expressed in the same units
+    // as the vertices: the canvas converts pixels to drawing coordinates for us.
+    // The fill and the stroke of the triangle are described by a single style,
+    // and the canvas draws both of them in the right order
+    let triangle_style = ShapeStyle::filled(Color::new(1.0, 0.0, 0.0, 1.0))
+        .with_stroke(Color::new(1.0, 1.0, 0.0, 1.0), canvas.pixels(4.0));
+    canvas.draw_primitive(&vertices, &ShapePrimitive::Triangle, &triangle_style);
+
+    // A circle, whose vertices are generated by the tessellator
+    let circle = Ellipse::circle([0.5, 0.5], 0.25, ellipse::MIN_SEGMENTS)
+        .with_adaptive_segments(canvas.pixels_per_unit(), ellipse::DEFAULT_TOLERANCE);
+
+    // Filled with a radial gradient, mixed in OKLab so that the middle doesn't turn gray
+    let circle_gradient = Gradient::radial([0.45, 0.55], 0.3)
+        .with_colors(&[
+            Color::new(0.3, 0.9, 1.0, 1.0),
+            Color::new(0.0, 0.2, 0.8, 1.0),
+        ])
+        .with_interpolation(ColorInterpolation::Oklab);
+    let circle_style = ShapeStyle::filled(circle_gradient)
+        .with_stroke(Color::new(0.0, 0.0, 0.0, 1.0), canvas.pixels(2.0));
+    canvas.draw_primitive(&circle.outline(), &ShapePrimitive::Circle, &circle_style);
+
+    // A concave star with a square hole in the middle.
+    // The contours are joined in a single list of vertices,
+    // and the polygon keeps track of where each hole starts.
+    // The star is drawn around the origin, and then moved into place by the canvas
+    canvas.push_matrix();
+    canvas.translate(-0.5, 0.5);
+    canvas.rotate(time * 0.5);
+
+    let star_outline: Vec<Vertex> = (0..10)
+        .map(|i| {
+            let radius = if i % 2 == 0 { 0.3 } else { 0.12 };
+            let angle = std::f32::consts::TAU * i as f32 / 10.0 + std::f32::consts::FRAC_PI_2;
+            Vertex::new(radius * angle.cos(), radius * angle.sin())
+        })
+        .collect();
+    let hole_size = 0.05;
+    let star_hole: Vec<Vertex> = [[-1.0, -1.0], [1.0, -1.0], [1.0, 1.0], [-1.0, 1.0]]
+        .iter()
+        .map(|[x, y]| Vertex::new(x * hole_size, y * hole_size))
+        .collect();
+    let (star_vertices, hole_indices) = polygon::join_contours(&star_outline, &[star_hole]);
+
+    let star_style = ShapeStyle::filled(Color::new(1.0, 0.8, 0.0, 1.0))
+        .with_stroke(Color::new(0.0, 0.0, 0.0, 1.0), canvas.pixels(2.0))
+        .with_line_join(LineJoin::Round);
+    canvas.draw_primitive(
+        &star_vertices,
+        &ShapePrimitive::Polygon { hole_indices },
+        &star_style,
+    );
+    canvas.pop_matrix();
+
+    // A wavy curve built out of bezier curves, flattened to a quarter of a pixel.
+    // This time we use the Processing-like API: the style is set on the canvas,
+    // and saved/restored around the curve so that it doesn't leak into the next shapes
+    canvas.push_style();
+    canvas.no_fill();
+    canvas.stroke(Color::new(0.1, 0.6, 0.2, 1.0));
+    canvas.stroke_weight(canvas.pixels(6.0));
+    canvas.stroke_join(LineJoin::Round);
+    canvas.stroke_cap(LineCap::Round);
+
+    canvas.begin_shape();
+    canvas.vertex(0.1, -0.6);
+    canvas.bezier_vertex(0.3, -0.2, 0.5, -1.0, 0.7, -0.6);
+    canvas.quadratic_vertex(0.8, -0.4, 0.9, -0.6);
+    canvas.end_shape(EndShape::Open);
+
+    canvas.pop_style();
 }
 
-fn main() {
-    let event_loop = glutin::event_loop::EventLoop::new();
-    let window_builder = glutin::window::WindowBuilder::new().with_title("glium 101");
-
-    let context_builder = glutin::ContextBuilder::new().with_multisampling(16);
-    let display = glium::Display::new(window_builder, context_builder, &event_loop).unwrap();
-
-    implement_vertex!(Vertex, position);
-
-    // Event Loop for the Window
-    event_loop.run(move |event, _, control_flow| {
-        /*
-        When we defined the Vertex struct in our shape, we created a field named 'position'
-        which contains the position of our vertex. But contrary to what I let you think,
-        this struct doesn't contain the actual position of the vertex but only an attribute
-        whose value is passed to the vertex shader. OpenGL doesn't care about the name of
-        the attribute, all it does is passing its value to the vertex shader.
-        The 'in vec2 position;' line of our shader is here to declare that we are expected
-        to be passed an attribute named position whose type is vec2
-        (which corresponds to [f32; 2] in Rust).
-        The main function of our shader is called once per vertex, which means three times
-        for our triangle. The first time, the value of position will be [-0.5, -0.5], the
-        second time it will be [0, 0.5], and the third time [0.5, -0.25]. It is in this
-        function that we actually tell OpenGL what the position of our vertex is, thanks
-        to the gl_Position = vec4(position, 0.0, 1.0); line.
-        We need to do a small conversion because OpenGL doesn't expect two-dimensional
-        coordinates, but four-dimensional coordinates (the reason for this will be covered in
-        a later tutorial).
-        */
-
-        let geometry_shader = None;
-
-        let program = glium::Program::from_source(
-            &display,
-            VERTEX_SHADER_SRC,
-            FRAGMENT_SHADER_SRC,
-            geometry_shader,
+/// Hundreds of tiny translucent circles along a spiral.
+/// The canvas batches them all in a single draw call
+fn draw_confetti(canvas: &mut Canvas) {
+    let center = [-0.5, -0.5];
+    for i in 0..600 {
+        let t = i as f32 / 600.0;
+        let angle = t * 12.0 * std::f32::consts::PI;
+        let distance = 0.05 + t * 0.3;
+
+        let dot = Ellipse::circle(
+            [
+                center[0] + distance * angle.cos(),
+                center[1] + distance * angle.sin(),
+            ],
+            0.012,
+            ellipse::MIN_SEGMENTS,
         )
-        .unwrap();
+        .with_adaptive_segments(canvas.pixels_per_unit(), ellipse::DEFAULT_TOLERANCE);
 
-        // Here we do the actual drawing into the frame
-        let mut frame = display.draw();
+        // Overlapping dots get darker, like ink
+        let style = ShapeStyle::filled(Color::new(t, 0.2, 1.0 - t, 0.7))
+            .with_blend_mode(BlendMode::Multiply);
+        canvas.draw_primitive(&dot.outline(), &ShapePrimitive::Circle, &style);
+    }
+}
 
-        // Clear the background
-        frame.clear_color(1.0, 1.0, 1.0, 1.0);
-
-        // Here we draw our custom shape by sending the vertices and the shaders
-        // The 'draw command' (which contains all of the instructions for drawing)
-        // is generated programmatically based on the primitive that we need to render
-        let vertices = vec![
-            Vertex {
-                position: [-0.5, -0.5],
-            },
-            Vertex {
-                position: [0.0, 0.5],
-            },
-            Vertex {
-                position: [0.5, -0.25],
-            },
-        ];
-
-        // Fill of the triangle
-        let add_fill = true;
-        let stroke_width = None;
-        let triangle_color = Color::new(1.0, 0.0, 0.0, 0.0);
-        let triangle_draw_command = generate_draw_command(
-            &display,
-            vertices.clone(),
-            ShapePrimitive::Triangle,
-            triangle_color,
-            add_fill,
-            stroke_width,
-        );
-
-        frame
-            .draw(
-                &triangle_draw_command.vertex_buffer,
-                &triangle_draw_command.indices,
-                &program,
-                &triangle_draw_command.uniforms,
-                &triangle_draw_command.draw_parameters,
-            )
-            .unwrap();
-
-        // Stroke of the triangle
-        let add_fill = false;
-        let stroke_width = Some(4.0 as f32);
-        let triangle_color = Color::new(1.0, 1.0, 0.0, 0.0);
-        let triangle_draw_command = generate_draw_command(
-            &display,
-            vertices.clone(),
-            ShapePrimitive::Triangle,
-            triangle_color,
-            add_fill,
-            stroke_width,
-        );
-
-        frame
-            .draw(
-                &triangle_draw_command.vertex_buffer,
-                &triangle_draw_command.indices,
-                &program,
-                &triangle_draw_command.uniforms,
-                &triangle_draw_command.draw_parameters,
-            )
-            .unwrap();
-
-        frame.finish().unwrap();
-
-        let next_frame_time =
-            std::time::Instant::now() + std::time::Duration::from_nanos(16_666_667);
-
-        *control_flow = glutin::event_loop::ControlFlow::WaitUntil(next_frame_time);
-
-        match event {
-            glutin::event::Event::WindowEvent { window_id, event } => match event {
-                glutin::event::WindowEvent::CloseRequested => {
-                    *control_flow = glutin::event_loop::ControlFlow::Exit;
-                    return;
-                }
-                _ => return,
-            },
-            glutin::event::Event::NewEvents(cause) => match cause {
-                glutin::event::StartCause::ResumeTimeReached { .. } => (),
-                glutin::event::StartCause::Init => (),
-                _ => return,
-            },
-            _ => (),
-        }
-    });
+fn main() {
+    run_sketch(Demo::default(), SketchSettings::new("glium 101"));
 }
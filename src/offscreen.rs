@@ -0,0 +1,456 @@
+//! Drawing into textures instead of windows, and reading the pixels back.
+//!
+//! An ``OffscreenTarget`` is an sRGB texture (plus a multisampled one, resolved into it
+//! after drawing) that can be passed to ``Canvas::render`` like the frame of a window.
+//! Being sRGB, it works with both color pipelines, and its pixels can be read back as
+//! they would appear on screen.
+
+use std::fmt;
+use std::io::BufWriter;
+use std::rc::Rc;
+
+use glium::backend::{Context, Facade};
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::texture::{
+    MipmapsOption, SrgbFormat, SrgbTexture2d, SrgbTexture2dMultisample, TextureCreationError,
+    TextureFormat,
+};
+use glium::uniforms::MagnifySamplerFilter;
+use glium::{BlitTarget, CapabilitiesSource, Surface};
+
+use crate::color::{linear_to_srgb, srgb_to_linear};
+use crate::color_pipeline::ColorPipeline;
+use crate::instancing::InstancingError;
+
+#[derive(Debug)]
+pub enum OffscreenError {
+    Texture(TextureCreationError),
+    Draw(InstancingError),
+    /// The image couldn't be written
+    Io(std::io::Error),
+}
+
+impl fmt::Display for OffscreenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OffscreenError::Texture(error) => {
+                write!(f, "could not create the offscreen target: {error}")
+            }
+            OffscreenError::Draw(error) => write!(f, "could not draw the frame: {error}"),
+            OffscreenError::Io(error) => write!(f, "could not write the image: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for OffscreenError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            OffscreenError::Texture(error) => Some(error),
+            OffscreenError::Draw(error) => Some(error),
+            OffscreenError::Io(error) => Some(error),
+        }
+    }
+}
+
+impl From<TextureCreationError> for OffscreenError {
+    fn from(error: TextureCreationError) -> Self {
+        OffscreenError::Texture(error)
+    }
+}
+
+impl From<InstancingError> for OffscreenError {
+    fn from(error: InstancingError) -> Self {
+        OffscreenError::Draw(error)
+    }
+}
+
+impl From<std::io::Error> for OffscreenError {
+    fn from(error: std::io::Error) -> Self {
+        OffscreenError::Io(error)
+    }
+}
+
+/// 8 bits per channel sRGB pixels, with straight (not premultiplied) alpha like in image files
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RgbaImage {
+    pub width: u32,
+    pub height: u32,
+    /// Rows from top to bottom, 4 bytes per pixel
+    pub data: Vec<u8>,
+    /// The pixels as they were rendered, when read from an ``OffscreenTarget``
+    framebuffer: Option<Framebuffer>,
+}
+
+/// Premultiplied pixels read back from the GPU (rows from top to bottom), kept for
+/// ``save_exr``: un-premultiplying them to 8 bits loses precision in the translucent parts
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Framebuffer {
+    color_pipeline: ColorPipeline,
+    premultiplied: Vec<u8>,
+}
+
+impl Framebuffer {
+    /// The pixel starting at ``index``, as linear values premultiplied by alpha
+    fn linear_premultiplied(&self, index: usize, to_linear: &[f32]) -> [f32; 4] {
+        let pixel = &self.premultiplied[index..index + 4];
+        let alpha = pixel[3] as f32 / 255.0;
+        let linear = |channel: u8| match self.color_pipeline {
+            // The sRGB texture encoded the linear values once premultiplied
+            ColorPipeline::Linear => to_linear[channel as usize],
+            // The shaders wrote sRGB values premultiplied by alpha, untouched
+            ColorPipeline::Srgb if alpha > 0.0 => {
+                srgb_to_linear((channel as f32 / 255.0 / alpha).min(1.0)) * alpha
+            }
+            ColorPipeline::Srgb => 0.0,
+        };
+        [linear(pixel[0]), linear(pixel[1]), linear(pixel[2]), alpha]
+    }
+}
+
+/// ``srgb_to_linear`` for every 8 bits value
+fn linear_table() -> Vec<f32> {
+    (0..=255)
+        .map(|channel| srgb_to_linear(channel as f32 / 255.0))
+        .collect()
+}
+
+impl RgbaImage {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            data: vec![0; width as usize * height as usize * 4],
+            framebuffer: None,
+        }
+    }
+
+    /// The pixel in column ``x`` and row ``y``, counting from the top left corner
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let start = (y as usize * self.width as usize + x as usize) * 4;
+        self.data[start..start + 4].try_into().unwrap()
+    }
+
+    /// Writes the image as an 8 bits RGBA PNG, tagged as sRGB
+    pub fn save_png(&self, file_path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+        let file = BufWriter::new(std::fs::File::create(file_path)?);
+        let mut encoder = png::Encoder::new(file, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_srgb(png::SrgbRenderingIntent::Perceptual);
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.data)?;
+        writer.finish()?;
+        Ok(())
+    }
+
+    /// Writes the image as an 8 bits RGBA TIFF, with straight alpha
+    pub fn save_tiff(&self, file_path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+        let file = BufWriter::new(std::fs::File::create(file_path)?);
+        tiff::encoder::TiffEncoder::new(file)
+            .and_then(|mut encoder| {
+                encoder.write_image::<tiff::encoder::colortype::RGBA8>(
+                    self.width,
+                    self.height,
+                    &self.data,
+                )
+            })
+            .map_err(tiff_error)
+    }
+
+    /// Writes the image as a half float OpenEXR file. Following the conventions of the format,
+    /// colors are linear and premultiplied by alpha
+    pub fn save_exr(&self, file_path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+        use exr::prelude::f16;
+
+        let to_linear = linear_table();
+        let pixel = |x: usize, y: usize| {
+            let [r, g, b, a] = self.linear_premultiplied(x, y, &to_linear);
+            let half = f16::from_f32;
+            (half(r), half(g), half(b), half(a))
+        };
+
+        exr::prelude::write_rgba_file(file_path, self.width as usize, self.height as usize, pixel)
+            .map_err(|error| match error {
+                exr::error::Error::Io(error) => error,
+                error => std::io::Error::other(error.to_string()),
+            })
+    }
+
+    /// The pixel in column ``x`` and row ``y``, as linear values premultiplied by alpha
+    fn linear_premultiplied(&self, x: usize, y: usize, to_linear: &[f32]) -> [f32; 4] {
+        let index = (y * self.width as usize + x) * 4;
+        if let Some(framebuffer) = &self.framebuffer {
+            return framebuffer.linear_premultiplied(index, to_linear);
+        }
+
+        let pixel = &self.data[index..index + 4];
+        let alpha = pixel[3] as f32 / 255.0;
+        let channel = |value: u8| to_linear[value as usize] * alpha;
+        [
+            channel(pixel[0]),
+            channel(pixel[1]),
+            channel(pixel[2]),
+            alpha,
+        ]
+    }
+
+    /// Converts the rows read from OpenGL, which go from the bottom to the top
+    /// and are premultiplied by alpha (see ``BlendMode::blend``). How they were
+    /// premultiplied depends on ``color_pipeline``: with ``Linear``, the sRGB texture
+    /// holds the sRGB encoding of the linear color times alpha, so it has to be decoded
+    /// before dividing by alpha, and encoded again
+    fn from_framebuffer(
+        width: u32,
+        height: u32,
+        bottom_up: &[u8],
+        color_pipeline: ColorPipeline,
+    ) -> Self {
+        let row_length = width as usize * 4;
+        let premultiplied: Vec<u8> = bottom_up
+            .chunks_exact(row_length)
+            .rev()
+            .flatten()
+            .copied()
+            .collect();
+
+        let to_linear = linear_table();
+        let mut data = Vec::with_capacity(premultiplied.len());
+        for pixel in premultiplied.chunks_exact(4) {
+            let alpha = pixel[3];
+            let unpremultiply = |channel: u8| match (alpha, color_pipeline) {
+                (0, _) => 0,
+                (255, _) => channel,
+                (_, ColorPipeline::Srgb) => {
+                    ((channel as u32 * 255 + alpha as u32 / 2) / alpha as u32).min(255) as u8
+                }
+                (_, ColorPipeline::Linear) => {
+                    let linear = to_linear[channel as usize] / (alpha as f32 / 255.0);
+                    (linear_to_srgb(linear.min(1.0)) * 255.0).round() as u8
+                }
+            };
+            data.extend([
+                unpremultiply(pixel[0]),
+                unpremultiply(pixel[1]),
+                unpremultiply(pixel[2]),
+                alpha,
+            ]);
+        }
+
+        Self {
+            width,
+            height,
+            data,
+            framebuffer: Some(Framebuffer {
+                color_pipeline,
+                premultiplied,
+            }),
+        }
+    }
+}
+
+pub struct OffscreenTarget {
+    context: Rc<Context>,
+    /// Where the pixels end up
+    texture: SrgbTexture2d,
+    /// Drawn into instead of ``texture`` when anti-aliasing is enabled
+    multisampled: Option<SrgbTexture2dMultisample>,
+}
+
+impl OffscreenTarget {
+    /// ``samples`` is the number of samples used for anti-aliasing (0 or 1 to disable it).
+    /// Contexts that don't support that many samples fall back to no anti-aliasing
+    pub fn new(
+        facade: &impl Facade,
+        width: u32,
+        height: u32,
+        samples: u16,
+    ) -> Result<Self, TextureCreationError> {
+        let texture = SrgbTexture2d::empty_with_format(
+            facade,
+            SrgbFormat::U8U8U8U8,
+            MipmapsOption::NoMipmap,
+            width,
+            height,
+        )?;
+        let samples = supported_samples(facade, samples);
+        let multisampled = if samples > 1 {
+            SrgbTexture2dMultisample::empty_with_format(
+                facade,
+                SrgbFormat::U8U8U8U8,
+                MipmapsOption::NoMipmap,
+                width,
+                height,
+                samples,
+            )
+            .ok()
+        } else {
+            None
+        };
+
+        Ok(Self {
+            context: facade.get_context().clone(),
+            texture,
+            multisampled,
+        })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.texture.width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.texture.height()
+    }
+
+    pub fn is_multisampled(&self) -> bool {
+        self.multisampled.is_some()
+    }
+
+    /// The texture holding the result, once ``draw`` is done
+    pub fn texture(&self) -> &SrgbTexture2d {
+        &self.texture
+    }
+
+    /// Calls ``draw`` with a surface to draw on (like ``Canvas::render``),
+    /// then resolves the anti-aliasing samples into ``texture``
+    pub fn draw<R>(&self, draw: impl FnOnce(&mut SimpleFrameBuffer) -> R) -> R {
+        // Our own textures are always complete, so the framebuffers are valid
+        let mut resolved = SimpleFrameBuffer::new(&self.context, &self.texture).unwrap();
+
+        let Some(multisampled) = &self.multisampled else {
+            return draw(&mut resolved);
+        };
+
+        let mut framebuffer = SimpleFrameBuffer::new(&self.context, multisampled).unwrap();
+        let result = draw(&mut framebuffer);
+        let (width, height) = (self.width() as i32, self.height() as i32);
+        framebuffer.blit_whole_color_to(
+            &resolved,
+            &BlitTarget {
+                left: 0,
+                bottom: 0,
+                width,
+                height,
+            },
+            MagnifySamplerFilter::Nearest,
+        );
+        result
+    }
+
+    /// Copies the pixels of ``texture`` back from the GPU, top row first.
+    /// ``color_pipeline`` is the one of the canvas that rendered them
+    pub fn read_pixels(&self, color_pipeline: ColorPipeline) -> RgbaImage {
+        let image: glium::texture::RawImage2d<u8> = self.texture.read();
+        RgbaImage::from_framebuffer(image.width, image.height, &image.data, color_pipeline)
+    }
+}
+
+/// The highest number of samples up to ``requested`` that multisampled sRGB textures
+/// support. Asking for more doesn't fail but gives an unusable texture (llvmpipe stops at 8,
+/// while windows default to 16)
+fn supported_samples(facade: &impl Facade, requested: u16) -> u32 {
+    let requested = requested as u32;
+    let format = TextureFormat::Srgb(SrgbFormat::U8U8U8U8);
+    let capabilities = facade.get_capabilities();
+
+    let supported = capabilities
+        .internal_formats_textures
+        .get(&format)
+        .and_then(|infos| infos.multisamples.as_ref());
+    match supported {
+        Some(counts) => counts
+            .iter()
+            .map(|&count| count as u32)
+            .filter(|&count| count <= requested)
+            .max()
+            .unwrap_or(0),
+        // Unknown: all we can do is trust the maximum of framebuffers
+        None => capabilities
+            .max_framebuffer_samples
+            .map_or(requested, |max| requested.min(max as u32)),
+    }
+}
+
+/// Keeps the IO errors as they are, with their kind
+pub(crate) fn tiff_error(error: tiff::TiffError) -> std::io::Error {
+    match error {
+        tiff::TiffError::IoError(error) => error,
+        error => std::io::Error::other(error.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One translucent sRGB color, premultiplied the way each pipeline writes it to the
+    /// sRGB texture. The second row is the top one, since OpenGL rows go upwards
+    fn framebuffer(color_pipeline: ColorPipeline, [r, g, b]: [u8; 3], alpha: u8) -> Vec<u8> {
+        let a = alpha as f32 / 255.0;
+        let premultiply = |channel: u8| {
+            let srgb = channel as f32 / 255.0;
+            let written = match color_pipeline {
+                ColorPipeline::Linear => linear_to_srgb(srgb_to_linear(srgb) * a),
+                ColorPipeline::Srgb => srgb * a,
+            };
+            (written * 255.0).round() as u8
+        };
+        let mut pixels = vec![0, 0, 0, 0];
+        pixels.extend([premultiply(r), premultiply(g), premultiply(b), alpha]);
+        pixels
+    }
+
+    fn assert_close(actual: &[u8], expected: &[u8]) {
+        for (a, e) in actual.iter().zip(expected) {
+            assert!(a.abs_diff(*e) <= 1, "expected {expected:?}, got {actual:?}");
+        }
+    }
+
+    #[test]
+    fn unpremultiplies_each_pipeline() {
+        for color_pipeline in [ColorPipeline::Linear, ColorPipeline::Srgb] {
+            for alpha in [64, 128, 200] {
+                let pixels = framebuffer(color_pipeline, [200, 120, 40], alpha);
+                let image = RgbaImage::from_framebuffer(1, 2, &pixels, color_pipeline);
+                assert_close(&image.pixel(0, 0), &[200, 120, 40, alpha]);
+                assert_eq!(image.pixel(0, 1), [0, 0, 0, 0]);
+            }
+        }
+    }
+
+    #[test]
+    fn linear_framebuffers_are_not_divided_as_srgb() {
+        let pixels = framebuffer(ColorPipeline::Linear, [200, 200, 200], 128);
+        let image = RgbaImage::from_framebuffer(1, 2, &pixels, ColorPipeline::Linear);
+        // Dividing the encoded bytes by alpha would give a far brighter 255
+        assert_close(&image.pixel(0, 0), &[200, 200, 200, 128]);
+    }
+
+    #[test]
+    fn opaque_and_transparent_pixels_are_kept() {
+        let pixels = [10, 20, 30, 255, 0, 0, 0, 0];
+        for color_pipeline in [ColorPipeline::Linear, ColorPipeline::Srgb] {
+            let image = RgbaImage::from_framebuffer(2, 1, &pixels, color_pipeline);
+            assert_eq!(image.data, pixels);
+        }
+    }
+
+    #[test]
+    fn exr_values_come_from_the_framebuffer() {
+        let to_linear = linear_table();
+        let alpha = 0.5;
+        // Premultiplied linear values that don't survive a round trip through 8 bits
+        let linear = 0.003;
+        let byte = (linear_to_srgb(linear) * 255.0).round() as u8;
+        let pixels = [0, 0, 0, 0, byte, byte, byte, 128];
+        let image = RgbaImage::from_framebuffer(1, 2, &pixels, ColorPipeline::Linear);
+
+        let [r, _, _, a] = image.linear_premultiplied(0, 0, &to_linear);
+        assert_eq!(r, to_linear[byte as usize]);
+        assert!((a - alpha).abs() < 0.01);
+
+        let [_, g, _, _] = image.linear_premultiplied(0, 1, &to_linear);
+        assert_eq!(g, 0.0);
+    }
+}
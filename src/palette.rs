@@ -0,0 +1,712 @@
+//! Palettes: a handful of colors picked by hand (or by an art director).
+//!
+//! Palettes can be loaded from the files design tools export (GIMP ``.gpl``,
+//! Adobe ``.ase``, coolors-like JSON, or plain lists of hex codes) or generated
+//! from a single color with the classic harmonies of the color wheel.
+//! Sketches then pick their colors by index or at random, optionally favoring
+//! some colors over the others with weights.
+
+use std::fmt;
+
+use crate::color::Color;
+
+#[derive(Debug)]
+pub enum PaletteError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    /// The content doesn't follow ``format``
+    Format {
+        format: &'static str,
+        message: String,
+    },
+}
+
+impl fmt::Display for PaletteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PaletteError::Io(error) => write!(f, "could not read the palette file: {error}"),
+            PaletteError::Json(error) => write!(f, "invalid JSON palette: {error}"),
+            PaletteError::Format { format, message } => {
+                write!(f, "invalid {format} palette: {message}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PaletteError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PaletteError::Io(error) => Some(error),
+            PaletteError::Json(error) => Some(error),
+            PaletteError::Format { .. } => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for PaletteError {
+    fn from(error: std::io::Error) -> Self {
+        PaletteError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for PaletteError {
+    fn from(error: serde_json::Error) -> Self {
+        PaletteError::Json(error)
+    }
+}
+
+fn format_error(format: &'static str, message: impl Into<String>) -> PaletteError {
+    PaletteError::Format {
+        format,
+        message: message.into(),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PaletteEntry {
+    pub color: Color,
+    /// The name given by the palette file, if any
+    pub name: Option<String>,
+    /// How likely the color is to be picked by ``Palette::weighted_random``,
+    /// relative to the others. Defaults to 1
+    pub weight: f32,
+}
+
+impl PaletteEntry {
+    pub fn new(color: Color) -> Self {
+        Self {
+            color,
+            name: None,
+            weight: 1.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Palette {
+    pub name: Option<String>,
+    pub entries: Vec<PaletteEntry>,
+}
+
+impl Palette {
+    pub fn new(colors: impl IntoIterator<Item = Color>) -> Self {
+        Self {
+            name: None,
+            entries: colors.into_iter().map(PaletteEntry::new).collect(),
+        }
+    }
+
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Sets the weights of the colors, in order. Colors without a weight keep theirs
+    pub fn with_weights(mut self, weights: &[f32]) -> Self {
+        for (entry, &weight) in self.entries.iter_mut().zip(weights) {
+            entry.weight = weight;
+        }
+        self
+    }
+
+    pub fn push(&mut self, color: Color) {
+        self.entries.push(PaletteEntry::new(color));
+    }
+
+    pub fn push_weighted(&mut self, color: Color, weight: f32) {
+        self.entries.push(PaletteEntry {
+            weight,
+            ..PaletteEntry::new(color)
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn colors(&self) -> impl Iterator<Item = Color> + '_ {
+        self.entries.iter().map(|entry| entry.color)
+    }
+
+    /// The color at ``index``, going back to the first one after the last:
+    /// handy to cycle through the palette with a counter.
+    /// Empty palettes give transparent black
+    pub fn color(&self, index: usize) -> Color {
+        match self.entries.len() {
+            0 => Color::new(0.0, 0.0, 0.0, 0.0),
+            len => self.entries[index % len].color,
+        }
+    }
+
+    /// The first color with the given name
+    pub fn named(&self, name: &str) -> Option<Color> {
+        self.entries
+            .iter()
+            .find(|entry| entry.name.as_deref() == Some(name))
+            .map(|entry| entry.color)
+    }
+
+    /// A color picked at random, each one being as likely as the others
+    pub fn random(&self) -> Color {
+        self.random_with(&mut fastrand::Rng::new())
+    }
+
+    /// Same as ``random``, with a generator that can be seeded
+    /// to get the same colors every time the sketch runs
+    pub fn random_with(&self, rng: &mut fastrand::Rng) -> Color {
+        match self.entries.len() {
+            0 => self.color(0),
+            len => self.color(rng.usize(..len)),
+        }
+    }
+
+    /// A color picked at random, favoring the ones with the biggest weights
+    pub fn weighted_random(&self) -> Color {
+        self.weighted_random_with(&mut fastrand::Rng::new())
+    }
+
+    /// Same as ``weighted_random``, with a generator that can be seeded
+    pub fn weighted_random_with(&self, rng: &mut fastrand::Rng) -> Color {
+        let weight = |entry: &PaletteEntry| entry.weight.max(0.0);
+        let total: f32 = self.entries.iter().map(weight).sum();
+        if total <= 0.0 {
+            return self.random_with(rng);
+        }
+
+        let mut target = rng.f32() * total;
+        for entry in &self.entries {
+            target -= weight(entry);
+            if target < 0.0 {
+                return entry.color;
+            }
+        }
+        // Rounding errors can leave a tiny bit of the target behind
+        self.entries
+            .iter()
+            .rev()
+            .find(|entry| weight(entry) > 0.0)
+            .map_or(self.color(0), |entry| entry.color)
+    }
+
+    /// The color and the one opposite to it on the color wheel
+    pub fn complementary(color: Color) -> Self {
+        Self::new([color, rotate_hue(color, 180.0)])
+    }
+
+    /// The color and the two colors spaced evenly around the color wheel from it
+    pub fn triadic(color: Color) -> Self {
+        Self::new([color, rotate_hue(color, 120.0), rotate_hue(color, 240.0)])
+    }
+
+    /// The color between its two neighbours, ``angle`` degrees away on each side
+    /// (30 degrees being the usual choice)
+    pub fn analogous(color: Color, angle: f32) -> Self {
+        Self::new([rotate_hue(color, -angle), color, rotate_hue(color, angle)])
+    }
+
+    /// Loads a palette file, whose format is guessed from its extension:
+    /// ``.gpl``, ``.ase`` and ``.json``. Anything else is read as a list of hex codes
+    pub fn load(file_path: impl AsRef<std::path::Path>) -> Result<Self, PaletteError> {
+        let file_path = file_path.as_ref();
+        let extension = file_path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_ascii_lowercase());
+
+        match extension.as_deref() {
+            Some("ase") => Self::from_ase(&std::fs::read(file_path)?),
+            Some("gpl") => Self::from_gpl(&std::fs::read_to_string(file_path)?),
+            Some("json") => Self::from_json(&std::fs::read_to_string(file_path)?),
+            _ => Self::from_hex_list(&std::fs::read_to_string(file_path)?),
+        }
+    }
+
+    /// Hex codes separated by spaces, commas, semicolons, slashes, new lines or dashes, with
+    /// or without ``#`` (or quotes), which covers most of what gets copied from the web,
+    /// coolors URLs included. Lines starting with ``//`` are ignored
+    pub fn from_hex_list(text: &str) -> Result<Self, PaletteError> {
+        let mut palette = Palette::default();
+
+        for line in text.lines() {
+            let line = line.trim();
+            if line.starts_with("//") {
+                continue;
+            }
+            // Only the end of URLs (like https://coolors.co/264653-2a9d8f) has colors
+            let line = if line.contains("://") {
+                line.rsplit('/').next().unwrap_or(line)
+            } else {
+                line
+            };
+
+            let separators = |c: char| c.is_whitespace() || ",;-/\"'".contains(c);
+            for code in line.split(separators).filter(|code| !code.is_empty()) {
+                let color = Color::from_hex(code)
+                    .ok_or_else(|| format_error("hex", format!("{code:?} is not a hex color")))?;
+                palette.push(color);
+            }
+        }
+
+        Ok(palette)
+    }
+
+    /// A GIMP palette: a ``GIMP Palette`` header, optional ``Name:`` and ``Columns:``
+    /// lines, then one ``red green blue name`` line per color (channels from 0 to 255)
+    pub fn from_gpl(text: &str) -> Result<Self, PaletteError> {
+        let mut lines = text.lines().enumerate();
+        if lines.next().map(|(_, line)| line.trim()) != Some("GIMP Palette") {
+            return Err(format_error("GIMP", "missing the \"GIMP Palette\" header"));
+        }
+
+        let mut palette = Palette::default();
+        for (index, line) in lines {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with("Columns:") {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix("Name:") {
+                palette.name = Some(name.trim().to_string());
+                continue;
+            }
+
+            let invalid = || format_error("GIMP", format!("invalid color on line {}", index + 1));
+            let mut parts = line.split_whitespace();
+            let mut channel = || -> Result<u8, PaletteError> {
+                let value = parts.next().ok_or_else(invalid)?;
+                // Some tools write channels as floats
+                let value: f32 = value.parse().map_err(|_| invalid())?;
+                Ok(value.round().clamp(0.0, 255.0) as u8)
+            };
+            let color = Color::from_rgba8(channel()?, channel()?, channel()?, 255);
+
+            let name = parts.collect::<Vec<_>>().join(" ");
+            palette.entries.push(PaletteEntry {
+                // GIMP names unnamed colors "Untitled"
+                name: Some(name).filter(|name| !name.is_empty() && name != "Untitled"),
+                ..PaletteEntry::new(color)
+            });
+        }
+
+        Ok(palette)
+    }
+
+    /// An Adobe Swatch Exchange file. Groups are flattened, and CMYK, Lab
+    /// and gray swatches are converted to RGB (without color management)
+    pub fn from_ase(bytes: &[u8]) -> Result<Self, PaletteError> {
+        let mut reader = AseReader { bytes, position: 0 };
+        if reader.take(4)? != b"ASEF" {
+            return Err(format_error("ASE", "missing the \"ASEF\" signature"));
+        }
+        // Version, always 1.0
+        reader.take(4)?;
+
+        let mut palette = Palette::default();
+        let block_count = reader.u32()?;
+        for _ in 0..block_count {
+            let block_type = reader.u16()?;
+            let length = reader.u32()? as usize;
+            let mut block = AseReader {
+                bytes: reader.take(length)?,
+                position: 0,
+            };
+
+            const COLOR_ENTRY: u16 = 0x0001;
+            const GROUP_START: u16 = 0xc001;
+            match block_type {
+                COLOR_ENTRY => palette.entries.push(block.color_entry()?),
+                GROUP_START if palette.name.is_none() => palette.name = Some(block.name()?),
+                // The end of groups, and anything newer than this parser
+                _ => (),
+            }
+        }
+
+        Ok(palette)
+    }
+
+    /// JSON as exported by coolors and similar tools: an array of hex codes,
+    /// an array of objects with a ``hex`` (and maybe a ``name``) field,
+    /// an object mapping names to hex codes (kept in the order of the file),
+    /// or any of these under a ``colors`` field
+    pub fn from_json(text: &str) -> Result<Self, PaletteError> {
+        use serde_json::Value;
+
+        let value: Value = serde_json::from_str(text)?;
+        let mut palette = Palette::default();
+
+        let parse = |value: &Value| -> Result<Color, PaletteError> {
+            let code = value
+                .as_str()
+                .ok_or_else(|| format_error("JSON", format!("{value} is not a hex code")))?;
+            Color::from_hex(code)
+                .ok_or_else(|| format_error("JSON", format!("{code:?} is not a hex color")))
+        };
+
+        let (name, colors) = match &value {
+            Value::Object(object) if object.contains_key("colors") => (
+                object.get("name").and_then(Value::as_str),
+                &object["colors"],
+            ),
+            _ => (None, &value),
+        };
+        palette.name = name.map(str::to_string);
+
+        match colors {
+            Value::Array(items) => {
+                for item in items {
+                    let entry = match item {
+                        Value::Object(object) => PaletteEntry {
+                            name: object
+                                .get("name")
+                                .and_then(Value::as_str)
+                                .map(str::to_string),
+                            ..PaletteEntry::new(parse(object.get("hex").unwrap_or(&Value::Null))?)
+                        },
+                        _ => PaletteEntry::new(parse(item)?),
+                    };
+                    palette.entries.push(entry);
+                }
+            }
+            Value::Object(object) => {
+                for (name, code) in object {
+                    palette.entries.push(PaletteEntry {
+                        name: Some(name.clone()),
+                        ..PaletteEntry::new(parse(code)?)
+                    });
+                }
+            }
+            _ => {
+                return Err(format_error(
+                    "JSON",
+                    "expected an array or an object of colors",
+                ))
+            }
+        }
+
+        Ok(palette)
+    }
+}
+
+/// Turns the hue of ``color`` by ``degrees`` around the HSL color wheel
+fn rotate_hue(color: Color, degrees: f32) -> Color {
+    let [hue, saturation, lightness] = color.to_hsl();
+    Color::from_hsl(
+        (hue + degrees).rem_euclid(360.0),
+        saturation,
+        lightness,
+        color.a,
+    )
+}
+
+/// Reads the big-endian values of ASE files
+struct AseReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> AseReader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], PaletteError> {
+        let bytes = self
+            .bytes
+            .get(self.position..self.position + count)
+            .ok_or_else(|| format_error("ASE", "unexpected end of file"))?;
+        self.position += count;
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> Result<u16, PaletteError> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, PaletteError> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn f32(&mut self) -> Result<f32, PaletteError> {
+        Ok(f32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    /// A UTF-16 string, preceded by its length and followed by a null character
+    fn name(&mut self) -> Result<String, PaletteError> {
+        let length = self.u16()? as usize;
+        let units = (0..length)
+            .map(|_| self.u16())
+            .collect::<Result<Vec<_>, _>>()?;
+        let name = String::from_utf16_lossy(&units);
+        Ok(name.trim_end_matches('\0').to_string())
+    }
+
+    fn color_entry(&mut self) -> Result<PaletteEntry, PaletteError> {
+        let name = self.name()?;
+        let model = self.take(4)?;
+
+        let color = match model {
+            b"RGB " => Color::new(self.f32()?, self.f32()?, self.f32()?, 1.0),
+            b"CMYK" => {
+                let [c, m, y, k] = [self.f32()?, self.f32()?, self.f32()?, self.f32()?];
+                Color::new(
+                    (1.0 - c) * (1.0 - k),
+                    (1.0 - m) * (1.0 - k),
+                    (1.0 - y) * (1.0 - k),
+                    1.0,
+                )
+            }
+            // Lightness is stored between 0 and 1
+            b"LAB " => lab_to_color(self.f32()? * 100.0, self.f32()?, self.f32()?),
+            b"Gray" => {
+                let gray = self.f32()?;
+                Color::new(gray, gray, gray, 1.0)
+            }
+            _ => {
+                let model = String::from_utf8_lossy(model);
+                return Err(format_error(
+                    "ASE",
+                    format!("unknown color model {model:?}"),
+                ));
+            }
+        };
+
+        Ok(PaletteEntry {
+            name: Some(name).filter(|name| !name.is_empty()),
+            ..PaletteEntry::new(color.clamped())
+        })
+    }
+}
+
+/// CIE L*a*b* (with the D50 white point used by Adobe) to sRGB
+fn lab_to_color(lightness: f32, a: f32, b: f32) -> Color {
+    let fy = (lightness + 16.0) / 116.0;
+    let fx = fy + a / 500.0;
+    let fz = fy - b / 200.0;
+
+    let inverse = |t: f32| {
+        if t > 6.0 / 29.0 {
+            t * t * t
+        } else {
+            3.0 * (6.0f32 / 29.0).powi(2) * (t - 4.0 / 29.0)
+        }
+    };
+    let [x, y, z] = [0.96422 * inverse(fx), inverse(fy), 0.82521 * inverse(fz)];
+
+    // XYZ (D50) to linear sRGB, adapted to D65 with the Bradford transform
+    Color::from_linear_rgb(
+        3.133_856 * x - 1.616_867 * y - 0.490_615 * z,
+        -0.978_768 * x + 1.916_142 * y + 0.033_454 * z,
+        0.071_945 * x - 0.228_991 * y + 1.405_243 * z,
+        1.0,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex_codes(palette: &Palette) -> Vec<String> {
+        palette.colors().map(|color| color.to_hex()).collect()
+    }
+
+    fn names(palette: &Palette) -> Vec<Option<&str>> {
+        palette
+            .entries
+            .iter()
+            .map(|entry| entry.name.as_deref())
+            .collect()
+    }
+
+    /// A name the way ASE files store them: length, big-endian UTF-16 and a null character
+    fn ase_name(name: &str) -> Vec<u8> {
+        let units: Vec<u16> = name.encode_utf16().chain([0]).collect();
+        let mut bytes = (units.len() as u16).to_be_bytes().to_vec();
+        bytes.extend(units.iter().flat_map(|unit| unit.to_be_bytes()));
+        bytes
+    }
+
+    fn ase_color(name: &str, model: &[u8; 4], values: &[f32]) -> (u16, Vec<u8>) {
+        let mut data = ase_name(name);
+        data.extend_from_slice(model);
+        data.extend(values.iter().flat_map(|value| value.to_be_bytes()));
+        // The color type (global, spot or normal), which isn't read
+        data.extend_from_slice(&2u16.to_be_bytes());
+        (0x0001, data)
+    }
+
+    fn ase_file(blocks: &[(u16, Vec<u8>)]) -> Vec<u8> {
+        let mut bytes = b"ASEF".to_vec();
+        bytes.extend_from_slice(&[0, 1, 0, 0]);
+        bytes.extend_from_slice(&(blocks.len() as u32).to_be_bytes());
+        for (block_type, data) in blocks {
+            bytes.extend_from_slice(&block_type.to_be_bytes());
+            bytes.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(data);
+        }
+        bytes
+    }
+
+    #[test]
+    fn hex_lists_split_on_slashes_outside_of_urls() {
+        let palette = Palette::from_hex_list("#264653 / #2a9d8f\n").unwrap();
+        assert_eq!(hex_codes(&palette), ["#264653", "#2a9d8f"]);
+
+        let palette = Palette::from_hex_list("https://coolors.co/264653-2a9d8f").unwrap();
+        assert_eq!(hex_codes(&palette), ["#264653", "#2a9d8f"]);
+    }
+
+    #[test]
+    fn hex_lists_accept_what_gets_copied_from_the_web() {
+        let text = "// Coast\n\"#264653\", '2a9d8f'; e9c46a\n\n#F4A261 e76f51-abc\n";
+        let palette = Palette::from_hex_list(text).unwrap();
+        assert_eq!(
+            hex_codes(&palette),
+            ["#264653", "#2a9d8f", "#e9c46a", "#f4a261", "#e76f51", "#aabbcc"]
+        );
+
+        assert!(matches!(
+            Palette::from_hex_list("#264653 nope"),
+            Err(PaletteError::Format { format: "hex", .. })
+        ));
+    }
+
+    #[test]
+    fn gimp_palettes_have_names_and_channels_from_0_to_255() {
+        let text = "GIMP Palette\nName: Coast\nColumns: 4\n# A comment\n\n \
+                    38  70  83\tcharcoal\n233 196 106\tUntitled\n244.6 162 97 sandy brown\n";
+        let palette = Palette::from_gpl(text).unwrap();
+
+        assert_eq!(palette.name.as_deref(), Some("Coast"));
+        assert_eq!(hex_codes(&palette), ["#264653", "#e9c46a", "#f5a261"]);
+        assert_eq!(
+            names(&palette),
+            [Some("charcoal"), None, Some("sandy brown")]
+        );
+    }
+
+    #[test]
+    fn gimp_palettes_report_their_mistakes() {
+        assert!(Palette::from_gpl("38 70 83 charcoal\n").is_err());
+
+        let error = Palette::from_gpl("GIMP Palette\n38 70\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid GIMP palette: invalid color on line 2"
+        );
+    }
+
+    #[test]
+    fn ase_files_convert_every_color_model_to_rgb() {
+        let bytes = ase_file(&[
+            (0xc001, ase_name("Coast")),
+            ase_color("Rød", b"RGB ", &[1.0, 0.0, 0.0]),
+            ase_color("", b"CMYK", &[0.0, 1.0, 1.0, 0.5]),
+            ase_color("gray", b"Gray", &[0.2]),
+            ase_color("white", b"LAB ", &[1.0, 0.0, 0.0]),
+            (0xc002, Vec::new()),
+        ]);
+        let palette = Palette::from_ase(&bytes).unwrap();
+
+        assert_eq!(palette.name.as_deref(), Some("Coast"));
+        assert_eq!(
+            names(&palette),
+            [Some("Rød"), None, Some("gray"), Some("white")]
+        );
+        assert_eq!(hex_codes(&palette)[..3], ["#ff0000", "#800000", "#333333"]);
+        // The D50 white point of Lab is adapted to the D65 one of sRGB
+        let white = palette.color(3);
+        for channel in [white.r, white.g, white.b] {
+            assert!((channel - 1.0).abs() < 0.01, "{white:?}");
+        }
+    }
+
+    #[test]
+    fn ase_files_report_their_mistakes() {
+        assert!(Palette::from_ase(b"RIFF\0\0\0\0").is_err());
+
+        let bytes = ase_file(&[ase_color("red", b"RGB ", &[1.0, 0.0, 0.0])]);
+        let error = Palette::from_ase(&bytes[..bytes.len() - 4]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid ASE palette: unexpected end of file"
+        );
+
+        let bytes = ase_file(&[ase_color("red", b"HSB ", &[0.0, 1.0, 1.0])]);
+        assert!(Palette::from_ase(&bytes).is_err());
+    }
+
+    #[test]
+    fn json_palettes_come_in_four_shapes() {
+        let palette = Palette::from_json(r##"["#264653", "2a9d8f"]"##).unwrap();
+        assert_eq!(hex_codes(&palette), ["#264653", "#2a9d8f"]);
+        assert_eq!(names(&palette), [None, None]);
+
+        let palette =
+            Palette::from_json(r##"[{"hex": "#264653", "name": "charcoal"}, {"hex": "#2a9d8f"}]"##)
+                .unwrap();
+        assert_eq!(hex_codes(&palette), ["#264653", "#2a9d8f"]);
+        assert_eq!(names(&palette), [Some("charcoal"), None]);
+
+        // Not in alphabetical order, which the palette must keep
+        let palette =
+            Palette::from_json(r##"{"saffron": "#e9c46a", "charcoal": "#264653"}"##).unwrap();
+        assert_eq!(hex_codes(&palette), ["#e9c46a", "#264653"]);
+        assert_eq!(names(&palette), [Some("saffron"), Some("charcoal")]);
+
+        let palette = Palette::from_json(
+            r##"{"name": "Coast", "colors": {"sandy": "#f4a261", "burnt": "#e76f51"}}"##,
+        )
+        .unwrap();
+        assert_eq!(palette.name.as_deref(), Some("Coast"));
+        assert_eq!(names(&palette), [Some("sandy"), Some("burnt")]);
+    }
+
+    #[test]
+    fn json_palettes_report_their_mistakes() {
+        assert!(matches!(
+            Palette::from_json("[\"#264653\""),
+            Err(PaletteError::Json(_))
+        ));
+        assert!(matches!(
+            Palette::from_json("[12]"),
+            Err(PaletteError::Format { format: "JSON", .. })
+        ));
+        assert!(Palette::from_json(r#"{"colors": 3}"#).is_err());
+        assert!(Palette::from_json(r#"[{"name": "no hex"}]"#).is_err());
+    }
+
+    /// How many times each color is picked out of ``samples`` weighted picks
+    fn weighted_counts(palette: &Palette, samples: usize) -> Vec<usize> {
+        let mut rng = fastrand::Rng::with_seed(7);
+        let mut counts = vec![0; palette.len()];
+        for _ in 0..samples {
+            let color = palette.weighted_random_with(&mut rng);
+            let index = palette.colors().position(|other| other == color).unwrap();
+            counts[index] += 1;
+        }
+        counts
+    }
+
+    #[test]
+    fn weighted_random_follows_the_weights() {
+        let palette = Palette::from_hex_list("#ff0000 #00ff00 #0000ff #ffffff")
+            .unwrap()
+            .with_weights(&[1.0, 3.0, 0.0, -2.0]);
+        let counts = weighted_counts(&palette, 8000);
+
+        // Colors without a (positive) weight are never picked
+        assert_eq!(counts[2..], [0, 0]);
+        let ratio = counts[1] as f32 / counts[0] as f32;
+        assert!((ratio - 3.0).abs() < 0.3, "{counts:?}");
+    }
+
+    #[test]
+    fn weighted_random_without_weights_is_uniform() {
+        let palette = Palette::from_hex_list("#ff0000 #00ff00 #0000ff")
+            .unwrap()
+            .with_weights(&[0.0, 0.0, 0.0]);
+        let counts = weighted_counts(&palette, 3000);
+        assert!(counts.iter().all(|&count| count > 800), "{counts:?}");
+
+        assert_eq!(Palette::default().weighted_random().a, 0.0);
+    }
+}
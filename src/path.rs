@@ -0,0 +1,665 @@
+//! A vector path made of lines and curves, plus the code to flatten it into polylines.
+//!
+//! Curves are flattened with adaptive subdivision: flat parts of a curve get only a few
+//! segments, while tight bends get as many as needed to stay within the requested tolerance.
+
+use std::f32::consts::{FRAC_PI_2, TAU};
+
+use crate::tessellation::polygon::{self, FillRule};
+use crate::tessellation::stroke::{self, StrokeOptions};
+use crate::tessellation::Mesh;
+use crate::transform::Transform;
+
+/// A single drawing instruction of a ``Path``.
+/// Arcs are converted to cubic curves as soon as they are added, so they don't appear here
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PathCommand {
+    MoveTo([f32; 2]),
+    LineTo([f32; 2]),
+    QuadTo {
+        control: [f32; 2],
+        to: [f32; 2],
+    },
+    CubicTo {
+        control_1: [f32; 2],
+        control_2: [f32; 2],
+        to: [f32; 2],
+    },
+    Close,
+}
+
+/// The result of flattening one of the sub-paths of a ``Path``
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Polyline {
+    pub points: Vec<[f32; 2]>,
+    pub closed: bool,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Path {
+    commands: Vec<PathCommand>,
+    // Where the pen currently is, and where the current sub-path started
+    current: Option<[f32; 2]>,
+    subpath_start: [f32; 2],
+}
+
+impl Path {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn commands(&self) -> &[PathCommand] {
+        &self.commands
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// The point the next command will start from
+    pub fn current_point(&self) -> Option<[f32; 2]> {
+        self.current
+    }
+
+    /// Starts a new sub-path at ``to``
+    pub fn move_to(&mut self, to: [f32; 2]) -> &mut Self {
+        self.commands.push(PathCommand::MoveTo(to));
+        self.current = Some(to);
+        self.subpath_start = to;
+        self
+    }
+
+    pub fn line_to(&mut self, to: [f32; 2]) -> &mut Self {
+        self.ensure_subpath(to);
+        self.commands.push(PathCommand::LineTo(to));
+        self.current = Some(to);
+        self
+    }
+
+    /// Quadratic bezier curve from the current point to ``to``
+    pub fn quad_to(&mut self, control: [f32; 2], to: [f32; 2]) -> &mut Self {
+        self.ensure_subpath(control);
+        self.commands.push(PathCommand::QuadTo { control, to });
+        self.current = Some(to);
+        self
+    }
+
+    /// Cubic bezier curve from the current point to ``to``
+    pub fn cubic_to(
+        &mut self,
+        control_1: [f32; 2],
+        control_2: [f32; 2],
+        to: [f32; 2],
+    ) -> &mut Self {
+        self.ensure_subpath(control_1);
+        self.commands.push(PathCommand::CubicTo {
+            control_1,
+            control_2,
+            to,
+        });
+        self.current = Some(to);
+        self
+    }
+
+    /// Elliptical arc from the current point to ``to``, with the same parameters used by SVG:
+    /// ``radii`` of the ellipse, its rotation (in radians), and the flags choosing
+    /// which one of the four possible arcs to draw
+    pub fn arc_to(
+        &mut self,
+        radii: [f32; 2],
+        x_axis_rotation: f32,
+        large_arc: bool,
+        sweep: bool,
+        to: [f32; 2],
+    ) -> &mut Self {
+        let from = match self.current {
+            Some(from) => from,
+            None => {
+                self.move_to(to);
+                return self;
+            }
+        };
+
+        match EndpointArc::new(from, radii, x_axis_rotation, large_arc, sweep, to) {
+            Some(arc) => arc.to_cubics(self, to),
+            // Degenerate arcs (zero radius) are just straight lines
+            None => {
+                if from != to {
+                    self.line_to(to);
+                }
+            }
+        }
+        self
+    }
+
+    /// Connects the current point back to the start of the sub-path
+    pub fn close(&mut self) -> &mut Self {
+        if self.current.is_some() {
+            self.commands.push(PathCommand::Close);
+            self.current = Some(self.subpath_start);
+        }
+        self
+    }
+
+    /// Adds a closed sub-path going through all of the ``points``
+    pub fn polygon(&mut self, points: &[[f32; 2]]) -> &mut Self {
+        self.polyline(points);
+        self.close()
+    }
+
+    /// Adds an open sub-path going through all of the ``points``
+    pub fn polyline(&mut self, points: &[[f32; 2]]) -> &mut Self {
+        if let Some((first, rest)) = points.split_first() {
+            self.move_to(*first);
+            for &point in rest {
+                self.line_to(point);
+            }
+        }
+        self
+    }
+
+    /// Adds an axis aligned rectangle, ``origin`` being its corner with the smallest coordinates
+    pub fn rect(&mut self, origin: [f32; 2], size: [f32; 2]) -> &mut Self {
+        let [x, y] = origin;
+        let [width, height] = size;
+        self.polygon(&[
+            [x, y],
+            [x + width, y],
+            [x + width, y + height],
+            [x, y + height],
+        ])
+    }
+
+    /// Adds an axis aligned ellipse, made of four cubic curves
+    pub fn ellipse(&mut self, center: [f32; 2], radii: [f32; 2]) -> &mut Self {
+        let [cx, cy] = center;
+        let rx = radii[0];
+        self.move_to([cx + rx, cy]);
+        self.arc_to(radii, 0.0, false, true, [cx - rx, cy]);
+        self.arc_to(radii, 0.0, false, true, [cx + rx, cy]);
+        self.close()
+    }
+
+    pub fn circle(&mut self, center: [f32; 2], radius: f32) -> &mut Self {
+        self.ellipse(center, [radius, radius])
+    }
+
+    /// Applies ``transform`` to every point of the path
+    pub fn map_points(&mut self, mut transform: impl FnMut([f32; 2]) -> [f32; 2]) -> &mut Self {
+        for command in &mut self.commands {
+            match command {
+                PathCommand::MoveTo(to) | PathCommand::LineTo(to) => *to = transform(*to),
+                PathCommand::QuadTo { control, to } => {
+                    *control = transform(*control);
+                    *to = transform(*to);
+                }
+                PathCommand::CubicTo {
+                    control_1,
+                    control_2,
+                    to,
+                } => {
+                    *control_1 = transform(*control_1);
+                    *control_2 = transform(*control_2);
+                    *to = transform(*to);
+                }
+                PathCommand::Close => (),
+            }
+        }
+        self.current = self.current.map(&mut transform);
+        self.subpath_start = transform(self.subpath_start);
+        self
+    }
+
+    pub fn transform(&mut self, transform: &Transform) -> &mut Self {
+        self.map_points(|point| transform.apply(point))
+    }
+
+    /// Approximates every sub-path with straight segments,
+    /// staying within ``tolerance`` of the real curves
+    pub fn flatten(&self, tolerance: f32) -> Vec<Polyline> {
+        let tolerance = tolerance.max(f32::EPSILON);
+        let mut polylines = Vec::new();
+        let mut current = Polyline::default();
+        let mut pen = [0.0, 0.0];
+
+        for command in &self.commands {
+            match *command {
+                PathCommand::MoveTo(to) => {
+                    if current.points.len() > 1 {
+                        polylines.push(std::mem::take(&mut current));
+                    }
+                    current.points.clear();
+                    current.closed = false;
+                    current.points.push(to);
+                    pen = to;
+                }
+                PathCommand::LineTo(to) => {
+                    current.points.push(to);
+                    pen = to;
+                }
+                PathCommand::QuadTo { control, to } => {
+                    // A quadratic curve is a cubic curve with both control points
+                    // two thirds of the way towards the quadratic control point
+                    let control_1 = lerp(pen, control, 2.0 / 3.0);
+                    let control_2 = lerp(to, control, 2.0 / 3.0);
+                    flatten_cubic(
+                        pen,
+                        control_1,
+                        control_2,
+                        to,
+                        tolerance,
+                        0,
+                        &mut current.points,
+                    );
+                    pen = to;
+                }
+                PathCommand::CubicTo {
+                    control_1,
+                    control_2,
+                    to,
+                } => {
+                    flatten_cubic(
+                        pen,
+                        control_1,
+                        control_2,
+                        to,
+                        tolerance,
+                        0,
+                        &mut current.points,
+                    );
+                    pen = to;
+                }
+                PathCommand::Close => {
+                    if let Some(&start) = current.points.first() {
+                        current.closed = true;
+                        polylines.push(std::mem::take(&mut current));
+                        // Anything drawn after a close starts back from the sub-path start
+                        current.points.push(start);
+                        pen = start;
+                    }
+                }
+            }
+        }
+
+        if current.points.len() > 1 {
+            polylines.push(current);
+        }
+
+        polylines
+    }
+
+    /// The triangles covering the interior of the path.
+    /// Open sub-paths are implicitly closed, and ``fill_rule`` tells which of the areas
+    /// enclosed by several sub-paths are holes (see ``polygon::fill_contours``)
+    pub fn fill_mesh(&self, tolerance: f32, fill_rule: FillRule) -> Mesh {
+        let contours: Vec<Vec<[f32; 2]>> = self
+            .flatten(tolerance)
+            .into_iter()
+            .map(|polyline| polyline.points)
+            .collect();
+
+        polygon::fill_contours(&contours, fill_rule)
+    }
+
+    /// The triangles covering the outline of every sub-path
+    pub fn stroke_mesh(&self, options: &StrokeOptions, tolerance: f32) -> Mesh {
+        let mut mesh = Mesh::new();
+        for polyline in self.flatten(tolerance) {
+            mesh.append(stroke::stroke_polyline(
+                &polyline.points,
+                polyline.closed,
+                options,
+            ));
+        }
+        mesh
+    }
+
+    /// Starts a new sub-path if a drawing command is issued before any ``move_to``
+    fn ensure_subpath(&mut self, fallback: [f32; 2]) {
+        if self.current.is_none() {
+            self.move_to(fallback);
+        }
+    }
+}
+
+fn lerp(a: [f32; 2], b: [f32; 2], t: f32) -> [f32; 2] {
+    [a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t]
+}
+
+/// Distance of ``point`` from the infinite line going through ``a`` and ``b``
+fn distance_from_line(point: [f32; 2], a: [f32; 2], b: [f32; 2]) -> f32 {
+    let direction = [b[0] - a[0], b[1] - a[1]];
+    let length = direction[0].hypot(direction[1]);
+    let offset = [point[0] - a[0], point[1] - a[1]];
+
+    if length <= f32::EPSILON {
+        return offset[0].hypot(offset[1]);
+    }
+
+    (offset[0] * direction[1] - offset[1] * direction[0]).abs() / length
+}
+
+// Deep enough for any curve that fits on a screen (or a print)
+const MAX_SUBDIVISION_DEPTH: u32 = 16;
+
+/// Adds the points approximating the cubic curve to ``points``, not including ``from``
+fn flatten_cubic(
+    from: [f32; 2],
+    control_1: [f32; 2],
+    control_2: [f32; 2],
+    to: [f32; 2],
+    tolerance: f32,
+    depth: u32,
+    points: &mut Vec<[f32; 2]>,
+) {
+    // The curve always stays within the hull of its control points,
+    // so if they are close enough to the chord we can use the chord itself
+    let flatness =
+        distance_from_line(control_1, from, to).max(distance_from_line(control_2, from, to));
+
+    if flatness <= tolerance || depth >= MAX_SUBDIVISION_DEPTH {
+        points.push(to);
+        return;
+    }
+
+    // Otherwise split the curve in two halves (de Casteljau) and try again
+    let ab = lerp(from, control_1, 0.5);
+    let bc = lerp(control_1, control_2, 0.5);
+    let cd = lerp(control_2, to, 0.5);
+    let abc = lerp(ab, bc, 0.5);
+    let bcd = lerp(bc, cd, 0.5);
+    let middle = lerp(abc, bcd, 0.5);
+
+    flatten_cubic(from, ab, abc, middle, tolerance, depth + 1, points);
+    flatten_cubic(middle, bcd, cd, to, tolerance, depth + 1, points);
+}
+
+/// An SVG style arc, converted to its center parameterization
+/// (see the "Elliptical arc implementation notes" of the SVG specification)
+struct EndpointArc {
+    center: [f32; 2],
+    radii: [f32; 2],
+    rotation: f32,
+    start_angle: f32,
+    sweep_angle: f32,
+}
+
+impl EndpointArc {
+    fn new(
+        from: [f32; 2],
+        radii: [f32; 2],
+        rotation: f32,
+        large_arc: bool,
+        sweep: bool,
+        to: [f32; 2],
+    ) -> Option<Self> {
+        let [mut rx, mut ry] = [radii[0].abs(), radii[1].abs()];
+        if from == to || rx <= f32::EPSILON || ry <= f32::EPSILON {
+            return None;
+        }
+
+        let (sin, cos) = rotation.sin_cos();
+
+        // Move the origin halfway between the two points, and undo the rotation
+        let dx = (from[0] - to[0]) * 0.5;
+        let dy = (from[1] - to[1]) * 0.5;
+        let x1 = cos * dx + sin * dy;
+        let y1 = -sin * dx + cos * dy;
+
+        // Radii that are too small get scaled up until the arc fits
+        let lambda = (x1 * x1) / (rx * rx) + (y1 * y1) / (ry * ry);
+        if lambda > 1.0 {
+            let scale = lambda.sqrt();
+            rx *= scale;
+            ry *= scale;
+        }
+
+        let numerator = rx * rx * ry * ry - rx * rx * y1 * y1 - ry * ry * x1 * x1;
+        let denominator = rx * rx * y1 * y1 + ry * ry * x1 * x1;
+        let mut factor = (numerator / denominator).max(0.0).sqrt();
+        if large_arc == sweep {
+            factor = -factor;
+        }
+
+        let cx1 = factor * rx * y1 / ry;
+        let cy1 = -factor * ry * x1 / rx;
+
+        let center = [
+            cos * cx1 - sin * cy1 + (from[0] + to[0]) * 0.5,
+            sin * cx1 + cos * cy1 + (from[1] + to[1]) * 0.5,
+        ];
+
+        let angle_between = |u: [f32; 2], v: [f32; 2]| {
+            let cross = u[0] * v[1] - u[1] * v[0];
+            let dot = u[0] * v[0] + u[1] * v[1];
+            cross.atan2(dot)
+        };
+
+        let start_vector = [(x1 - cx1) / rx, (y1 - cy1) / ry];
+        let end_vector = [(-x1 - cx1) / rx, (-y1 - cy1) / ry];
+        let start_angle = angle_between([1.0, 0.0], start_vector);
+        let mut sweep_angle = angle_between(start_vector, end_vector);
+
+        if !sweep && sweep_angle > 0.0 {
+            sweep_angle -= TAU;
+        } else if sweep && sweep_angle < 0.0 {
+            sweep_angle += TAU;
+        }
+
+        Some(Self {
+            center,
+            radii: [rx, ry],
+            rotation,
+            start_angle,
+            sweep_angle,
+        })
+    }
+
+    fn point_at(&self, angle: f32) -> [f32; 2] {
+        let (sin, cos) = self.rotation.sin_cos();
+        let x = self.radii[0] * angle.cos();
+        let y = self.radii[1] * angle.sin();
+        [
+            self.center[0] + cos * x - sin * y,
+            self.center[1] + sin * x + cos * y,
+        ]
+    }
+
+    fn derivative_at(&self, angle: f32) -> [f32; 2] {
+        let (sin, cos) = self.rotation.sin_cos();
+        let x = -self.radii[0] * angle.sin();
+        let y = self.radii[1] * angle.cos();
+        [cos * x - sin * y, sin * x + cos * y]
+    }
+
+    /// Appends the arc to ``path`` as a series of cubic curves, each spanning at most 90 degrees.
+    /// The last curve ends exactly on ``end``, regardless of rounding errors
+    fn to_cubics(&self, path: &mut Path, end: [f32; 2]) {
+        let segments = (self.sweep_angle.abs() / FRAC_PI_2).ceil().max(1.0) as u32;
+        let step = self.sweep_angle / segments as f32;
+        // Length of the control arms that best approximates a circular arc of ``step`` radians
+        let arm = 4.0 / 3.0 * (step / 4.0).tan();
+
+        for i in 0..segments {
+            let angle_0 = self.start_angle + step * i as f32;
+            let angle_1 = angle_0 + step;
+
+            let from = self.point_at(angle_0);
+            let to = if i + 1 == segments {
+                end
+            } else {
+                self.point_at(angle_1)
+            };
+            let tangent_0 = self.derivative_at(angle_0);
+            let tangent_1 = self.derivative_at(angle_1);
+
+            path.cubic_to(
+                [from[0] + tangent_0[0] * arm, from[1] + tangent_0[1] * arm],
+                [to[0] - tangent_1[0] * arm, to[1] - tangent_1[1] * arm],
+                to,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn distance(a: [f32; 2], b: [f32; 2]) -> f32 {
+        (a[0] - b[0]).hypot(a[1] - b[1])
+    }
+
+    fn distance_from_segment(point: [f32; 2], a: [f32; 2], b: [f32; 2]) -> f32 {
+        let direction = [b[0] - a[0], b[1] - a[1]];
+        let length_squared = direction[0] * direction[0] + direction[1] * direction[1];
+        if length_squared <= f32::EPSILON {
+            return distance(point, a);
+        }
+        let t =
+            ((point[0] - a[0]) * direction[0] + (point[1] - a[1]) * direction[1]) / length_squared;
+        distance(point, lerp(a, b, t.clamp(0.0, 1.0)))
+    }
+
+    fn distance_from_polyline(point: [f32; 2], points: &[[f32; 2]]) -> f32 {
+        points
+            .windows(2)
+            .map(|segment| distance_from_segment(point, segment[0], segment[1]))
+            .fold(f32::MAX, f32::min)
+    }
+
+    fn cubic_at(points: [[f32; 2]; 4], t: f32) -> [f32; 2] {
+        let [a, b, c, d] = points;
+        let ab = lerp(a, b, t);
+        let bc = lerp(b, c, t);
+        let cd = lerp(c, d, t);
+        lerp(lerp(ab, bc, t), lerp(bc, cd, t), t)
+    }
+
+    /// The points of a path made of a single sub-path
+    fn flattened(path: &Path, tolerance: f32) -> Vec<[f32; 2]> {
+        let mut polylines = path.flatten(tolerance);
+        assert_eq!(polylines.len(), 1);
+        polylines.remove(0).points
+    }
+
+    fn arc(large_arc: bool, sweep: bool) -> Vec<[f32; 2]> {
+        let mut path = Path::new();
+        path.move_to([0.0, 0.0])
+            .arc_to([10.0, 10.0], 0.0, large_arc, sweep, [10.0, 0.0]);
+        flattened(&path, 0.01)
+    }
+
+    #[test]
+    fn cubic_flattening_stays_within_tolerance() {
+        let curve = [[0.0, 0.0], [0.0, 100.0], [100.0, 100.0], [100.0, 0.0]];
+        let mut previous_count = 0;
+
+        for tolerance in [1.0, 0.1, 0.01] {
+            let mut path = Path::new();
+            path.move_to(curve[0])
+                .cubic_to(curve[1], curve[2], curve[3]);
+            let points = flattened(&path, tolerance);
+
+            for step in 0..=1000 {
+                let point = cubic_at(curve, step as f32 / 1000.0);
+                let error = distance_from_polyline(point, &points);
+                assert!(error <= tolerance * 1.01, "{error} > {tolerance}");
+            }
+
+            // Adaptive: finer tolerances need more points
+            assert!(points.len() > previous_count);
+            previous_count = points.len();
+        }
+    }
+
+    #[test]
+    fn quadratic_flattening_stays_within_tolerance() {
+        let (from, control, to) = ([0.0, 0.0], [50.0, 80.0], [100.0, 0.0]);
+        let mut path = Path::new();
+        path.move_to(from).quad_to(control, to);
+        let points = flattened(&path, 0.05);
+
+        for step in 0..=1000 {
+            let t = step as f32 / 1000.0;
+            let point = lerp(lerp(from, control, t), lerp(control, to, t), t);
+            assert!(distance_from_polyline(point, &points) <= 0.05 * 1.01);
+        }
+    }
+
+    #[test]
+    fn flat_curves_are_a_single_segment() {
+        let mut path = Path::new();
+        path.move_to([0.0, 0.0])
+            .cubic_to([1.0, 0.0], [2.0, 0.0], [3.0, 0.0]);
+        assert_eq!(flattened(&path, 0.1), vec![[0.0, 0.0], [3.0, 0.0]]);
+    }
+
+    #[test]
+    fn flattening_keeps_exact_endpoints() {
+        let mut path = Path::new();
+        path.move_to([0.3, 0.7])
+            .cubic_to([10.1, 50.3], [-20.7, 30.9], [40.13, 5.17])
+            .quad_to([12.5, -3.25], [7.77, 8.88])
+            .arc_to([3.0, 5.0], 0.4, true, false, [1.23, 4.56]);
+        let points = flattened(&path, 0.01);
+        assert_eq!(points.first(), Some(&[0.3, 0.7]));
+        assert!(points.contains(&[40.13, 5.17]));
+        assert!(points.contains(&[7.77, 8.88]));
+        assert_eq!(points.last(), Some(&[1.23, 4.56]));
+    }
+
+    #[test]
+    fn small_arc_radii_are_scaled_up() {
+        // A radius of 1 can't join points 10 apart: the arc becomes a half circle of radius 5
+        let mut path = Path::new();
+        path.move_to([0.0, 0.0])
+            .arc_to([1.0, 1.0], 0.0, false, true, [10.0, 0.0]);
+        let points = flattened(&path, 0.001);
+
+        assert_eq!(points.last(), Some(&[10.0, 0.0]));
+        for &point in &points {
+            assert!((distance(point, [5.0, 0.0]) - 5.0).abs() < 0.01);
+        }
+        let lowest = points.iter().map(|point| point[1]).fold(f32::MAX, f32::min);
+        assert!((lowest + 5.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn arc_flags_pick_one_of_four_arcs() {
+        // The two circles of radius 10 going through both points have their centers
+        // at (5, ±8.66): their small arcs bulge by 1.34, their large arcs by 18.66
+        let height = 10.0 - 75.0_f32.sqrt();
+        let cases = [
+            (false, true, [5.0, 75.0_f32.sqrt()], -height),
+            (false, false, [5.0, -(75.0_f32.sqrt())], height),
+            (true, true, [5.0, -(75.0_f32.sqrt())], -20.0 + height),
+            (true, false, [5.0, 75.0_f32.sqrt()], 20.0 - height),
+        ];
+
+        for (large_arc, sweep, center, extreme) in cases {
+            let points = arc(large_arc, sweep);
+            for &point in &points {
+                assert!((distance(point, center) - 10.0).abs() < 0.02);
+            }
+
+            let furthest = points
+                .iter()
+                .map(|point| point[1])
+                .max_by(|a, b| a.abs().total_cmp(&b.abs()))
+                .unwrap();
+            assert!(
+                (furthest - extreme).abs() < 0.02,
+                "large_arc: {large_arc}, sweep: {sweep}, expected {extreme}, got {furthest}"
+            );
+        }
+    }
+
+    #[test]
+    fn degenerate_arcs_are_lines() {
+        let mut path = Path::new();
+        path.move_to([0.0, 0.0])
+            .arc_to([0.0, 5.0], 0.0, false, true, [10.0, 0.0]);
+        assert_eq!(path.commands()[1], PathCommand::LineTo([10.0, 0.0]));
+    }
+}
@@ -0,0 +1,477 @@
+//! Pen plotter output (HPGL and G-code) for the strokes of a recorded frame.
+//!
+//! Plotters only draw lines, so fills are ignored and every stroke is flattened into
+//! polylines expressed in millimeters on the paper. Since the pen moving around in the air
+//! is pure waste of time, the polylines are reordered and joined before being written out.
+
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use crate::svg::SvgRecording;
+
+/// Paper sizes in millimeters, portrait orientation
+pub const A3: [f32; 2] = [297.0, 420.0];
+pub const A4: [f32; 2] = [210.0, 297.0];
+pub const A5: [f32; 2] = [148.0, 210.0];
+pub const LETTER: [f32; 2] = [215.9, 279.4];
+
+/// HPGL coordinates are expressed in plotter units of 0.025 millimeters
+const HPGL_UNITS_PER_MM: f32 = 40.0;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PlotterSettings {
+    /// Width and height of the paper, in millimeters
+    pub paper_size: [f32; 2],
+    /// Empty space left on each side of the paper, in millimeters.
+    /// The frame is scaled uniformly to fit what remains, and centered
+    pub margin: f32,
+    /// Maximum distance (in millimeters) between curves and the lines drawn by the plotter
+    pub tolerance: f32,
+    /// Lines whose ends are closer than this (in millimeters) are drawn without lifting the pen
+    pub merge_distance: f32,
+}
+
+impl Default for PlotterSettings {
+    fn default() -> Self {
+        Self {
+            paper_size: A4,
+            margin: 15.0,
+            tolerance: 0.05,
+            merge_distance: 0.1,
+        }
+    }
+}
+
+/// How the G-code output moves the pen up and down, and how fast it draws
+#[derive(Clone, Debug, PartialEq)]
+pub struct GcodeSettings {
+    pub pen_up: String,
+    pub pen_down: String,
+    /// Drawing speed, in millimeters per minute
+    pub feed_rate: f32,
+}
+
+impl Default for GcodeSettings {
+    fn default() -> Self {
+        Self {
+            pen_up: "G0 Z5".to_string(),
+            pen_down: "G1 Z0 F500".to_string(),
+            feed_rate: 2000.0,
+        }
+    }
+}
+
+/// The lines to draw on paper, in millimeters, with the origin in the bottom left corner
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Plot {
+    pub paper_size: [f32; 2],
+    pub paths: Vec<Vec<[f32; 2]>>,
+}
+
+impl Plot {
+    /// Collects the strokes of ``recording``, fitting them on the paper.
+    /// The paths are optimized (see ``Plot::optimize``) before being returned
+    pub fn from_recording(recording: &SvgRecording, settings: &PlotterSettings) -> Self {
+        let [paper_width, paper_height] = settings.paper_size;
+        let available_width = (paper_width - 2.0 * settings.margin).max(0.0);
+        let available_height = (paper_height - 2.0 * settings.margin).max(0.0);
+
+        let scale = if recording.width > 0.0 && recording.height > 0.0 {
+            (available_width / recording.width).min(available_height / recording.height)
+        } else {
+            0.0
+        };
+        let offset_x = (paper_width - recording.width * scale) * 0.5;
+        let offset_y = (paper_height - recording.height * scale) * 0.5;
+
+        // The recording has Y pointing down, while the paper has it pointing up
+        let to_paper = |[x, y]: [f32; 2]| {
+            [
+                offset_x + x * scale,
+                offset_y + (recording.height - y) * scale,
+            ]
+        };
+        // Flattening happens in pixels, so the tolerance has to be converted too
+        let tolerance = settings.tolerance / scale.max(f32::EPSILON);
+
+        let mut paths = Vec::new();
+        for shape in &recording.shapes {
+            if shape.style.stroke.is_none() || shape.style.stroke_width <= 0.0 {
+                continue;
+            }
+
+            for polyline in shape.path.flatten(tolerance) {
+                let mut points: Vec<[f32; 2]> = polyline
+                    .points
+                    .iter()
+                    .map(|&point| to_paper(point))
+                    .collect();
+                if polyline.closed && points.len() > 2 && points.first() != points.last() {
+                    points.push(points[0]);
+                }
+                if points.len() > 1 {
+                    paths.push(points);
+                }
+            }
+        }
+
+        let mut plot = Self {
+            paper_size: settings.paper_size,
+            paths,
+        };
+        plot.optimize(settings.merge_distance);
+        plot
+    }
+
+    /// Reduces the time spent with the pen up:
+    /// segments drawn twice are removed, paths are sorted so that each one starts close
+    /// to where the previous one ended (flipping them if needed), and paths touching
+    /// each other are joined into a single one
+    pub fn optimize(&mut self, merge_distance: f32) {
+        let paths = remove_duplicate_segments(std::mem::take(&mut self.paths));
+        let paths = sort_nearest_neighbour(paths);
+        self.paths = join_paths(paths, merge_distance);
+    }
+
+    /// Total distance travelled with the pen up, starting from the origin
+    pub fn pen_up_distance(&self) -> f32 {
+        let mut position = [0.0, 0.0];
+        let mut distance = 0.0;
+
+        for path in &self.paths {
+            distance += length(position, path[0]);
+            position = path[path.len() - 1];
+        }
+
+        distance
+    }
+
+    /// Total length of the lines drawn with the pen down
+    pub fn pen_down_distance(&self) -> f32 {
+        self.paths
+            .iter()
+            .flat_map(|path| path.windows(2))
+            .map(|segment| length(segment[0], segment[1]))
+            .sum()
+    }
+
+    pub fn to_hpgl(&self) -> String {
+        let units = |[x, y]: [f32; 2]| {
+            format!(
+                "{},{}",
+                (x * HPGL_UNITS_PER_MM).round() as i32,
+                (y * HPGL_UNITS_PER_MM).round() as i32
+            )
+        };
+
+        let mut hpgl = String::from("IN;SP1;\n");
+
+        for path in &self.paths {
+            // Writing into a String can't fail
+            writeln!(hpgl, "PU{};", units(path[0])).unwrap();
+            let points: Vec<String> = path[1..].iter().map(|&point| units(point)).collect();
+            writeln!(hpgl, "PD{};", points.join(",")).unwrap();
+        }
+
+        hpgl.push_str("PU;SP0;\n");
+        hpgl
+    }
+
+    pub fn to_gcode(&self, settings: &GcodeSettings) -> String {
+        let mut gcode = String::new();
+
+        // Millimeters, absolute positioning
+        gcode.push_str("G21\nG90\n");
+        writeln!(gcode, "{}", settings.pen_up).unwrap();
+
+        for path in &self.paths {
+            let [x, y] = path[0];
+            writeln!(gcode, "G0 X{x:.3} Y{y:.3}").unwrap();
+            writeln!(gcode, "{}", settings.pen_down).unwrap();
+            for &[x, y] in &path[1..] {
+                writeln!(gcode, "G1 X{x:.3} Y{y:.3} F{:.0}", settings.feed_rate).unwrap();
+            }
+            writeln!(gcode, "{}", settings.pen_up).unwrap();
+        }
+
+        gcode.push_str("G0 X0 Y0\n");
+        gcode
+    }
+
+    pub fn save_hpgl(&self, file_path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+        std::fs::write(file_path, self.to_hpgl())
+    }
+
+    pub fn save_gcode(
+        &self,
+        file_path: impl AsRef<std::path::Path>,
+        settings: &GcodeSettings,
+    ) -> std::io::Result<()> {
+        std::fs::write(file_path, self.to_gcode(settings))
+    }
+}
+
+fn length(a: [f32; 2], b: [f32; 2]) -> f32 {
+    (b[0] - a[0]).hypot(b[1] - a[1])
+}
+
+// Segments are compared on a grid of a hundredth of a millimeter
+fn quantize([x, y]: [f32; 2]) -> (i64, i64) {
+    ((x * 100.0).round() as i64, (y * 100.0).round() as i64)
+}
+
+/// Drops every segment that has already been drawn (in either direction),
+/// splitting paths where segments are removed
+fn remove_duplicate_segments(paths: Vec<Vec<[f32; 2]>>) -> Vec<Vec<[f32; 2]>> {
+    let mut seen = HashSet::new();
+    let mut result = Vec::with_capacity(paths.len());
+
+    for path in paths {
+        let mut current: Vec<[f32; 2]> = Vec::new();
+
+        for segment in path.windows(2) {
+            let (a, b) = (quantize(segment[0]), quantize(segment[1]));
+            // Segments too short to be seen are dropped as well
+            let key = if a <= b { (a, b) } else { (b, a) };
+            if a == b || !seen.insert(key) {
+                if current.len() > 1 {
+                    result.push(std::mem::take(&mut current));
+                }
+                current.clear();
+                continue;
+            }
+
+            if current.is_empty() {
+                current.push(segment[0]);
+            }
+            current.push(segment[1]);
+        }
+
+        if current.len() > 1 {
+            result.push(current);
+        }
+    }
+
+    result
+}
+
+/// Greedily picks the path starting (or ending) closest to the current position of the pen.
+/// Closed paths can start anywhere, so they are rotated to begin at their closest point
+fn sort_nearest_neighbour(mut remaining: Vec<Vec<[f32; 2]>>) -> Vec<Vec<[f32; 2]>> {
+    let mut sorted = Vec::with_capacity(remaining.len());
+    let mut position = [0.0, 0.0];
+
+    while !remaining.is_empty() {
+        let mut best_index = 0;
+        let mut best_distance = f32::MAX;
+        // Where to start drawing the best path: Ok(index) for closed paths, Err(reverse) otherwise
+        let mut best_start = Err(false);
+
+        for (index, path) in remaining.iter().enumerate() {
+            let closed = path.len() > 2 && path[0] == path[path.len() - 1];
+
+            if closed {
+                for (point_index, &point) in path.iter().enumerate() {
+                    let distance = length(position, point);
+                    if distance < best_distance {
+                        best_index = index;
+                        best_distance = distance;
+                        best_start = Ok(point_index);
+                    }
+                }
+            } else {
+                for (reverse, point) in [(false, path[0]), (true, path[path.len() - 1])] {
+                    let distance = length(position, point);
+                    if distance < best_distance {
+                        best_index = index;
+                        best_distance = distance;
+                        best_start = Err(reverse);
+                    }
+                }
+            }
+        }
+
+        let mut path = remaining.swap_remove(best_index);
+        match best_start {
+            Ok(start) => {
+                // Drop the duplicated closing point, rotate, and close the loop again
+                path.pop();
+                let start = start % path.len();
+                path.rotate_left(start);
+                path.push(path[0]);
+            }
+            Err(true) => path.reverse(),
+            Err(false) => (),
+        }
+
+        position = path[path.len() - 1];
+        sorted.push(path);
+    }
+
+    sorted
+}
+
+/// Joins consecutive paths when one starts (almost) where the previous one ended
+fn join_paths(paths: Vec<Vec<[f32; 2]>>, merge_distance: f32) -> Vec<Vec<[f32; 2]>> {
+    let mut joined: Vec<Vec<[f32; 2]>> = Vec::with_capacity(paths.len());
+
+    for path in paths {
+        match joined.last_mut() {
+            Some(previous) if length(previous[previous.len() - 1], path[0]) <= merge_distance => {
+                previous.extend_from_slice(&path[1..]);
+            }
+            _ => joined.push(path),
+        }
+    }
+
+    joined
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::path::Path;
+    use crate::shapes::ShapeStyle;
+    use crate::transform::Transform;
+
+    fn plot(paths: &[&[[f32; 2]]]) -> Plot {
+        Plot {
+            paper_size: A4,
+            paths: paths.iter().map(|path| path.to_vec()).collect(),
+        }
+    }
+
+    #[test]
+    fn optimize_reduces_travel() {
+        let mut plot = plot(&[
+            &[[10.0, 0.0], [11.0, 0.0]],
+            &[[3.0, 0.0], [4.0, 0.0]],
+            &[[6.0, 0.0], [5.0, 0.0]],
+        ]);
+        assert_eq!(plot.pen_up_distance(), 20.0);
+
+        plot.optimize(0.1);
+        // From the origin to 3, from 4 to 5 and from 6 to 10, the middle path being flipped
+        assert_eq!(
+            plot.paths,
+            [
+                vec![[3.0, 0.0], [4.0, 0.0]],
+                vec![[5.0, 0.0], [6.0, 0.0]],
+                vec![[10.0, 0.0], [11.0, 0.0]],
+            ]
+        );
+        assert_eq!(plot.pen_up_distance(), 8.0);
+        assert_eq!(plot.pen_down_distance(), 3.0);
+    }
+
+    #[test]
+    fn closed_paths_start_at_their_closest_point() {
+        let square = [
+            [10.0, 10.0],
+            [20.0, 10.0],
+            [20.0, 20.0],
+            [10.0, 20.0],
+            [10.0, 10.0],
+        ];
+        let mut plot = plot(&[&[[0.0, 0.0], [21.0, 21.0]], &square]);
+        plot.optimize(0.0);
+
+        assert_eq!(plot.paths[1][0], [20.0, 20.0]);
+        assert_eq!(plot.paths[1][4], [20.0, 20.0]);
+        assert!((plot.pen_up_distance() - 2.0_f32.sqrt()).abs() < 1e-5);
+    }
+
+    #[test]
+    fn duplicate_segments_are_removed() {
+        let paths = vec![
+            vec![[0.0, 0.0], [10.0, 0.0], [10.0, 10.0]],
+            // Its first segment was already drawn, the other way around
+            vec![[10.0, 10.0], [10.0, 0.0], [20.0, 0.0]],
+            // Drawn twice within a hundredth of a millimeter, and a segment of length 0
+            vec![
+                [0.0, 0.001],
+                [10.0, 0.0],
+                [10.0, 0.0],
+                [30.0, 0.0],
+                [0.0, 0.0],
+                [0.0, 30.0],
+            ],
+        ];
+        assert_eq!(
+            remove_duplicate_segments(paths),
+            [
+                vec![[0.0, 0.0], [10.0, 0.0], [10.0, 10.0]],
+                vec![[10.0, 0.0], [20.0, 0.0]],
+                vec![[10.0, 0.0], [30.0, 0.0], [0.0, 0.0], [0.0, 30.0]],
+            ]
+        );
+    }
+
+    #[test]
+    fn touching_paths_are_joined() {
+        let paths = vec![
+            vec![[0.0, 0.0], [1.0, 0.0]],
+            vec![[1.05, 0.0], [2.0, 0.0]],
+            vec![[3.0, 0.0], [4.0, 0.0]],
+        ];
+        assert_eq!(
+            join_paths(paths, 0.1),
+            [
+                vec![[0.0, 0.0], [1.0, 0.0], [2.0, 0.0]],
+                vec![[3.0, 0.0], [4.0, 0.0]],
+            ]
+        );
+    }
+
+    #[test]
+    fn hpgl_output() {
+        let plot = plot(&[
+            &[[0.0, 0.0], [10.0, 0.0], [10.0, 5.0]],
+            &[[20.0, 20.0], [25.0125, 20.0]],
+        ]);
+        assert_eq!(
+            plot.to_hpgl(),
+            "IN;SP1;\nPU0,0;\nPD400,0,400,200;\nPU800,800;\nPD1001,800;\nPU;SP0;\n"
+        );
+    }
+
+    #[test]
+    fn gcode_output() {
+        let plot = plot(&[&[[0.0, 0.0], [10.0, 0.0], [10.0, 5.5]]]);
+        let settings = GcodeSettings {
+            pen_up: "M3 S0".to_string(),
+            pen_down: "M3 S90".to_string(),
+            feed_rate: 1500.0,
+        };
+        assert_eq!(
+            plot.to_gcode(&settings),
+            "G21\nG90\nM3 S0\n\
+             G0 X0.000 Y0.000\nM3 S90\n\
+             G1 X10.000 Y0.000 F1500\nG1 X10.000 Y5.500 F1500\nM3 S0\n\
+             G0 X0 Y0\n"
+        );
+    }
+
+    #[test]
+    fn strokes_are_fitted_on_the_paper() {
+        let mut recording = SvgRecording::new(100.0, 50.0, Transform::identity());
+        let mut line = Path::new();
+        line.move_to([0.0, 0.0]).line_to([100.0, 0.0]);
+        let black = Color::new(0.0, 0.0, 0.0, 1.0);
+        recording.record_path(&line, &ShapeStyle::stroked(black, 1.0));
+        // Fills aren't plotted
+        let mut square = Path::new();
+        square.rect([10.0, 10.0], [10.0, 10.0]);
+        recording.record_path(&square, &ShapeStyle::filled(black));
+
+        let settings = PlotterSettings {
+            paper_size: [220.0, 220.0],
+            margin: 10.0,
+            ..Default::default()
+        };
+        let plot = Plot::from_recording(&recording, &settings);
+        // Scaled by 2 and centered, with Y going up
+        assert_eq!(plot.paths, [vec![[10.0, 160.0], [210.0, 160.0]]]);
+    }
+}
@@ -0,0 +1,330 @@
+//! Recording sketches as numbered image sequences, to be turned into videos later.
+//!
+//! While a canvas is recording, ``run_sketch`` stops following the wall clock: every frame
+//! advances the sketch by exactly ``1 / frame_rate`` seconds and is written to disk before
+//! the next one is drawn. A slow machine makes the recording take longer, but never drops
+//! nor duplicates frames, so the sequence plays back at the intended speed.
+//! Frames are named ``frame-00000.png``, ``frame-00001.png``, and so on, which is what
+//! ``ffmpeg -i frame-%05d.png`` expects. They can also be encoded right away, see ``video``.
+
+use std::path::{Path, PathBuf};
+
+use crate::capture::frame_path;
+use crate::offscreen::RgbaImage;
+use crate::video::{ApngEncoder, FfmpegEncoder, GifEncoder, VideoSettings};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum FrameFormat {
+    /// 8 bits RGBA
+    #[default]
+    Png,
+    /// 8 bits RGBA, uncompressed
+    Tiff,
+    /// Half float RGBA, linear and premultiplied, for compositing software
+    Exr,
+}
+
+impl FrameFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            FrameFormat::Png => "png",
+            FrameFormat::Tiff => "tif",
+            FrameFormat::Exr => "exr",
+        }
+    }
+
+    /// The format matching the extension of ``path``, if any
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "png" => Some(FrameFormat::Png),
+            "tif" | "tiff" => Some(FrameFormat::Tiff),
+            "exr" => Some(FrameFormat::Exr),
+            _ => None,
+        }
+    }
+
+    pub fn save(&self, image: &RgbaImage, path: impl AsRef<Path>) -> std::io::Result<()> {
+        match self {
+            FrameFormat::Png => image.save_png(path),
+            FrameFormat::Tiff => image.save_tiff(path),
+            FrameFormat::Exr => image.save_exr(path),
+        }
+    }
+}
+
+/// Where the frames of a recording go
+#[derive(Clone, Debug, PartialEq)]
+pub enum RecordingOutput {
+    /// Numbered images in a directory, which is created if needed
+    Frames {
+        directory: PathBuf,
+        format: FrameFormat,
+    },
+    /// A video, encoded by ffmpeg
+    Video(VideoSettings),
+    /// An animated GIF
+    Gif(PathBuf),
+    /// An animated PNG
+    Apng(PathBuf),
+}
+
+impl RecordingOutput {
+    /// The directory of the frames, or the file of the animation
+    pub fn path(&self) -> &Path {
+        match self {
+            RecordingOutput::Frames { directory, .. } => directory,
+            RecordingOutput::Video(settings) => &settings.output,
+            RecordingOutput::Gif(path) | RecordingOutput::Apng(path) => path,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordingSettings {
+    pub output: RecordingOutput,
+    /// Recording stops by itself after that many frames.
+    /// ``None`` records until ``Canvas::stop_recording`` is called
+    pub frame_count: Option<u64>,
+}
+
+impl RecordingSettings {
+    /// Records PNG frames into ``directory``
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self::with_output(RecordingOutput::Frames {
+            directory: directory.into(),
+            format: FrameFormat::default(),
+        })
+    }
+
+    /// Records PNG frames into a new ``frames-<date>-<time>`` directory
+    pub fn timestamped() -> Self {
+        Self::new(frame_path("frames-{timestamp}", 0))
+    }
+
+    pub fn video(settings: VideoSettings) -> Self {
+        Self::with_output(RecordingOutput::Video(settings))
+    }
+
+    pub fn gif(path: impl Into<PathBuf>) -> Self {
+        Self::with_output(RecordingOutput::Gif(path.into()))
+    }
+
+    pub fn apng(path: impl Into<PathBuf>) -> Self {
+        Self::with_output(RecordingOutput::Apng(path.into()))
+    }
+
+    fn with_output(output: RecordingOutput) -> Self {
+        Self {
+            output,
+            frame_count: None,
+        }
+    }
+
+    /// The format of the frames, when recording an image sequence
+    pub fn with_format(mut self, format: FrameFormat) -> Self {
+        if let RecordingOutput::Frames { format: frames, .. } = &mut self.output {
+            *frames = format;
+        }
+        self
+    }
+
+    pub fn with_frame_count(mut self, frame_count: u64) -> Self {
+        self.frame_count = Some(frame_count);
+        self
+    }
+}
+
+/// The encoders are created with the first frame, once its size is known
+enum Encoder {
+    Ffmpeg(FfmpegEncoder),
+    Gif(GifEncoder),
+    Apng(ApngEncoder),
+}
+
+impl Encoder {
+    fn new(
+        output: &RecordingOutput,
+        first_frame: &RgbaImage,
+        frame_rate: f32,
+    ) -> std::io::Result<Self> {
+        let (width, height) = (first_frame.width, first_frame.height);
+        Ok(match output {
+            RecordingOutput::Video(settings) => {
+                Encoder::Ffmpeg(FfmpegEncoder::new(settings, width, height, frame_rate)?)
+            }
+            RecordingOutput::Gif(path) => {
+                Encoder::Gif(GifEncoder::new(path, width, height, frame_rate)?)
+            }
+            RecordingOutput::Apng(path) => {
+                Encoder::Apng(ApngEncoder::new(path, width, height, frame_rate)?)
+            }
+            RecordingOutput::Frames { .. } => unreachable!("image sequences aren't encoded"),
+        })
+    }
+
+    fn write_frame(&mut self, image: &RgbaImage) -> std::io::Result<()> {
+        match self {
+            Encoder::Ffmpeg(encoder) => encoder.write_frame(image),
+            Encoder::Gif(encoder) => encoder.write_frame(image),
+            Encoder::Apng(encoder) => encoder.write_frame(image),
+        }
+    }
+
+    fn finish(self) -> std::io::Result<()> {
+        match self {
+            Encoder::Ffmpeg(encoder) => encoder.finish(),
+            Encoder::Gif(encoder) => encoder.finish(),
+            Encoder::Apng(encoder) => encoder.finish(),
+        }
+    }
+}
+
+/// Writes the frames of a recording, keeping track of how many there are.
+/// Animations are only complete once ``finish`` is called (or the recorder is dropped)
+pub struct Recorder {
+    settings: RecordingSettings,
+    frame_rate: f32,
+    recorded_frames: u64,
+    encoder: Option<Encoder>,
+}
+
+impl Recorder {
+    /// ``frame_rate`` is the one of the sketch, used by videos and animations
+    pub fn new(settings: RecordingSettings, frame_rate: f32) -> Self {
+        Self {
+            settings,
+            frame_rate,
+            recorded_frames: 0,
+            encoder: None,
+        }
+    }
+
+    pub fn settings(&self) -> &RecordingSettings {
+        &self.settings
+    }
+
+    pub fn recorded_frames(&self) -> u64 {
+        self.recorded_frames
+    }
+
+    /// Whether the frame count of the settings has been reached
+    pub fn is_finished(&self) -> bool {
+        self.settings
+            .frame_count
+            .is_some_and(|frame_count| self.recorded_frames >= frame_count)
+    }
+
+    /// Writes ``image`` as the next frame of the recording, and returns the path
+    /// of the file it went to
+    pub fn save(&mut self, image: &RgbaImage) -> std::io::Result<PathBuf> {
+        let path = match &self.settings.output {
+            RecordingOutput::Frames { directory, format } => {
+                if self.recorded_frames == 0 {
+                    std::fs::create_dir_all(directory)?;
+                }
+                let file_name = format!("frame-#####.{}", format.extension());
+                let path = directory.join(frame_path(&file_name, self.recorded_frames));
+                format.save(image, &path)?;
+                path
+            }
+            output => {
+                let encoder = match self.encoder.take() {
+                    Some(encoder) => encoder,
+                    None => Encoder::new(output, image, self.frame_rate)?,
+                };
+                self.encoder.insert(encoder).write_frame(image)?;
+                output.path().to_path_buf()
+            }
+        };
+
+        self.recorded_frames += 1;
+        Ok(path)
+    }
+
+    /// Completes the video or the animation. Image sequences are always complete
+    pub fn finish(&mut self) -> std::io::Result<()> {
+        self.encoder.take().map_or(Ok(()), Encoder::finish)
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_are_found_from_any_case_of_extension() {
+        assert_eq!(FrameFormat::from_path("a.png"), Some(FrameFormat::Png));
+        assert_eq!(FrameFormat::from_path("A.PNG"), Some(FrameFormat::Png));
+        assert_eq!(
+            FrameFormat::from_path("out/frame.Tif"),
+            Some(FrameFormat::Tiff)
+        );
+        assert_eq!(
+            FrameFormat::from_path("frame.TIFF"),
+            Some(FrameFormat::Tiff)
+        );
+        assert_eq!(FrameFormat::from_path("frame.exr"), Some(FrameFormat::Exr));
+        assert_eq!(FrameFormat::from_path("frame.jpg"), None);
+        assert_eq!(FrameFormat::from_path("frame"), None);
+        // A leading dot is a hidden file, not an extension
+        assert_eq!(FrameFormat::from_path(".tiff"), None);
+    }
+
+    #[test]
+    fn extensions_are_recognized_again() {
+        for format in [FrameFormat::Png, FrameFormat::Tiff, FrameFormat::Exr] {
+            let path = format!("frame.{}", format.extension());
+            assert_eq!(FrameFormat::from_path(path), Some(format));
+        }
+    }
+
+    #[test]
+    fn recordings_finish_after_their_frame_count() {
+        let directory =
+            std::env::temp_dir().join(format!("glium-101-{}-recording", std::process::id()));
+        let settings = RecordingSettings::new(&directory).with_frame_count(2);
+        let mut recorder = Recorder::new(settings, 60.0);
+        let image = RgbaImage::new(2, 2);
+
+        assert!(!recorder.is_finished());
+        let first = recorder.save(&image).unwrap();
+        assert!(!recorder.is_finished());
+        let second = recorder.save(&image).unwrap();
+        assert!(recorder.is_finished());
+        assert_eq!(recorder.recorded_frames(), 2);
+
+        assert_eq!(first, directory.join("frame-00000.png"));
+        assert_eq!(second, directory.join("frame-00001.png"));
+        assert!(second.is_file());
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn recordings_without_a_frame_count_never_finish() {
+        let directory =
+            std::env::temp_dir().join(format!("glium-101-{}-endless", std::process::id()));
+        let mut recorder = Recorder::new(RecordingSettings::new(&directory), 60.0);
+        let image = RgbaImage::new(1, 1);
+
+        for _ in 0..3 {
+            recorder.save(&image).unwrap();
+            assert!(!recorder.is_finished());
+        }
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn a_frame_count_of_zero_is_finished_right_away() {
+        let recorder = Recorder::new(RecordingSettings::new("unused").with_frame_count(0), 30.0);
+        assert!(recorder.is_finished());
+    }
+}
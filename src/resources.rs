@@ -0,0 +1,329 @@
+//! GPU resources that live across frames.
+//!
+//! Compiling shaders and uploading buffers is expensive, so we do it only once:
+//! programs are compiled the first time they are requested, and meshes are cached using
+//! whatever they were generated from as a key. Meshes that haven't been used for a few
+//! frames are dropped, so animated geometry doesn't make the cache grow forever.
+//! Textures (like the ones gradients are baked into) are cached the same way.
+
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+use glium::backend::{Context, Facade};
+
+use crate::color_pipeline::ColorPipeline;
+use crate::path::Path;
+use crate::shapes::Vertex;
+use crate::tessellation::Mesh;
+
+/// A mesh that has been uploaded to the GPU
+pub struct GpuMesh {
+    pub vertex_buffer: glium::VertexBuffer<Vertex>,
+    pub index_buffer: glium::IndexBuffer<u32>,
+    /// What the mesh covers, for the recording (see ``Canvas::draw_instances``).
+    /// ``None`` leaves the copies of the mesh out of it
+    pub outline: Option<Rc<Path>>,
+}
+
+impl GpuMesh {
+    pub fn new(facade: &impl Facade, mesh: &Mesh) -> Self {
+        // Vertex buffers are the basic ingredients that will be uploaded to the GPU
+        let vertex_buffer = glium::VertexBuffer::new(facade, &mesh.vertices).unwrap();
+
+        // Both fills and strokes end up being plain triangles
+        let index_buffer = glium::IndexBuffer::new(
+            facade,
+            glium::index::PrimitiveType::TrianglesList,
+            &mesh.indices,
+        )
+        .unwrap();
+
+        Self {
+            vertex_buffer,
+            index_buffer,
+            outline: None,
+        }
+    }
+
+    /// Records the copies of the mesh drawn by ``Canvas::draw_instances`` as ``outline``,
+    /// filled with the color of each instance. ``Mesh::outline`` works for fills
+    pub fn with_outline(mut self, outline: Path) -> Self {
+        self.outline = Some(Rc::new(outline));
+        self
+    }
+}
+
+struct Cached<T> {
+    resource: Rc<T>,
+    last_used_frame: u64,
+}
+
+/// By default, meshes survive this many frames without being used
+pub const DEFAULT_MAX_UNUSED_FRAMES: u64 = 8;
+
+pub struct ResourceCache {
+    context: Rc<Context>,
+    color_pipeline: ColorPipeline,
+    programs: HashMap<String, Rc<glium::Program>>,
+    meshes: HashMap<CacheKey, Cached<GpuMesh>>,
+    textures: HashMap<CacheKey, Cached<glium::texture::Texture2d>>,
+    frame: u64,
+    /// Meshes and textures that haven't been used for more frames than this
+    /// are freed by ``end_frame``
+    pub max_unused_frames: u64,
+}
+
+impl ResourceCache {
+    pub fn new(display: &glium::Display) -> Self {
+        Self::with_facade(display, ColorPipeline::for_display(display))
+    }
+
+    /// Resources living in any OpenGL context, like the one of a ``Headless`` renderer.
+    /// ``color_pipeline`` is the one ``draw_commands`` upload colors for
+    pub fn with_facade(facade: &impl Facade, color_pipeline: ColorPipeline) -> Self {
+        Self {
+            context: facade.get_context().clone(),
+            color_pipeline,
+            programs: HashMap::new(),
+            meshes: HashMap::new(),
+            textures: HashMap::new(),
+            frame: 0,
+            max_unused_frames: DEFAULT_MAX_UNUSED_FRAMES,
+        }
+    }
+
+    /// The OpenGL context the resources live in, which can be used as a ``Facade``
+    pub fn context(&self) -> &Rc<Context> {
+        &self.context
+    }
+
+    /// How ``draw_commands`` upload their colors. The programs returned by ``program``
+    /// expect ``ColorPipeline::for_display`` of the window they draw in
+    pub fn color_pipeline(&self) -> ColorPipeline {
+        self.color_pipeline
+    }
+
+    pub fn set_color_pipeline(&mut self, color_pipeline: ColorPipeline) {
+        self.color_pipeline = color_pipeline;
+    }
+
+    /// Returns the program called ``name``, compiling it the first time it's requested
+    pub fn program(
+        &mut self,
+        name: &str,
+        vertex_shader: &str,
+        fragment_shader: &str,
+    ) -> Result<Rc<glium::Program>, glium::ProgramCreationError> {
+        self.program_for_pipeline(name, vertex_shader, fragment_shader, ColorPipeline::Linear)
+    }
+
+    /// Like ``program``, but the colors written by the fragment shader are handled according
+    /// to ``color_pipeline``. With ``ColorPipeline::Srgb`` they are written to the framebuffer
+    /// as they are, even if it's an sRGB one
+    pub fn program_for_pipeline(
+        &mut self,
+        name: &str,
+        vertex_shader: &str,
+        fragment_shader: &str,
+        color_pipeline: ColorPipeline,
+    ) -> Result<Rc<glium::Program>, glium::ProgramCreationError> {
+        let key = match color_pipeline {
+            ColorPipeline::Linear => name.to_string(),
+            ColorPipeline::Srgb => format!("{name} (sRGB output)"),
+        };
+        if let Some(program) = self.programs.get(&key) {
+            return Ok(program.clone());
+        }
+
+        let program = Rc::new(glium::Program::new(
+            &self.context,
+            glium::program::ProgramCreationInput::SourceCode {
+                vertex_shader,
+                tessellation_control_shader: None,
+                tessellation_evaluation_shader: None,
+                geometry_shader: None,
+                fragment_shader,
+                transform_feedback_varyings: None,
+                outputs_srgb: color_pipeline.outputs_srgb(),
+                uses_point_size: false,
+            },
+        )?);
+
+        self.programs.insert(key, program.clone());
+        Ok(program)
+    }
+
+    /// Returns the mesh identified by ``key``, calling ``generate`` and uploading
+    /// its result only if it isn't in the cache already
+    pub fn mesh(&mut self, key: CacheKey, generate: impl FnOnce() -> Mesh) -> Rc<GpuMesh> {
+        let frame = self.frame;
+        let context = &self.context;
+
+        let cached = self.meshes.entry(key).or_insert_with(|| Cached {
+            resource: Rc::new(GpuMesh::new(context, &generate())),
+            last_used_frame: frame,
+        });
+
+        cached.last_used_frame = frame;
+        cached.resource.clone()
+    }
+
+    /// Returns the texture identified by ``key``, calling ``generate`` and uploading
+    /// its result only if it isn't in the cache already.
+    /// Textures are stored as half floats, so linear colors don't get banding in the darks
+    pub fn texture(
+        &mut self,
+        key: CacheKey,
+        generate: impl FnOnce() -> glium::texture::RawImage2d<'static, f32>,
+    ) -> Rc<glium::texture::Texture2d> {
+        let frame = self.frame;
+        let context = &self.context;
+        let cached = self.textures.entry(key).or_insert_with(|| {
+            let texture = glium::texture::Texture2d::with_format(
+                context,
+                generate(),
+                glium::texture::UncompressedFloatFormat::F16F16F16F16,
+                glium::texture::MipmapsOption::NoMipmap,
+            )
+            .unwrap();
+            Cached {
+                resource: Rc::new(texture),
+                last_used_frame: frame,
+            }
+        });
+
+        cached.last_used_frame = frame;
+        cached.resource.clone()
+    }
+
+    /// Number of meshes currently living on the GPU
+    pub fn mesh_count(&self) -> usize {
+        self.meshes.len()
+    }
+
+    /// Must be called once per frame, after drawing, to free the resources that are no longer used
+    pub fn end_frame(&mut self) {
+        let frame = self.frame;
+        let max_unused_frames = self.max_unused_frames;
+        self.meshes
+            .retain(|_, cached| frame - cached.last_used_frame <= max_unused_frames);
+        self.textures
+            .retain(|_, cached| frame - cached.last_used_frame <= max_unused_frames);
+        self.frame += 1;
+    }
+
+    /// Drops all of the cached meshes (programs are kept)
+    pub fn clear_meshes(&mut self) {
+        self.meshes.clear();
+    }
+
+    /// Drops all of the cached textures
+    pub fn clear_textures(&mut self) {
+        self.textures.clear();
+    }
+}
+
+/// The key of a mesh or a texture in the cache: everything it was generated from.
+/// Floats are added through their bits, so two meshes only share a key
+/// if they were generated from exactly the same numbers.
+/// The whole key is kept (and compared on lookup), rather than a hash of it,
+/// so different shapes never end up sharing a mesh
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    bytes: Vec<u8>,
+}
+
+impl CacheKey {
+    pub fn new(kind: &str) -> Self {
+        let mut key = Self::default();
+        key.add(kind);
+        key
+    }
+
+    pub fn add_f32(&mut self, value: f32) -> &mut Self {
+        self.add(value.to_bits())
+    }
+
+    pub fn add_point(&mut self, point: [f32; 2]) -> &mut Self {
+        self.add_f32(point[0]).add_f32(point[1])
+    }
+
+    /// Adds ``value`` the way it's hashed: ``Hash`` implementations
+    /// tell strings and slices apart by their ends or their lengths
+    pub fn add<T: Hash>(&mut self, value: T) -> &mut Self {
+        value.hash(&mut KeyWriter(&mut self.bytes));
+        self
+    }
+
+    /// Adds another key, like the one of the geometry a stroke is generated from
+    pub fn add_key(&mut self, key: &CacheKey) -> &mut Self {
+        self.add(&key.bytes)
+    }
+}
+
+/// Collects what ``Hash`` implementations feed to a hasher, instead of hashing it
+struct KeyWriter<'a>(&'a mut Vec<u8>);
+
+impl Hasher for KeyWriter<'_> {
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.extend_from_slice(bytes);
+    }
+
+    fn finish(&self) -> u64 {
+        unreachable!("cache keys are compared, not hashed")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(kind: &str, points: &[[f32; 2]]) -> CacheKey {
+        let mut key = CacheKey::new(kind);
+        for &point in points {
+            key.add_point(point);
+        }
+        key
+    }
+
+    #[test]
+    fn same_material_gives_equal_keys() {
+        let points = [[0.5, 1.0], [2.0, -3.0]];
+        assert_eq!(key("path", &points), key("path", &points));
+    }
+
+    #[test]
+    fn any_difference_gives_different_keys() {
+        let points = [[0.5, 1.0], [2.0, -3.0]];
+        assert_ne!(
+            key("path", &points),
+            key("path", &[[1.0, 0.5], [2.0, -3.0]])
+        );
+        assert_ne!(key("path", &points), key("primitive", &points));
+        assert_ne!(key("path", &points), key("path", &points[..1]));
+        // Equal numbers, but not the same bits: they may not give the same mesh
+        assert_ne!(key("path", &[[0.0, 0.0]]), key("path", &[[-0.0, 0.0]]));
+    }
+
+    #[test]
+    fn strings_and_nested_keys_keep_their_boundaries() {
+        assert_ne!(CacheKey::new("ab"), CacheKey::new("a").add("b").clone());
+
+        let geometry = key("path", &[[1.0, 2.0]]);
+        let mut stroke = CacheKey::new("stroke");
+        stroke.add_key(&geometry).add_f32(3.0);
+        let mut other = CacheKey::new("stroke");
+        other.add_key(&key("path", &[[1.0, 2.0], [3.0, 0.0]]));
+        assert_ne!(stroke, other);
+    }
+
+    #[test]
+    fn cache_maps_compare_whole_keys() {
+        let mut meshes = HashMap::new();
+        meshes.insert(key("path", &[[1.0, 2.0]]), "first");
+        meshes.insert(key("path", &[[2.0, 1.0]]), "second");
+        assert_eq!(meshes.len(), 2);
+        assert_eq!(meshes[&key("path", &[[1.0, 2.0]])], "first");
+    }
+}
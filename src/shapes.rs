@@ -0,0 +1,728 @@
+use std::rc::Rc;
+
+use glium::implement_vertex;
+
+use crate::blend::BlendMode;
+use crate::color::Color;
+use crate::gradient::Gradient;
+use crate::path::{Path, PathCommand};
+use crate::resources::{CacheKey, GpuMesh, ResourceCache};
+use crate::tessellation::polygon::{self, FillRule};
+use crate::tessellation::stroke::{self, LineCap, LineJoin, StrokeOptions};
+use crate::tessellation::Mesh;
+use crate::transform::Transform;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Vertex {
+    pub position: [f32; 2],
+}
+
+implement_vertex!(Vertex, position);
+
+impl Vertex {
+    pub fn new(x: f32, y: f32) -> Self {
+        Self { position: [x, y] }
+    }
+}
+
+/// Per-instance attributes, used when the same mesh is drawn many times in one go
+/// (see ``instancing::Instances``). The vertices of the mesh are first multiplied by ``scale``,
+/// then by the 2x2 ``matrix`` (rotations, shears...) and finally moved by ``translation``
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Instance {
+    /// Columns of the matrix, like GLSL's ``mat2``
+    pub matrix: [[f32; 2]; 2],
+    pub translation: [f32; 2],
+    pub scale: [f32; 2],
+    pub color: [f32; 4],
+}
+
+implement_vertex!(Instance, matrix, translation, scale, color);
+
+impl Instance {
+    pub fn new(translation: [f32; 2], color: Color) -> Self {
+        Self {
+            matrix: [[1.0, 0.0], [0.0, 1.0]],
+            translation,
+            scale: [1.0, 1.0],
+            color: [color.r, color.g, color.b, color.a],
+        }
+    }
+
+    /// An instance placed by ``transform``, which is applied after the scale
+    pub fn from_transform(transform: &Transform, color: Color) -> Self {
+        Self {
+            matrix: [[transform.a, transform.b], [transform.c, transform.d]],
+            ..Self::new([transform.e, transform.f], color)
+        }
+    }
+
+    pub fn with_scale(mut self, x: f32, y: f32) -> Self {
+        self.scale = [x, y];
+        self
+    }
+
+    /// Everything applied to the vertices of the mesh, scale included
+    pub fn transform(&self) -> Transform {
+        let [[a, b], [c, d]] = self.matrix;
+        let [sx, sy] = self.scale;
+        let [e, f] = self.translation;
+        Transform::new(a * sx, b * sx, c * sy, d * sy, e, f)
+    }
+}
+
+pub const VERTEX_SHADER_SRC: &str = r#"
+#version 140
+
+in vec2 position;
+
+void main() {
+    vec2 pos = position;
+    gl_Position = vec4(pos, 0.0, 1.0);
+}
+"#;
+
+pub const FRAGMENT_SHADER_SRC: &str = r#"
+#version 140
+
+out vec4 color;
+uniform vec4 requested_rgba_color;
+
+void main() {
+    // Premultiplied alpha, see ``blend``
+    color = vec4(requested_rgba_color.rgb * requested_rgba_color.a, requested_rgba_color.a);
+}
+"#;
+
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ShapePrimitive {
+    /// The vertices are the outline of a circle or an ellipse,
+    /// usually generated via ``tessellation::ellipse::Ellipse::outline``
+    Circle,
+    Triangle,
+    /// Any simple polygon, concave ones included.
+    /// The vertices start with the outer contour, followed by the contours of the holes:
+    /// ``hole_indices`` contains the index of the first vertex of each hole
+    /// (see ``tessellation::polygon::join_contours``)
+    Polygon {
+        hole_indices: Vec<usize>,
+    },
+}
+
+impl ShapePrimitive {
+    /// A polygon without any hole
+    pub fn polygon() -> Self {
+        Self::Polygon {
+            hole_indices: Vec::new(),
+        }
+    }
+}
+
+/// What fills (or strokes) a shape
+#[derive(Clone, Debug, PartialEq)]
+pub enum Paint {
+    Solid(Color),
+    /// Shared, so that styles stay cheap to copy around
+    Gradient(Rc<Gradient>),
+}
+
+impl From<Color> for Paint {
+    fn from(color: Color) -> Self {
+        Paint::Solid(color)
+    }
+}
+
+impl From<Gradient> for Paint {
+    fn from(gradient: Gradient) -> Self {
+        Paint::Gradient(Rc::new(gradient))
+    }
+}
+
+impl Paint {
+    /// Gradients follow the shapes they paint around
+    pub fn transform(&mut self, transform: &Transform) {
+        if let Paint::Gradient(gradient) = self {
+            Rc::make_mut(gradient).transform(transform);
+        }
+    }
+
+    /// The color itself, or a single color standing for the whole gradient
+    pub fn average_color(&self) -> Color {
+        match self {
+            Paint::Solid(color) => *color,
+            Paint::Gradient(gradient) => gradient.average_color(),
+        }
+    }
+}
+
+/// How a shape should be painted: with a fill, a stroke, or both.
+/// The fill is always drawn first, so that the stroke ends up on top of it
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShapeStyle {
+    pub fill: Option<Paint>,
+    pub stroke: Option<Paint>,
+    pub stroke_width: f32,
+    pub line_join: LineJoin,
+    pub line_cap: LineCap,
+    pub miter_limit: f32,
+    /// Only matters for paths with several sub-paths
+    pub fill_rule: FillRule,
+    /// Used for both the fill and the stroke
+    pub blend_mode: BlendMode,
+}
+
+impl Default for ShapeStyle {
+    fn default() -> Self {
+        Self {
+            fill: None,
+            stroke: None,
+            stroke_width: 1.0,
+            line_join: LineJoin::default(),
+            line_cap: LineCap::default(),
+            miter_limit: stroke::DEFAULT_MITER_LIMIT,
+            fill_rule: FillRule::default(),
+            blend_mode: BlendMode::default(),
+        }
+    }
+}
+
+impl ShapeStyle {
+    pub fn new(fill: Option<Paint>, stroke: Option<Paint>, stroke_width: f32) -> Self {
+        Self {
+            fill,
+            stroke,
+            stroke_width,
+            ..Default::default()
+        }
+    }
+
+    pub fn filled(paint: impl Into<Paint>) -> Self {
+        Self {
+            fill: Some(paint.into()),
+            ..Default::default()
+        }
+    }
+
+    pub fn stroked(paint: impl Into<Paint>, stroke_width: f32) -> Self {
+        Self {
+            stroke: Some(paint.into()),
+            stroke_width,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_fill(mut self, paint: impl Into<Paint>) -> Self {
+        self.fill = Some(paint.into());
+        self
+    }
+
+    pub fn with_stroke(mut self, paint: impl Into<Paint>, stroke_width: f32) -> Self {
+        self.stroke = Some(paint.into());
+        self.stroke_width = stroke_width;
+        self
+    }
+
+    pub fn with_line_join(mut self, line_join: LineJoin) -> Self {
+        self.line_join = line_join;
+        self
+    }
+
+    pub fn with_line_cap(mut self, line_cap: LineCap) -> Self {
+        self.line_cap = line_cap;
+        self
+    }
+
+    pub fn with_miter_limit(mut self, miter_limit: f32) -> Self {
+        self.miter_limit = miter_limit;
+        self
+    }
+
+    pub fn with_fill_rule(mut self, fill_rule: FillRule) -> Self {
+        self.fill_rule = fill_rule;
+        self
+    }
+
+    pub fn with_blend_mode(mut self, blend_mode: BlendMode) -> Self {
+        self.blend_mode = blend_mode;
+        self
+    }
+
+    /// A style with neither a fill nor a stroke won't produce any draw command
+    pub fn is_visible(&self) -> bool {
+        self.fill.is_some() || (self.stroke.is_some() && self.stroke_width > 0.0)
+    }
+
+    /// Scales the stroke width along with the shape, and moves the gradients around
+    pub fn transform(&mut self, transform: &Transform) {
+        self.stroke_width *= transform.average_scale();
+        for paint in [&mut self.fill, &mut self.stroke].into_iter().flatten() {
+            paint.transform(transform);
+        }
+    }
+
+    /// The options used to tessellate the stroke of the shape
+    pub fn stroke_options(&self) -> StrokeOptions {
+        StrokeOptions::new(self.stroke_width)
+            .with_join(self.line_join)
+            .with_cap(self.line_cap)
+            .with_miter_limit(self.miter_limit)
+    }
+}
+
+/// A path together with the style used to paint it
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Shape {
+    pub path: Path,
+    pub style: ShapeStyle,
+}
+
+impl Shape {
+    pub fn new(path: Path, style: ShapeStyle) -> Self {
+        Self { path, style }
+    }
+
+    /// Moves the shape around, along with its stroke width and gradients
+    pub fn transform(&mut self, transform: &Transform) {
+        self.path.transform(transform);
+        self.style.transform(transform);
+    }
+}
+
+/// Returned by ``generate_draw_commands`` for the styles it can't draw
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DrawCommandError {
+    /// Draw commands paint with a single color. Shapes painted with gradients
+    /// have to be drawn by a ``Canvas``, which binds the textures of the gradients
+    Gradient,
+}
+
+impl std::fmt::Display for DrawCommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DrawCommandError::Gradient => write!(
+                f,
+                "draw commands only paint solid colors, gradients need a Canvas"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DrawCommandError {}
+
+pub struct SketchDrawCommand<'a> {
+    /// Shared with the cache, so that unchanged shapes aren't uploaded again every frame
+    pub mesh: Rc<GpuMesh>,
+    pub uniforms:
+        glium::uniforms::UniformsStorage<'a, (f32, f32, f32, f32), glium::uniforms::EmptyUniforms>,
+    pub draw_parameters: glium::draw_parameters::DrawParameters<'a>,
+}
+
+/// ``vertices`` should contain the exact number of vertices
+/// that will be composing our shape.
+/// The returned commands are ordered (fill first, then stroke)
+/// and can be drawn in one go with ``draw_commands``.
+/// The meshes are cached in ``resources``: drawing the same shape again
+/// in the next frame neither tessellates nor uploads anything.
+/// Fails if the style is painted with a gradient, see ``DrawCommandError``
+pub fn generate_draw_commands(
+    resources: &mut ResourceCache,
+    vertices: &[Vertex],
+    primitive: ShapePrimitive,
+    style: &ShapeStyle,
+) -> Result<Vec<SketchDrawCommand<'static>>, DrawCommandError> {
+    let mut key = CacheKey::new("primitive");
+    for vertex in vertices {
+        key.add_point(vertex.position);
+    }
+    key.add(&primitive);
+
+    generate_styled_draw_commands(
+        resources,
+        style,
+        &key,
+        || fill_mesh(vertices, &primitive),
+        |options| stroke_mesh(vertices, &primitive, options),
+    )
+}
+
+/// Same as ``generate_draw_commands``, but for a ``Path`` made of lines and curves.
+/// Curves are flattened so that they never stray more than ``tolerance`` from the ideal shape
+pub fn generate_path_draw_commands(
+    resources: &mut ResourceCache,
+    path: &Path,
+    style: &ShapeStyle,
+    tolerance: f32,
+) -> Result<Vec<SketchDrawCommand<'static>>, DrawCommandError> {
+    let mut key = CacheKey::new("path");
+    add_path_to_key(&mut key, path);
+    key.add_f32(tolerance);
+
+    generate_styled_draw_commands(
+        resources,
+        style,
+        &key,
+        || path.fill_mesh(tolerance, style.fill_rule),
+        |options| path.stroke_mesh(&options.with_tolerance(tolerance), tolerance),
+    )
+}
+
+fn add_path_to_key(key: &mut CacheKey, path: &Path) {
+    for command in path.commands() {
+        match *command {
+            PathCommand::MoveTo(to) => key.add('M').add_point(to),
+            PathCommand::LineTo(to) => key.add('L').add_point(to),
+            PathCommand::QuadTo { control, to } => key.add('Q').add_point(control).add_point(to),
+            PathCommand::CubicTo {
+                control_1,
+                control_2,
+                to,
+            } => key
+                .add('C')
+                .add_point(control_1)
+                .add_point(control_2)
+                .add_point(to),
+            PathCommand::Close => key.add('Z'),
+        };
+    }
+}
+
+/// The meshes for the fill and the stroke are only generated if the style requires them,
+/// and only if ``resources`` doesn't have them already.
+/// ``geometry_key`` identifies the geometry the meshes are generated from
+fn generate_styled_draw_commands(
+    resources: &mut ResourceCache,
+    style: &ShapeStyle,
+    geometry_key: &CacheKey,
+    fill: impl FnOnce() -> Mesh,
+    stroke: impl FnOnce(&StrokeOptions) -> Mesh,
+) -> Result<Vec<SketchDrawCommand<'static>>, DrawCommandError> {
+    let solid_color = |paint: &Paint| match paint {
+        Paint::Solid(color) => Ok(*color),
+        Paint::Gradient(_) => Err(DrawCommandError::Gradient),
+    };
+    let fill_color = style.fill.as_ref().map(solid_color).transpose()?;
+    let stroke_color = match &style.stroke {
+        Some(paint) if style.stroke_width > 0.0 => Some(solid_color(paint)?),
+        _ => None,
+    };
+
+    let mut commands = Vec::with_capacity(2);
+    // ``draw_commands`` is used with programs that let an sRGB framebuffer convert
+    // their output, in which case the uniforms must be linear
+    let color_pipeline = resources.color_pipeline();
+
+    if let Some(fill_color) = fill_color {
+        let mut key = CacheKey::new("fill");
+        key.add_key(geometry_key).add(style.fill_rule);
+        let mesh = resources.mesh(key, fill);
+        commands.push(generate_draw_command(
+            mesh,
+            color_pipeline.upload(fill_color),
+            style.blend_mode,
+        ));
+    }
+
+    if let Some(stroke_color) = stroke_color {
+        let options = style.stroke_options();
+        let mut key = CacheKey::new("stroke");
+        key.add_key(geometry_key)
+            .add_f32(options.width)
+            .add(options.join)
+            .add(options.cap)
+            .add_f32(options.miter_limit);
+        let mesh = resources.mesh(key, || stroke(&options));
+        commands.push(generate_draw_command(
+            mesh,
+            color_pipeline.upload(stroke_color),
+            style.blend_mode,
+        ));
+    }
+
+    Ok(commands)
+}
+
+/// The triangles covering the interior of the shape
+pub fn fill_mesh(vertices: &[Vertex], primitive: &ShapePrimitive) -> Mesh {
+    let vertex_count = vertices.len() as u32;
+
+    // Tell OpenGL how to link together the vertices that we will pass.
+    // The outline of a circle is convex, so a fan starting from its first vertex
+    // covers the whole interior without needing an extra center vertex
+    let indices = match primitive {
+        ShapePrimitive::Circle => (1..vertex_count.saturating_sub(1))
+            .flat_map(|i| [0, i, i + 1])
+            .collect(),
+        ShapePrimitive::Triangle => (0..vertex_count - vertex_count % 3).collect(),
+        // Polygons can be concave, so they need to be properly triangulated
+        ShapePrimitive::Polygon { hole_indices } => {
+            return polygon::fill_polygon(vertices, hole_indices);
+        }
+    };
+
+    Mesh {
+        vertices: vertices.to_vec(),
+        indices,
+    }
+}
+
+/// The triangles covering the outline of the shape
+pub fn stroke_mesh(
+    vertices: &[Vertex],
+    primitive: &ShapePrimitive,
+    options: &StrokeOptions,
+) -> Mesh {
+    let points: Vec<[f32; 2]> = vertices.iter().map(|vertex| vertex.position).collect();
+
+    match primitive {
+        ShapePrimitive::Circle => stroke::stroke_polyline(&points, true, options),
+        // Every triangle of the list gets its own closed outline
+        ShapePrimitive::Triangle => {
+            let mut mesh = Mesh::new();
+            for triangle in points.chunks_exact(3) {
+                mesh.append(stroke::stroke_polyline(triangle, true, options));
+            }
+            mesh
+        }
+        // The outer contour and each of the holes are outlined separately
+        ShapePrimitive::Polygon { hole_indices } => {
+            let mut mesh = Mesh::new();
+            for range in polygon::contour_ranges(points.len(), hole_indices) {
+                mesh.append(stroke::stroke_polyline(&points[range], true, options));
+            }
+            mesh
+        }
+    }
+}
+
+/// The outline of the shape as a path, closing every contour.
+/// This is what gets recorded when exporting the frame as vectors
+pub fn primitive_path(vertices: &[Vertex], primitive: &ShapePrimitive) -> Path {
+    let points: Vec<[f32; 2]> = vertices.iter().map(|vertex| vertex.position).collect();
+    let mut path = Path::new();
+
+    match primitive {
+        ShapePrimitive::Circle => {
+            path.polygon(&points);
+        }
+        ShapePrimitive::Triangle => {
+            for triangle in points.chunks_exact(3) {
+                path.polygon(triangle);
+            }
+        }
+        ShapePrimitive::Polygon { hole_indices } => {
+            for range in polygon::contour_ranges(points.len(), hole_indices) {
+                path.polygon(&points[range]);
+            }
+        }
+    }
+
+    path
+}
+
+/// ``color`` will be the color used to paint all of the triangles in ``mesh``,
+/// blended with what's below them according to ``blend_mode``.
+/// It has already been converted by the color pipeline
+fn generate_draw_command(
+    mesh: Rc<GpuMesh>,
+    [r, g, b, a]: [f32; 4],
+    blend_mode: BlendMode,
+) -> SketchDrawCommand<'static> {
+    let rgba_color = (r, g, b, a);
+
+    // A uniform that will be passed to our shader
+    let uniforms = glium::uniform! {
+        requested_rgba_color: rgba_color,
+    };
+
+    let draw_parameters = glium::draw_parameters::DrawParameters {
+        multisampling: true,
+        blend: blend_mode.blend(),
+        ..Default::default()
+    };
+
+    SketchDrawCommand {
+        mesh,
+        uniforms,
+        draw_parameters,
+    }
+}
+
+/// Draws all of the ``commands`` in order, using the same ``program`` for all of them
+pub fn draw_commands<S: glium::Surface>(
+    surface: &mut S,
+    program: &glium::Program,
+    commands: &[SketchDrawCommand],
+) -> Result<(), glium::DrawError> {
+    for command in commands {
+        surface.draw(
+            &command.mesh.vertex_buffer,
+            &command.mesh.index_buffer,
+            program,
+            &command.uniforms,
+            &command.draw_parameters,
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-4;
+
+    fn vertices(points: &[[f32; 2]]) -> Vec<Vertex> {
+        points.iter().map(|&[x, y]| Vertex::new(x, y)).collect()
+    }
+
+    /// Sum of the areas of the triangles, counting overlaps twice
+    fn area(mesh: &Mesh) -> f32 {
+        mesh.indices
+            .chunks_exact(3)
+            .map(|triangle| {
+                let [a, b, c] =
+                    [0, 1, 2].map(|corner| mesh.vertices[triangle[corner] as usize].position);
+                ((b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0])).abs() * 0.5
+            })
+            .sum()
+    }
+
+    fn square(origin: [f32; 2], size: f32) -> Vec<[f32; 2]> {
+        let [x, y] = origin;
+        vec![[x, y], [x + size, y], [x + size, y + size], [x, y + size]]
+    }
+
+    #[test]
+    fn circles_are_filled_with_a_fan_from_their_first_vertex() {
+        let outline = vertices(&square([0.0, 0.0], 2.0));
+        let mesh = fill_mesh(&outline, &ShapePrimitive::Circle);
+
+        assert_eq!(mesh.indices, vec![0, 1, 2, 0, 2, 3]);
+        assert_eq!(mesh.vertices, outline);
+        assert!((area(&mesh) - 4.0).abs() < EPSILON);
+
+        // Not enough vertices for a single triangle
+        let mesh = fill_mesh(&outline[..2], &ShapePrimitive::Circle);
+        assert!(mesh.indices.is_empty());
+    }
+
+    #[test]
+    fn triangle_lists_ignore_the_incomplete_triangle_at_the_end() {
+        let points = vertices(&[
+            [0.0, 0.0],
+            [1.0, 0.0],
+            [0.0, 1.0],
+            [2.0, 0.0],
+            [3.0, 0.0],
+            [2.0, 1.0],
+            [5.0, 5.0],
+        ]);
+        let mesh = fill_mesh(&points, &ShapePrimitive::Triangle);
+
+        assert_eq!(mesh.indices, vec![0, 1, 2, 3, 4, 5]);
+        assert!((area(&mesh) - 1.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn concave_polygons_are_filled_without_spilling_out() {
+        // An L shape, made of three unit squares
+        let l_shape = vertices(&[
+            [0.0, 0.0],
+            [2.0, 0.0],
+            [2.0, 1.0],
+            [1.0, 1.0],
+            [1.0, 2.0],
+            [0.0, 2.0],
+        ]);
+        let mesh = fill_mesh(&l_shape, &ShapePrimitive::polygon());
+
+        assert_eq!(mesh.indices.len(), 4 * 3);
+        assert!((area(&mesh) - 3.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn polygon_holes_are_left_empty() {
+        let outline = vertices(&square([0.0, 0.0], 4.0));
+        let mut hole = vertices(&square([1.0, 1.0], 2.0));
+        hole.reverse();
+        let (points, hole_indices) = polygon::join_contours(&outline, &[hole]);
+        let primitive = ShapePrimitive::Polygon { hole_indices };
+
+        let mesh = fill_mesh(&points, &primitive);
+        assert!((area(&mesh) - 12.0).abs() < EPSILON);
+
+        // Both contours get an outline
+        let path = primitive_path(&points, &primitive);
+        let closes = path
+            .commands()
+            .iter()
+            .filter(|command| **command == PathCommand::Close)
+            .count();
+        assert_eq!(closes, 2);
+    }
+
+    #[test]
+    fn every_triangle_of_a_list_is_outlined_on_its_own() {
+        let points = vertices(&[
+            [0.0, 0.0],
+            [10.0, 0.0],
+            [0.0, 10.0],
+            [20.0, 0.0],
+            [30.0, 0.0],
+            [20.0, 10.0],
+        ]);
+        let options = StrokeOptions::new(1.0);
+        let both = stroke_mesh(&points, &ShapePrimitive::Triangle, &options);
+        let first =
+            stroke::stroke_polyline(&[[0.0, 0.0], [10.0, 0.0], [0.0, 10.0]], true, &options);
+
+        // Both triangles have the same size, so their outlines have the same number of triangles
+        assert_eq!(both.indices.len(), first.indices.len() * 2);
+        assert!((area(&both) - area(&first) * 2.0).abs() < 0.01);
+
+        let path = primitive_path(&points, &ShapePrimitive::Triangle);
+        assert_eq!(
+            path.commands(),
+            &[
+                PathCommand::MoveTo([0.0, 0.0]),
+                PathCommand::LineTo([10.0, 0.0]),
+                PathCommand::LineTo([0.0, 10.0]),
+                PathCommand::Close,
+                PathCommand::MoveTo([20.0, 0.0]),
+                PathCommand::LineTo([30.0, 0.0]),
+                PathCommand::LineTo([20.0, 10.0]),
+                PathCommand::Close,
+            ]
+        );
+    }
+
+    #[test]
+    fn circle_outlines_are_closed() {
+        let outline = vertices(&square([0.0, 0.0], 10.0));
+        let options = StrokeOptions::new(2.0).with_join(LineJoin::Miter);
+        let mesh = stroke_mesh(&outline, &ShapePrimitive::Circle, &options);
+
+        // A closed square outline with mitered corners is a 12x12 square minus an 8x8 one
+        let xs = mesh.vertices.iter().map(|vertex| vertex.position[0]);
+        let (min, max) = xs.fold((f32::MAX, f32::MIN), |(min, max), x| {
+            (min.min(x), max.max(x))
+        });
+        assert!((min + 1.0).abs() < EPSILON);
+        assert!((max - 11.0).abs() < EPSILON);
+        assert!(area(&mesh) >= 144.0 - 64.0 - EPSILON);
+    }
+
+    #[test]
+    fn instances_scale_before_their_matrix() {
+        let transform = Transform::rotation(std::f32::consts::FRAC_PI_2)
+            .then(&Transform::translation(5.0, 0.0));
+        let instance = Instance::from_transform(&transform, Color::new(1.0, 1.0, 1.0, 1.0))
+            .with_scale(2.0, 1.0);
+        let [x, y] = instance.transform().apply([1.0, 0.0]);
+
+        assert!((x - 5.0).abs() < EPSILON);
+        assert!((y - 2.0).abs() < EPSILON);
+    }
+}
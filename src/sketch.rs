@@ -0,0 +1,408 @@
+//! The lifecycle of a sketch: set it up once, then update and draw it every frame.
+//!
+//! ``run_sketch`` owns the window, the OpenGL context and the event loop, so a sketch is
+//! just a type implementing ``Sketch``: see ``main.rs`` for an example.
+//! The same sketch can also run without a window, see ``run_headless``.
+
+use std::fmt;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use glium::glutin;
+pub use glium::glutin::event::{MouseButton, VirtualKeyCode};
+
+use crate::canvas::Canvas;
+use crate::capture::{frame_path, DEFAULT_SCREENSHOT_PATTERN};
+use crate::color_pipeline::ColorPipeline;
+use crate::instancing::InstancingError;
+use crate::offscreen::OffscreenError;
+use crate::plotter::{GcodeSettings, Plot, PlotterSettings};
+use crate::recording::RecordingSettings;
+use crate::tiled::{render_tiled, TiledRender};
+
+/// All of the methods have a default (empty) implementation, except for ``draw``
+#[allow(unused_variables)]
+pub trait Sketch {
+    /// Called once, before the first frame
+    fn setup(&mut self, canvas: &mut Canvas) {}
+
+    /// Called before drawing each frame. ``dt`` is the time elapsed
+    /// since the previous update, in seconds (0 for the first frame)
+    fn update(&mut self, dt: f32) {}
+
+    fn draw(&mut self, canvas: &mut Canvas);
+
+    fn key_pressed(&mut self, key: VirtualKeyCode) {}
+
+    fn key_released(&mut self, key: VirtualKeyCode) {}
+
+    /// ``position`` is expressed in the drawing coordinates of the canvas
+    fn mouse_moved(&mut self, position: [f32; 2]) {}
+
+    fn mouse_pressed(&mut self, button: MouseButton) {}
+
+    fn mouse_released(&mut self, button: MouseButton) {}
+
+    /// The new size of the window, in logical pixels
+    fn resized(&mut self, width: f32, height: f32) {}
+
+    /// Called when ``run_sketch`` has written something to disk, or failed to,
+    /// for a hotkey or a recording. Nothing is printed otherwise
+    fn saved(&mut self, result: Result<Saved, SaveError>) {}
+}
+
+/// What ``run_sketch`` wrote to disk, see ``Sketch::saved``
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Saved {
+    /// A frame (S, V, H and G keys) or a print (P key)
+    File(PathBuf),
+    /// A recording started (R key): its frames go there
+    RecordingStarted(PathBuf),
+    /// A recording is complete: it reached its frame count, or was stopped
+    /// by the R key or by closing the window
+    Recording { path: PathBuf, frames: u64 },
+}
+
+/// Something ``run_sketch`` couldn't write to ``path``
+#[derive(Debug)]
+pub struct SaveError {
+    pub path: PathBuf,
+    pub error: OffscreenError,
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "could not save {}: {}", self.path.display(), self.error)
+    }
+}
+
+impl std::error::Error for SaveError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SketchSettings {
+    pub title: String,
+    /// Size of the window, in pixels. ``None`` lets the platform decide
+    pub size: Option<[u32; 2]>,
+    /// Number of samples used for anti-aliasing
+    pub multisampling: u16,
+    /// Frames per second
+    pub frame_rate: f32,
+    /// ``ColorPipeline::Linear`` asks the platform for an sRGB framebuffer
+    pub color_pipeline: ColorPipeline,
+    /// Records the sketch from its first frame
+    pub recording: Option<RecordingSettings>,
+    /// The big image saved by the P key. ``None`` saves a PNG 8 times as big as the window
+    pub print: Option<TiledRender>,
+    /// Whether the keys described in ``run_sketch`` save frames, recordings and prints.
+    /// On by default, turn them off when the sketch needs those keys for itself
+    pub hotkeys: bool,
+}
+
+impl Default for SketchSettings {
+    fn default() -> Self {
+        Self {
+            title: "glium 101".to_string(),
+            size: None,
+            multisampling: 16,
+            frame_rate: 60.0,
+            color_pipeline: ColorPipeline::default(),
+            recording: None,
+            print: None,
+            hotkeys: true,
+        }
+    }
+}
+
+impl SketchSettings {
+    pub fn new(title: &str) -> Self {
+        Self {
+            title: title.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_size(mut self, width: u32, height: u32) -> Self {
+        self.size = Some([width, height]);
+        self
+    }
+
+    pub fn with_multisampling(mut self, multisampling: u16) -> Self {
+        self.multisampling = multisampling;
+        self
+    }
+
+    pub fn with_frame_rate(mut self, frame_rate: f32) -> Self {
+        self.frame_rate = frame_rate;
+        self
+    }
+
+    pub fn with_color_pipeline(mut self, color_pipeline: ColorPipeline) -> Self {
+        self.color_pipeline = color_pipeline;
+        self
+    }
+
+    pub fn with_recording(mut self, recording: RecordingSettings) -> Self {
+        self.recording = Some(recording);
+        self
+    }
+
+    pub fn with_print(mut self, print: TiledRender) -> Self {
+        self.print = Some(print);
+        self
+    }
+
+    pub fn with_hotkeys(mut self, hotkeys: bool) -> Self {
+        self.hotkeys = hotkeys;
+        self
+    }
+}
+
+/// Opens a window and runs ``sketch`` in it, until the window is closed.
+///
+/// With ``SketchSettings::hotkeys``, a few keys are handled after the sketch gets them:
+/// S saves the current frame as a PNG (see ``DEFAULT_SCREENSHOT_PATTERN``),
+/// V saves it as an SVG, H and G save its strokes for a pen plotter (as HPGL or G-code),
+/// all of them named after the same pattern.
+/// R starts or stops recording frames (see ``recording``),
+/// and P saves the frame at a much higher resolution, for print (see ``tiled``)
+pub fn run_sketch<S: Sketch + 'static>(mut sketch: S, settings: SketchSettings) -> ! {
+    let event_loop = glutin::event_loop::EventLoop::new();
+    let mut window_builder = glutin::window::WindowBuilder::new().with_title(&settings.title);
+    if let Some([width, height]) = settings.size {
+        window_builder =
+            window_builder.with_inner_size(glutin::dpi::PhysicalSize::new(width, height));
+    }
+
+    let context_builder = glutin::ContextBuilder::new()
+        .with_multisampling(settings.multisampling)
+        .with_srgb(settings.color_pipeline == ColorPipeline::Linear);
+    let display = glium::Display::new(window_builder, context_builder, &event_loop).unwrap();
+
+    // The platform may ignore our request, or give us an sRGB framebuffer anyway:
+    // the canvas makes sure colors end up on screen as they were picked either way
+    let mut canvas = Canvas::new(&display);
+    canvas.set_color_pipeline(settings.color_pipeline);
+    canvas.set_multisampling(settings.multisampling);
+    canvas.set_frame_rate(settings.frame_rate);
+    if let Some(recording) = &settings.recording {
+        canvas.start_recording(recording.clone());
+    }
+    sketch.setup(&mut canvas);
+
+    let frame_duration = Duration::from_secs_f32(1.0 / settings.frame_rate.max(1.0));
+    let mut last_update: Option<Instant> = None;
+
+    event_loop.run(move |event, _, control_flow| match event {
+        // Other events (like the mouse moving around) don't cause a redraw
+        glutin::event::Event::NewEvents(
+            glutin::event::StartCause::Init
+            | glutin::event::StartCause::ResumeTimeReached { .. }
+            | glutin::event::StartCause::Poll,
+        ) => {
+            display.gl_window().window().request_redraw();
+            // While recording, the next frame is drawn as soon as the last one is saved
+            *control_flow = if canvas.is_recording() {
+                glutin::event_loop::ControlFlow::Poll
+            } else {
+                glutin::event_loop::ControlFlow::WaitUntil(Instant::now() + frame_duration)
+            };
+        }
+        glutin::event::Event::RedrawRequested(_) => {
+            let now = Instant::now();
+            let dt = match last_update {
+                None => 0.0,
+                // Recordings advance by a fixed step, however long frames take to save
+                Some(_) if canvas.is_recording() => frame_duration.as_secs_f32(),
+                Some(last_update) => (now - last_update).as_secs_f32(),
+            };
+            last_update = Some(now);
+
+            let mut frame = display.draw();
+            draw_frame(&mut sketch, &mut canvas, &mut frame, dt).unwrap();
+            frame.finish().unwrap();
+            record_frame(&mut sketch, &mut canvas);
+        }
+        glutin::event::Event::WindowEvent { event, .. } => match event {
+            glutin::event::WindowEvent::CloseRequested => {
+                // Videos and animations are only playable once completed
+                stop_recording(&mut sketch, &mut canvas);
+                *control_flow = glutin::event_loop::ControlFlow::Exit;
+            }
+            glutin::event::WindowEvent::Resized(size) => {
+                let scale_factor = canvas.scale_factor();
+                canvas.resize(size.width as f32, size.height as f32, scale_factor);
+                sketch.resized(canvas.width(), canvas.height());
+            }
+            // Moving the window to a screen with a different density changes
+            // both the scale factor and the size in physical pixels
+            glutin::event::WindowEvent::ScaleFactorChanged {
+                scale_factor,
+                new_inner_size,
+            } => {
+                canvas.resize(
+                    new_inner_size.width as f32,
+                    new_inner_size.height as f32,
+                    scale_factor as f32,
+                );
+                sketch.resized(canvas.width(), canvas.height());
+            }
+            glutin::event::WindowEvent::CursorMoved { position, .. } => {
+                sketch.mouse_moved(canvas.window_to_canvas([position.x as f32, position.y as f32]));
+            }
+            glutin::event::WindowEvent::MouseInput { state, button, .. } => match state {
+                glutin::event::ElementState::Pressed => sketch.mouse_pressed(button),
+                glutin::event::ElementState::Released => sketch.mouse_released(button),
+            },
+            glutin::event::WindowEvent::KeyboardInput {
+                input:
+                    glutin::event::KeyboardInput {
+                        state,
+                        virtual_keycode: Some(key),
+                        ..
+                    },
+                ..
+            } => match state {
+                glutin::event::ElementState::Pressed => {
+                    sketch.key_pressed(key);
+                    if settings.hotkeys {
+                        handle_hotkey(&mut sketch, &mut canvas, key, settings.print.as_ref());
+                    }
+                }
+                glutin::event::ElementState::Released => sketch.key_released(key),
+            },
+            _ => (),
+        },
+        _ => (),
+    })
+}
+
+/// Updates ``sketch`` by ``dt`` seconds, then draws it on ``canvas``
+/// and renders the canvas on ``surface``
+pub(crate) fn draw_frame(
+    sketch: &mut impl Sketch,
+    canvas: &mut Canvas,
+    surface: &mut impl glium::Surface,
+    dt: f32,
+) -> Result<(), InstancingError> {
+    sketch.update(dt);
+    canvas.begin_frame();
+    sketch.draw(canvas);
+    let result = canvas.render(surface);
+    canvas.end_frame();
+    result
+}
+
+/// Draws the sketch again into a big image, see ``tiled``
+fn print(sketch: &mut impl Sketch, canvas: &mut Canvas, settings: Option<&TiledRender>) {
+    let settings = settings.cloned().unwrap_or_else(|| {
+        let width = (canvas.width() * canvas.scale_factor() * 8.0).round() as u32;
+        TiledRender::new(frame_path("print-{timestamp}.png", 0), width)
+    });
+
+    let result = render_tiled(sketch, canvas, &settings);
+    sketch.saved(saved_file(settings.output, result));
+}
+
+/// Saves the frame that was just drawn when recording, and reports the end of the recording
+fn record_frame(sketch: &mut impl Sketch, canvas: &mut Canvas) {
+    let Some((path, frames)) = canvas.recorder().map(|recorder| {
+        let path = recorder.settings().output.path().to_path_buf();
+        (path, recorder.recorded_frames())
+    }) else {
+        return;
+    };
+
+    match canvas.record_frame() {
+        Ok(_) if !canvas.is_recording() => sketch.saved(Ok(Saved::Recording {
+            path,
+            frames: frames + 1,
+        })),
+        Ok(_) => (),
+        Err(error) => {
+            // The recording is stopped, since every frame would fail the same way
+            sketch.saved(Err(SaveError { path, error }));
+            stop_recording(sketch, canvas);
+        }
+    }
+}
+
+/// Stops the recording in progress, if any, and reports where it went
+fn stop_recording(sketch: &mut impl Sketch, canvas: &mut Canvas) {
+    let Some(path) = canvas
+        .recorder()
+        .map(|recorder| recorder.settings().output.path().to_path_buf())
+    else {
+        return;
+    };
+
+    match canvas.stop_recording() {
+        Ok(recorder) => sketch.saved(Ok(Saved::Recording {
+            path,
+            frames: recorder.map_or(0, |recorder| recorder.recorded_frames()),
+        })),
+        Err(error) => sketch.saved(Err(SaveError {
+            path,
+            error: error.into(),
+        })),
+    }
+}
+
+/// The keys described in ``run_sketch``
+fn handle_hotkey(
+    sketch: &mut impl Sketch,
+    canvas: &mut Canvas,
+    key: VirtualKeyCode,
+    print_settings: Option<&TiledRender>,
+) {
+    match key {
+        VirtualKeyCode::P => return print(sketch, canvas, print_settings),
+        VirtualKeyCode::R if canvas.is_recording() => return stop_recording(sketch, canvas),
+        VirtualKeyCode::R => {
+            let settings = RecordingSettings::timestamped();
+            let path = settings.output.path().to_path_buf();
+            canvas.start_recording(settings);
+            return sketch.saved(Ok(Saved::RecordingStarted(path)));
+        }
+        _ => (),
+    }
+
+    let extension = match key {
+        VirtualKeyCode::S => "png",
+        VirtualKeyCode::V => "svg",
+        VirtualKeyCode::H => "hpgl",
+        VirtualKeyCode::G => "gcode",
+        _ => return,
+    };
+    let path = frame_file(canvas, extension);
+
+    let plot =
+        |canvas: &Canvas| Plot::from_recording(canvas.recording(), &PlotterSettings::default());
+    let result = match key {
+        // The canvas still holds the last frame, and renders it again offscreen
+        VirtualKeyCode::S => canvas
+            .render_image()
+            .and_then(|image| Ok(image.save_png(&path)?)),
+        VirtualKeyCode::V => canvas.recording().save(&path).map_err(OffscreenError::from),
+        VirtualKeyCode::H => plot(canvas).save_hpgl(&path).map_err(OffscreenError::from),
+        _ => plot(canvas)
+            .save_gcode(&path, &GcodeSettings::default())
+            .map_err(OffscreenError::from),
+    };
+    sketch.saved(saved_file(path, result));
+}
+
+/// Where the S, V, H and G keys save the frame, see ``DEFAULT_SCREENSHOT_PATTERN``
+fn frame_file(canvas: &Canvas, extension: &str) -> PathBuf {
+    frame_path(DEFAULT_SCREENSHOT_PATTERN, canvas.frame_count()).with_extension(extension)
+}
+
+fn saved_file(path: PathBuf, result: Result<(), OffscreenError>) -> Result<Saved, SaveError> {
+    match result {
+        Ok(()) => Ok(Saved::File(path)),
+        Err(error) => Err(SaveError { path, error }),
+    }
+}
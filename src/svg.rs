@@ -0,0 +1,7 @@
+//! Interoperability with SVG files.
+
+pub mod export;
+pub mod import;
+
+pub use export::{RecordedInstances, SvgRecording};
+pub use import::{load_svg, parse_path_data, parse_svg, SvgDocument, SvgError};
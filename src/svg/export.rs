@@ -0,0 +1,617 @@
+//! Recording of the shapes drawn in a frame, and their serialization as an SVG document.
+//!
+//! The recording keeps the original paths (curves included) instead of the triangles sent
+//! to the GPU, so the output stays resolution independent: ideal for print and plotters.
+
+use std::borrow::Cow;
+use std::fmt::Write;
+use std::rc::Rc;
+
+use crate::blend::BlendMode;
+use crate::color::Color;
+use crate::gradient::{ColorInterpolation, Gradient, GradientKind, SpreadMode};
+use crate::path::{Path, PathCommand};
+use crate::shapes::{primitive_path, Paint, Shape, ShapePrimitive, ShapeStyle, Vertex};
+use crate::tessellation::polygon::FillRule;
+use crate::tessellation::stroke::{LineCap, LineJoin};
+use crate::transform::Transform;
+
+/// The copies of a mesh drawn by ``Canvas::draw_instances``. Instancing is meant for
+/// thousands of copies, so their shapes are only built when the recording is exported
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordedInstances {
+    /// How many shapes were recorded before the copies
+    pub position: usize,
+    pub outline: Rc<Path>,
+    /// Where each copy goes (in pixels) and its color
+    pub copies: Vec<(Transform, Color)>,
+    pub blend_mode: BlendMode,
+}
+
+impl RecordedInstances {
+    /// A filled shape per visible copy
+    pub fn shapes(&self) -> impl Iterator<Item = Shape> + '_ {
+        self.copies
+            .iter()
+            .filter(|(_, color)| color.a > 0.0)
+            .map(|(transform, color)| {
+                let mut path = (*self.outline).clone();
+                path.transform(transform);
+                Shape::new(
+                    path,
+                    ShapeStyle::filled(*color).with_blend_mode(self.blend_mode),
+                )
+            })
+    }
+}
+
+/// All of the shapes drawn during a frame, in the order they were drawn
+#[derive(Clone, Debug, PartialEq)]
+pub struct SvgRecording {
+    /// Size of the window, in pixels
+    pub width: f32,
+    pub height: f32,
+    pub background: Option<Color>,
+    /// Shapes already converted to the pixel space of the window
+    pub shapes: Vec<Shape>,
+    /// Copies of meshes, drawn among the shapes. See ``shapes_in_order``
+    pub instances: Vec<RecordedInstances>,
+    /// Goes from the coordinates used when drawing to the pixels of the window
+    to_pixels: Transform,
+}
+
+impl SvgRecording {
+    /// ``to_pixels`` converts the coordinates used to draw the shapes
+    /// into pixels, with the origin in the top left corner and Y pointing down
+    pub fn new(width: f32, height: f32, to_pixels: Transform) -> Self {
+        Self {
+            width,
+            height,
+            background: None,
+            shapes: Vec::new(),
+            instances: Vec::new(),
+            to_pixels,
+        }
+    }
+
+    /// A recording for shapes drawn in normalized device coordinates,
+    /// where (-1, -1) is the bottom left corner of the window and (1, 1) the top right one
+    pub fn for_normalized_device_coordinates(width: f32, height: f32) -> Self {
+        let to_pixels = Transform::new(
+            width * 0.5,
+            0.0,
+            0.0,
+            -height * 0.5,
+            width * 0.5,
+            height * 0.5,
+        );
+        Self::new(width, height, to_pixels)
+    }
+
+    /// Forgets all of the recorded shapes, to start recording a new frame
+    pub fn clear(&mut self) {
+        self.background = None;
+        self.shapes.clear();
+        self.instances.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shapes.is_empty() && self.instances.is_empty() && self.background.is_none()
+    }
+
+    /// Records a clear of the whole frame: anything recorded before is hidden by it anyway
+    pub fn record_clear(&mut self, color: Color) {
+        self.shapes.clear();
+        self.instances.clear();
+        self.background = Some(color);
+    }
+
+    /// Records the same shape passed to ``generate_draw_commands``
+    pub fn record_primitive(
+        &mut self,
+        vertices: &[Vertex],
+        primitive: &ShapePrimitive,
+        style: &ShapeStyle,
+    ) {
+        self.record_path(&primitive_path(vertices, primitive), style);
+    }
+
+    /// Records the same shape passed to ``generate_path_draw_commands``
+    pub fn record_path(&mut self, path: &Path, style: &ShapeStyle) {
+        if path.is_empty() || !style.is_visible() {
+            return;
+        }
+
+        let mut shape = Shape::new(path.clone(), style.clone());
+        shape.transform(&self.to_pixels);
+        self.shapes.push(shape);
+    }
+
+    pub fn record_shape(&mut self, shape: &Shape) {
+        self.record_path(&shape.path, &shape.style);
+    }
+
+    /// Records copies of ``outline``, each one moved by its transform and filled with its color
+    pub fn record_instances(
+        &mut self,
+        outline: &Rc<Path>,
+        copies: impl IntoIterator<Item = (Transform, Color)>,
+        blend_mode: BlendMode,
+    ) {
+        let copies: Vec<_> = copies
+            .into_iter()
+            .map(|(transform, color)| (self.to_pixels.multiply(&transform), color))
+            .collect();
+        if outline.is_empty() || copies.is_empty() {
+            return;
+        }
+
+        self.instances.push(RecordedInstances {
+            position: self.shapes.len(),
+            outline: outline.clone(),
+            copies,
+            blend_mode,
+        });
+    }
+
+    /// The recorded shapes, with the copies of the instances where they were drawn
+    pub fn shapes_in_order(&self) -> impl Iterator<Item = Cow<'_, Shape>> {
+        let mut instances = self.instances.iter().peekable();
+        (0..=self.shapes.len()).flat_map(move |index| {
+            let mut shapes = Vec::new();
+            while let Some(recorded) = instances.next_if(|recorded| recorded.position == index) {
+                shapes.extend(recorded.shapes().map(Cow::Owned));
+            }
+            shapes
+                .into_iter()
+                .chain(self.shapes.get(index).map(Cow::Borrowed))
+        })
+    }
+
+    pub fn to_svg_string(&self) -> String {
+        let mut svg = String::new();
+        let width = format_number(self.width);
+        let height = format_number(self.height);
+
+        // Writing into a String can't fail, hence all of the unwraps below
+        writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}">"#
+        )
+        .unwrap();
+
+        if let Some(background) = self.background {
+            writeln!(
+                svg,
+                r#"  <rect width="{width}" height="{height}"{}/>"#,
+                color_attributes("fill", background)
+            )
+            .unwrap();
+        }
+
+        // Gradients are defined up front and referenced by the shapes painted with them
+        let mut defs = Defs::default();
+        let mut paths = String::new();
+        for shape in self.shapes_in_order() {
+            writeln!(
+                paths,
+                r#"  <path d="{}"{}/>"#,
+                path_data(&shape.path),
+                style_attributes(&shape.style, &mut defs)
+            )
+            .unwrap();
+        }
+
+        if !defs.elements.is_empty() {
+            writeln!(svg, "  <defs>\n{}  </defs>", defs.elements).unwrap();
+        }
+        svg.push_str(&paths);
+
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    pub fn save(&self, file_path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+        std::fs::write(file_path, self.to_svg_string())
+    }
+}
+
+/// Numbers are written with at most 3 decimals, without trailing zeros
+fn format_number(value: f32) -> String {
+    let text = format!("{value:.3}");
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    match text {
+        "-0" | "" => "0".to_string(),
+        _ => text.to_string(),
+    }
+}
+
+fn format_point(point: [f32; 2]) -> String {
+    format!("{} {}", format_number(point[0]), format_number(point[1]))
+}
+
+/// Serializes the commands of ``path`` using the SVG path syntax
+pub fn path_data(path: &Path) -> String {
+    let mut data = Vec::with_capacity(path.commands().len());
+
+    for command in path.commands() {
+        data.push(match *command {
+            PathCommand::MoveTo(to) => format!("M{}", format_point(to)),
+            PathCommand::LineTo(to) => format!("L{}", format_point(to)),
+            PathCommand::QuadTo { control, to } => {
+                format!("Q{} {}", format_point(control), format_point(to))
+            }
+            PathCommand::CubicTo {
+                control_1,
+                control_2,
+                to,
+            } => format!(
+                "C{} {} {}",
+                format_point(control_1),
+                format_point(control_2),
+                format_point(to)
+            ),
+            PathCommand::Close => "Z".to_string(),
+        });
+    }
+
+    data.join(" ")
+}
+
+fn color_hex(color: Color) -> String {
+    let channel = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+    format!(
+        "#{:02x}{:02x}{:02x}",
+        channel(color.r),
+        channel(color.g),
+        channel(color.b)
+    )
+}
+
+/// ``fill="#rrggbb"`` (or stroke), plus the opacity if the color isn't opaque
+fn color_attributes(property: &str, color: Color) -> String {
+    let mut attributes = format!(r#" {property}="{}""#, color_hex(color));
+    if color.a < 1.0 {
+        write!(
+            attributes,
+            r#" {property}-opacity="{}""#,
+            format_number(color.a.max(0.0))
+        )
+        .unwrap();
+    }
+    attributes
+}
+
+/// The elements referenced by the shapes, written before them
+#[derive(Default)]
+struct Defs {
+    elements: String,
+    /// Gradients are named after the number of gradients defined before them
+    gradient_count: usize,
+}
+
+/// Like ``color_attributes``, but gradients are referenced by id after being added to ``defs``.
+/// SVG has no conic nor mesh gradients, so those are exported as their average color
+fn paint_attributes(property: &str, paint: &Paint, defs: &mut Defs) -> String {
+    match paint {
+        Paint::Solid(color) => color_attributes(property, *color),
+        Paint::Gradient(gradient) => {
+            let id = format!("gradient-{}", defs.gradient_count);
+            match gradient_element(gradient, &id) {
+                Some(element) => {
+                    defs.elements.push_str(&element);
+                    defs.gradient_count += 1;
+                    format!(r#" {property}="url(#{id})""#)
+                }
+                None => color_attributes(property, gradient.average_color()),
+            }
+        }
+    }
+}
+
+/// A ``<linearGradient>`` or ``<radialGradient>`` element, with the stops in user space
+fn gradient_element(gradient: &Gradient, id: &str) -> Option<String> {
+    let (tag, geometry) = match gradient.kind {
+        GradientKind::Linear { start, end } => (
+            "linearGradient",
+            format!(
+                r#"x1="{}" y1="{}" x2="{}" y2="{}""#,
+                format_number(start[0]),
+                format_number(start[1]),
+                format_number(end[0]),
+                format_number(end[1])
+            ),
+        ),
+        GradientKind::Radial { center, radius } => (
+            "radialGradient",
+            format!(
+                r#"cx="{}" cy="{}" r="{}""#,
+                format_number(center[0]),
+                format_number(center[1]),
+                format_number(radius)
+            ),
+        ),
+        GradientKind::Conic { .. } | GradientKind::Mesh { .. } => return None,
+    };
+
+    let spread = match gradient.spread {
+        SpreadMode::Pad => "pad",
+        SpreadMode::Repeat => "repeat",
+        SpreadMode::Reflect => "reflect",
+    };
+    let Transform { a, b, c, d, e, f } = gradient.transform;
+    let matrix = [a, b, c, d, e, f].map(format_number).join(" ");
+
+    let mut element = format!(
+        r#"    <{tag} id="{id}" {geometry} gradientUnits="userSpaceOnUse" gradientTransform="matrix({matrix})" spreadMethod="{spread}">"#
+    );
+    element.push('\n');
+    for (offset, color) in svg_stops(gradient) {
+        writeln!(
+            element,
+            r#"      <stop offset="{}" stop-color="{}" stop-opacity="{}"/>"#,
+            format_number(offset),
+            color_hex(color),
+            format_number(color.a.clamp(0.0, 1.0))
+        )
+        .unwrap();
+    }
+    writeln!(element, "    </{tag}>").unwrap();
+    Some(element)
+}
+
+/// SVG viewers interpolate between stops in sRGB:
+/// other interpolations are approximated with extra stops
+fn svg_stops(gradient: &Gradient) -> Vec<(f32, Color)> {
+    const STEPS_BETWEEN_STOPS: usize = 8;
+
+    if gradient.interpolation == ColorInterpolation::Srgb {
+        return gradient
+            .stops
+            .iter()
+            .map(|stop| (stop.offset, stop.color))
+            .collect();
+    }
+
+    let mut stops = Vec::new();
+    for pair in gradient.stops.windows(2) {
+        let (from, to) = (pair[0], pair[1]);
+        stops.push((from.offset, from.color));
+        for step in 1..STEPS_BETWEEN_STOPS {
+            let t = step as f32 / STEPS_BETWEEN_STOPS as f32;
+            let offset = from.offset + (to.offset - from.offset) * t;
+            stops.push((offset, gradient.interpolation.mix(from.color, to.color, t)));
+        }
+    }
+    if let Some(last) = gradient.stops.last() {
+        stops.push((last.offset, last.color));
+    }
+    stops
+}
+
+fn style_attributes(style: &ShapeStyle, defs: &mut Defs) -> String {
+    let mut attributes = match &style.fill {
+        // SVG fills follow the non-zero rule by default
+        Some(fill) => match style.fill_rule {
+            FillRule::EvenOdd => paint_attributes("fill", fill, defs) + r#" fill-rule="evenodd""#,
+            FillRule::NonZero => paint_attributes("fill", fill, defs),
+        },
+        None => r#" fill="none""#.to_string(),
+    };
+
+    if let Some(stroke) = style.stroke.as_ref().filter(|_| style.stroke_width > 0.0) {
+        attributes += &paint_attributes("stroke", stroke, defs);
+        write!(
+            attributes,
+            r#" stroke-width="{}""#,
+            format_number(style.stroke_width)
+        )
+        .unwrap();
+
+        match style.line_join {
+            LineJoin::Miter => write!(
+                attributes,
+                r#" stroke-miterlimit="{}""#,
+                format_number(style.miter_limit)
+            )
+            .unwrap(),
+            LineJoin::Round => attributes += r#" stroke-linejoin="round""#,
+            LineJoin::Bevel => attributes += r#" stroke-linejoin="bevel""#,
+        }
+
+        match style.line_cap {
+            LineCap::Butt => (),
+            LineCap::Round => attributes += r#" stroke-linecap="round""#,
+            LineCap::Square => attributes += r#" stroke-linecap="square""#,
+        }
+    }
+
+    // Modes without a CSS equivalent are exported as normal ones
+    if style.blend_mode != BlendMode::Normal {
+        if let Some(name) = style.blend_mode.css_name() {
+            write!(attributes, r#" style="mix-blend-mode:{name}""#).unwrap();
+        }
+    }
+
+    attributes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str =
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="50" viewBox="0 0 100 50">"#;
+
+    fn square() -> Path {
+        let mut path = Path::new();
+        path.rect([10.0, 10.0], [20.0, 20.0]);
+        path
+    }
+
+    /// The SVG document of a single shape, drawn in pixels
+    fn svg_of(path: &Path, style: &ShapeStyle) -> String {
+        let mut recording = SvgRecording::new(100.0, 50.0, Transform::identity());
+        recording.record_path(path, style);
+        recording.to_svg_string()
+    }
+
+    fn document(body: &str) -> String {
+        format!("{HEADER}\n{body}</svg>\n")
+    }
+
+    #[test]
+    fn filled_shape() {
+        let style = ShapeStyle::filled(Color::new(1.0, 0.0, 0.0, 0.5));
+        assert_eq!(
+            svg_of(&square(), &style),
+            document(
+                r##"  <path d="M10 10 L30 10 L30 30 L10 30 Z" fill="#ff0000" fill-opacity="0.5" fill-rule="evenodd"/>
+"##
+            )
+        );
+    }
+
+    #[test]
+    fn stroked_shape() {
+        let mut path = Path::new();
+        path.move_to([0.0, 0.0])
+            .quad_to([5.0, 10.0], [10.0, 0.0])
+            .cubic_to([12.5, 1.0], [15.0, 2.0], [20.0, 0.0]);
+        let style = ShapeStyle::stroked(Color::new(0.0, 0.0, 1.0, 1.0), 2.5)
+            .with_line_join(LineJoin::Round)
+            .with_line_cap(LineCap::Square);
+        assert_eq!(
+            svg_of(&path, &style),
+            document(
+                r##"  <path d="M0 0 Q5 10 10 0 C12.5 1 15 2 20 0" fill="none" stroke="#0000ff" stroke-width="2.5" stroke-linejoin="round" stroke-linecap="square"/>
+"##
+            )
+        );
+    }
+
+    #[test]
+    fn gradient() {
+        let gradient = Gradient::linear([10.0, 0.0], [30.0, 0.0])
+            .with_colors(&[
+                Color::new(1.0, 1.0, 1.0, 1.0),
+                Color::new(0.0, 0.0, 0.0, 0.25),
+            ])
+            .with_spread(SpreadMode::Reflect);
+        let style = ShapeStyle::filled(gradient);
+        assert_eq!(
+            svg_of(&square(), &style),
+            document(
+                r##"  <defs>
+    <linearGradient id="gradient-0" x1="10" y1="0" x2="30" y2="0" gradientUnits="userSpaceOnUse" gradientTransform="matrix(1 0 0 1 0 0)" spreadMethod="reflect">
+      <stop offset="0" stop-color="#ffffff" stop-opacity="1"/>
+      <stop offset="1" stop-color="#000000" stop-opacity="0.25"/>
+    </linearGradient>
+  </defs>
+  <path d="M10 10 L30 10 L30 30 L10 30 Z" fill="url(#gradient-0)" fill-rule="evenodd"/>
+"##
+            )
+        );
+    }
+
+    #[test]
+    fn gradients_get_their_own_ids() {
+        let ramp = [
+            Color::new(1.0, 0.0, 0.0, 1.0),
+            Color::new(0.0, 0.0, 1.0, 1.0),
+        ];
+        let linear = Gradient::linear([0.0, 0.0], [1.0, 0.0]).with_colors(&ramp);
+        let radial = Gradient::radial([0.0, 0.0], 1.0).with_colors(&ramp);
+        // Exported as a plain color, so it doesn't take an id
+        let conic = Gradient::conic([0.0, 0.0], 0.0).with_colors(&ramp);
+
+        let mut recording = SvgRecording::new(100.0, 50.0, Transform::identity());
+        recording.record_path(&square(), &ShapeStyle::filled(linear.clone()));
+        recording.record_path(&square(), &ShapeStyle::filled(conic));
+        recording.record_path(&square(), &ShapeStyle::stroked(radial, 1.0));
+        recording.record_path(&square(), &ShapeStyle::filled(linear));
+        let svg = recording.to_svg_string();
+
+        for id in 0..3 {
+            assert_eq!(svg.matches(&format!(r#"id="gradient-{id}""#)).count(), 1);
+            assert_eq!(svg.matches(&format!("url(#gradient-{id})")).count(), 1);
+        }
+        assert!(!svg.contains("gradient-3"));
+    }
+
+    #[test]
+    fn blend_mode() {
+        let style =
+            ShapeStyle::filled(Color::new(0.0, 1.0, 0.0, 1.0)).with_blend_mode(BlendMode::Multiply);
+        assert_eq!(
+            svg_of(&square(), &style),
+            document(
+                r##"  <path d="M10 10 L30 10 L30 30 L10 30 Z" fill="#00ff00" fill-rule="evenodd" style="mix-blend-mode:multiply"/>
+"##
+            )
+        );
+
+        // Modes SVG doesn't know are exported as normal ones
+        let subtract = style.with_blend_mode(BlendMode::Subtract);
+        assert!(!svg_of(&square(), &subtract).contains("mix-blend-mode"));
+    }
+
+    #[test]
+    fn transformed_shape() {
+        // Drawn in normalized device coordinates, with Y pointing up
+        let mut recording = SvgRecording::for_normalized_device_coordinates(100.0, 50.0);
+        let mut path = Path::new();
+        path.move_to([-1.0, 1.0]).line_to([0.5, -0.5]);
+        let style = ShapeStyle::stroked(Color::new(0.0, 0.0, 0.0, 1.0), 0.1)
+            .with_line_join(LineJoin::Bevel);
+        recording.record_path(&path, &style);
+        recording.record_clear(Color::new(1.0, 1.0, 1.0, 1.0));
+        recording.record_path(&path, &style);
+
+        // The stroke width is scaled too, by the geometric mean of the two scales
+        assert_eq!(
+            recording.to_svg_string(),
+            document(
+                r##"  <rect width="100" height="50" fill="#ffffff"/>
+  <path d="M0 0 L75 37.5" fill="none" stroke="#000000" stroke-width="3.536" stroke-linejoin="bevel"/>
+"##
+            )
+        );
+    }
+
+    #[test]
+    fn instances_are_exported_where_they_were_drawn() {
+        let mut recording = SvgRecording::new(100.0, 50.0, Transform::identity());
+        let red = ShapeStyle::filled(Color::new(1.0, 0.0, 0.0, 1.0));
+        recording.record_path(&square(), &red);
+
+        let mut triangle = Path::new();
+        triangle.polygon(&[[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]]);
+        let copies = [
+            (
+                Transform::translation(5.0, 5.0),
+                Color::new(0.0, 0.0, 1.0, 1.0),
+            ),
+            // Invisible copies are left out
+            (Transform::identity(), Color::new(0.0, 0.0, 1.0, 0.0)),
+            (Transform::scaling(2.0, 2.0), Color::new(0.0, 1.0, 0.0, 1.0)),
+        ];
+        recording.record_instances(&Rc::new(triangle), copies, BlendMode::Normal);
+        recording.record_path(&square(), &red);
+
+        assert_eq!(recording.shapes.len(), 2);
+        assert_eq!(
+            recording.to_svg_string(),
+            document(
+                r##"  <path d="M10 10 L30 10 L30 30 L10 30 Z" fill="#ff0000" fill-rule="evenodd"/>
+  <path d="M5 5 L6 5 L5 6 Z" fill="#0000ff" fill-rule="evenodd"/>
+  <path d="M0 0 L2 0 L0 2 Z" fill="#00ff00" fill-rule="evenodd"/>
+  <path d="M10 10 L30 10 L30 30 L10 30 Z" fill="#ff0000" fill-rule="evenodd"/>
+"##
+            )
+        );
+
+        recording.record_clear(Color::new(1.0, 1.0, 1.0, 1.0));
+        assert!(recording.instances.is_empty());
+    }
+}
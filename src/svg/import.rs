@@ -0,0 +1,978 @@
+//! Loading of SVG documents (and of bare path ``d`` strings) into ``Shape``s.
+//!
+//! Only the static subset of SVG that matters for our sketches is supported:
+//! ``path``, ``rect``, ``circle``, ``ellipse``, ``line``, ``polygon`` and ``polyline`` elements,
+//! nested groups, transforms, and solid fills and strokes (including the ``style`` attribute).
+//! Gradients, patterns, text, clipping and masks are ignored.
+
+use std::fmt;
+
+use crate::blend::BlendMode;
+use crate::color::Color;
+use crate::path::Path;
+use crate::shapes::{Shape, ShapeStyle};
+use crate::tessellation::polygon::FillRule;
+use crate::tessellation::stroke::{LineCap, LineJoin};
+use crate::transform::Transform;
+
+#[derive(Debug)]
+pub enum SvgError {
+    Io(std::io::Error),
+    Xml(roxmltree::Error),
+    /// The ``d`` attribute of a path couldn't be parsed,
+    /// ``position`` being the byte offset where things went wrong
+    PathData {
+        position: usize,
+        message: String,
+    },
+    /// The root element isn't ``<svg>``
+    NotSvg,
+}
+
+impl fmt::Display for SvgError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SvgError::Io(error) => write!(f, "could not read the SVG file: {error}"),
+            SvgError::Xml(error) => write!(f, "invalid SVG document: {error}"),
+            SvgError::PathData { position, message } => {
+                write!(f, "invalid path data at byte {position}: {message}")
+            }
+            SvgError::NotSvg => write!(f, "the root element of the document is not <svg>"),
+        }
+    }
+}
+
+impl std::error::Error for SvgError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SvgError::Io(error) => Some(error),
+            SvgError::Xml(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for SvgError {
+    fn from(error: std::io::Error) -> Self {
+        SvgError::Io(error)
+    }
+}
+
+impl From<roxmltree::Error> for SvgError {
+    fn from(error: roxmltree::Error) -> Self {
+        SvgError::Xml(error)
+    }
+}
+
+/// The shapes of an SVG document, in its own coordinate space:
+/// the origin is the top left corner, Y points down, and the units are CSS pixels
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SvgDocument {
+    pub width: f32,
+    pub height: f32,
+    /// Shapes in painting order (the first one is at the bottom)
+    pub shapes: Vec<Shape>,
+}
+
+impl SvgDocument {
+    /// Applies ``transform`` to every shape of the document,
+    /// for example to go from SVG pixels to the coordinates used by the sketch
+    pub fn transform(&mut self, transform: &Transform) {
+        for shape in &mut self.shapes {
+            shape.transform(transform);
+        }
+    }
+}
+
+pub fn load_svg(file_path: impl AsRef<std::path::Path>) -> Result<SvgDocument, SvgError> {
+    let text = std::fs::read_to_string(file_path)?;
+    parse_svg(&text)
+}
+
+pub fn parse_svg(text: &str) -> Result<SvgDocument, SvgError> {
+    let document = roxmltree::Document::parse(text)?;
+    let root = document.root_element();
+
+    if root.tag_name().name() != "svg" {
+        return Err(SvgError::NotSvg);
+    }
+
+    let view_box = root.attribute("viewBox").and_then(|value| {
+        let numbers = parse_number_list(value);
+        (numbers.len() == 4 && numbers[2] > 0.0 && numbers[3] > 0.0)
+            .then(|| [numbers[0], numbers[1], numbers[2], numbers[3]])
+    });
+
+    let width = root.attribute("width").and_then(parse_length);
+    let height = root.attribute("height").and_then(parse_length);
+
+    let (width, height, root_transform) = match view_box {
+        Some([min_x, min_y, view_width, view_height]) => {
+            let width = width.unwrap_or(view_width);
+            let height = height.unwrap_or(view_height);
+
+            // Default preserveAspectRatio: uniform scale, centered ("xMidYMid meet")
+            let scale = (width / view_width).min(height / view_height);
+            let offset_x = (width - view_width * scale) * 0.5;
+            let offset_y = (height - view_height * scale) * 0.5;
+            let transform = Transform::translation(offset_x, offset_y)
+                .multiply(&Transform::scaling(scale, scale))
+                .multiply(&Transform::translation(-min_x, -min_y));
+
+            (width, height, transform)
+        }
+        None => (
+            width.unwrap_or(300.0),
+            height.unwrap_or(150.0),
+            Transform::identity(),
+        ),
+    };
+
+    let mut svg_document = SvgDocument {
+        width,
+        height,
+        shapes: Vec::new(),
+    };
+
+    let context = Context {
+        transform: root_transform,
+        style: InheritedStyle::default(),
+    };
+    visit_element(root, &context, &mut svg_document.shapes);
+
+    Ok(svg_document)
+}
+
+/// Everything that is passed down from a group to its children
+#[derive(Copy, Clone, Debug)]
+struct Context {
+    transform: Transform,
+    style: InheritedStyle,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct InheritedStyle {
+    fill: Option<Color>,
+    stroke: Option<Color>,
+    stroke_width: f32,
+    line_join: LineJoin,
+    line_cap: LineCap,
+    miter_limit: f32,
+    fill_rule: FillRule,
+    fill_opacity: f32,
+    stroke_opacity: f32,
+    // Strictly speaking opacity isn't inherited, it applies to the group as a whole.
+    // Multiplying it down to the children is a good enough approximation for us
+    opacity: f32,
+    // Same goes for the blend mode
+    blend_mode: BlendMode,
+}
+
+impl Default for InheritedStyle {
+    fn default() -> Self {
+        // The initial values defined by the SVG specification
+        Self {
+            fill: Some(Color::new(0.0, 0.0, 0.0, 1.0)),
+            stroke: None,
+            stroke_width: 1.0,
+            line_join: LineJoin::Miter,
+            line_cap: LineCap::Butt,
+            miter_limit: 4.0,
+            fill_rule: FillRule::NonZero,
+            fill_opacity: 1.0,
+            stroke_opacity: 1.0,
+            opacity: 1.0,
+            blend_mode: BlendMode::Normal,
+        }
+    }
+}
+
+impl InheritedStyle {
+    fn apply_property(&mut self, name: &str, value: &str) {
+        let value = value.trim();
+        if value == "inherit" {
+            return;
+        }
+
+        match name {
+            "fill" => {
+                if let Some(paint) = parse_paint(value) {
+                    self.fill = paint;
+                }
+            }
+            "stroke" => {
+                if let Some(paint) = parse_paint(value) {
+                    self.stroke = paint;
+                }
+            }
+            "stroke-width" => {
+                if let Some(width) = parse_length(value) {
+                    self.stroke_width = width;
+                }
+            }
+            "stroke-linejoin" => {
+                self.line_join = match value {
+                    "round" => LineJoin::Round,
+                    "bevel" => LineJoin::Bevel,
+                    _ => LineJoin::Miter,
+                }
+            }
+            "stroke-linecap" => {
+                self.line_cap = match value {
+                    "round" => LineCap::Round,
+                    "square" => LineCap::Square,
+                    _ => LineCap::Butt,
+                }
+            }
+            "stroke-miterlimit" => {
+                if let Ok(limit) = value.parse() {
+                    self.miter_limit = limit;
+                }
+            }
+            "fill-rule" => {
+                self.fill_rule = match value {
+                    "evenodd" => FillRule::EvenOdd,
+                    _ => FillRule::NonZero,
+                }
+            }
+            "fill-opacity" => self.fill_opacity = parse_opacity(value, self.fill_opacity),
+            "stroke-opacity" => self.stroke_opacity = parse_opacity(value, self.stroke_opacity),
+            "opacity" => self.opacity *= parse_opacity(value, 1.0),
+            "mix-blend-mode" => {
+                if let Some(blend_mode) = BlendMode::from_css_name(value) {
+                    self.blend_mode = blend_mode;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Presentation attributes first, then the ``style`` attribute which has priority over them
+    fn apply_element(&mut self, node: roxmltree::Node) {
+        const PROPERTIES: [&str; 11] = [
+            "fill",
+            "stroke",
+            "stroke-width",
+            "stroke-linejoin",
+            "stroke-linecap",
+            "stroke-miterlimit",
+            "fill-rule",
+            "fill-opacity",
+            "stroke-opacity",
+            "opacity",
+            "mix-blend-mode",
+        ];
+
+        for name in PROPERTIES {
+            if let Some(value) = node.attribute(name) {
+                self.apply_property(name, value);
+            }
+        }
+
+        if let Some(style) = node.attribute("style") {
+            for declaration in style.split(';') {
+                if let Some((name, value)) = declaration.split_once(':') {
+                    self.apply_property(name.trim(), value);
+                }
+            }
+        }
+    }
+
+    fn to_shape_style(self, transform: &Transform) -> ShapeStyle {
+        let with_alpha = |color: Color, alpha: f32| Color {
+            a: color.a * alpha,
+            ..color
+        };
+
+        ShapeStyle {
+            fill: self
+                .fill
+                .map(|color| with_alpha(color, self.fill_opacity * self.opacity).into()),
+            stroke: self
+                .stroke
+                .map(|color| with_alpha(color, self.stroke_opacity * self.opacity).into()),
+            stroke_width: self.stroke_width * transform.average_scale(),
+            line_join: self.line_join,
+            line_cap: self.line_cap,
+            miter_limit: self.miter_limit,
+            fill_rule: self.fill_rule,
+            blend_mode: self.blend_mode,
+        }
+    }
+}
+
+fn visit_element(node: roxmltree::Node, parent: &Context, shapes: &mut Vec<Shape>) {
+    if node.attribute("display") == Some("none") {
+        return;
+    }
+
+    let mut context = *parent;
+    context.style.apply_element(node);
+    if let Some(transform) = node.attribute("transform") {
+        context.transform = context.transform.multiply(&parse_transform(transform));
+    }
+
+    let tag = node.tag_name().name();
+    match tag {
+        "svg" | "g" | "a" => {
+            for child in node.children().filter(|child| child.is_element()) {
+                visit_element(child, &context, shapes);
+            }
+            return;
+        }
+        // Definitions are only drawn when referenced, which we don't support
+        "defs" | "symbol" | "clipPath" | "mask" | "pattern" | "marker" | "style" | "title"
+        | "desc" | "metadata" => return,
+        _ => (),
+    }
+
+    let Some(mut path) = element_path(node, tag) else {
+        return;
+    };
+
+    let hidden = matches!(node.attribute("visibility"), Some("hidden" | "collapse"));
+    let style = context.style.to_shape_style(&context.transform);
+    if path.is_empty() || hidden || !style.is_visible() {
+        return;
+    }
+
+    path.transform(&context.transform);
+    shapes.push(Shape::new(path, style));
+}
+
+/// The geometry of a basic shape or path element, ``None`` for elements we don't draw
+fn element_path(node: roxmltree::Node, tag: &str) -> Option<Path> {
+    let number = |name: &str| node.attribute(name).and_then(parse_length).unwrap_or(0.0);
+    let mut path = Path::new();
+
+    match tag {
+        "path" => {
+            // Like browsers, we draw the path up to its first error
+            let data = node.attribute("d").unwrap_or("");
+            return Some(parse_path_data_until_error(data).0);
+        }
+        "rect" => {
+            let (x, y) = (number("x"), number("y"));
+            let (width, height) = (number("width"), number("height"));
+            if width <= 0.0 || height <= 0.0 {
+                return None;
+            }
+
+            // A missing corner radius takes the value of the other one
+            let rx = node.attribute("rx").and_then(parse_length);
+            let ry = node.attribute("ry").and_then(parse_length);
+            let (rx, ry) = match (rx, ry) {
+                (Some(rx), Some(ry)) => (rx, ry),
+                (Some(r), None) | (None, Some(r)) => (r, r),
+                (None, None) => (0.0, 0.0),
+            };
+            let rx = rx.clamp(0.0, width * 0.5);
+            let ry = ry.clamp(0.0, height * 0.5);
+
+            if rx > 0.0 && ry > 0.0 {
+                let radii = [rx, ry];
+                path.move_to([x + rx, y])
+                    .line_to([x + width - rx, y])
+                    .arc_to(radii, 0.0, false, true, [x + width, y + ry])
+                    .line_to([x + width, y + height - ry])
+                    .arc_to(radii, 0.0, false, true, [x + width - rx, y + height])
+                    .line_to([x + rx, y + height])
+                    .arc_to(radii, 0.0, false, true, [x, y + height - ry])
+                    .line_to([x, y + ry])
+                    .arc_to(radii, 0.0, false, true, [x + rx, y])
+                    .close();
+            } else {
+                path.rect([x, y], [width, height]);
+            }
+        }
+        "circle" => {
+            let radius = number("r");
+            if radius <= 0.0 {
+                return None;
+            }
+            path.circle([number("cx"), number("cy")], radius);
+        }
+        "ellipse" => {
+            let radii = [number("rx"), number("ry")];
+            if radii[0] <= 0.0 || radii[1] <= 0.0 {
+                return None;
+            }
+            path.ellipse([number("cx"), number("cy")], radii);
+        }
+        "line" => {
+            path.move_to([number("x1"), number("y1")])
+                .line_to([number("x2"), number("y2")]);
+        }
+        "polygon" | "polyline" => {
+            let numbers = parse_number_list(node.attribute("points").unwrap_or(""));
+            let points: Vec<[f32; 2]> = numbers
+                .chunks_exact(2)
+                .map(|pair| [pair[0], pair[1]])
+                .collect();
+
+            if tag == "polygon" {
+                path.polygon(&points);
+            } else {
+                path.polyline(&points);
+            }
+        }
+        _ => return None,
+    }
+
+    Some(path)
+}
+
+/// Parses ``none``, ``#rgb``, ``#rrggbb``, ``rgb(r, g, b)`` and the most common color names.
+/// Returns ``None`` when the value isn't understood, so that the inherited paint is kept
+fn parse_paint(value: &str) -> Option<Option<Color>> {
+    let value = value.trim();
+
+    if value == "none" {
+        return Some(None);
+    }
+    // Paint servers (gradients, patterns) aren't supported, so nothing gets painted
+    if value.starts_with("url(") {
+        return Some(None);
+    }
+
+    value.parse::<Color>().ok().map(Some)
+}
+
+fn parse_opacity(value: &str, fallback: f32) -> f32 {
+    let value = value.trim();
+    let opacity = match value.strip_suffix('%') {
+        Some(percentage) => percentage.parse::<f32>().map(|p| p / 100.0),
+        None => value.parse::<f32>(),
+    };
+    opacity.map_or(fallback, |opacity| opacity.clamp(0.0, 1.0))
+}
+
+/// Lengths are expected in user units (pixels). Other absolute units are converted,
+/// percentages and font relative units aren't supported
+fn parse_length(value: &str) -> Option<f32> {
+    let value = value.trim();
+    let units = [
+        ("px", 1.0),
+        ("pt", 4.0 / 3.0),
+        ("pc", 16.0),
+        ("mm", 96.0 / 25.4),
+        ("cm", 96.0 / 2.54),
+        ("in", 96.0),
+    ];
+
+    for (suffix, scale) in units {
+        if let Some(number) = value.strip_suffix(suffix) {
+            return number.trim().parse::<f32>().ok().map(|n| n * scale);
+        }
+    }
+
+    value.parse().ok()
+}
+
+/// Numbers separated by commas and/or whitespace, like in ``points`` and ``viewBox``
+fn parse_number_list(value: &str) -> Vec<f32> {
+    let mut parser = PathDataParser::new(value);
+    let mut numbers = Vec::new();
+    while let Ok(number) = parser.number() {
+        numbers.push(number);
+    }
+    numbers
+}
+
+/// Parses a list of transforms like ``translate(10, 20) rotate(45)``.
+/// Unknown or malformed transforms are skipped
+pub fn parse_transform(value: &str) -> Transform {
+    let mut transform = Transform::identity();
+    let mut rest = value;
+
+    while let Some(open) = rest.find('(') {
+        let Some(close) = rest[open..].find(')').map(|close| open + close) else {
+            break;
+        };
+
+        let name = rest[..open].trim().trim_start_matches(',').trim();
+        let arguments = parse_number_list(&rest[open + 1..close]);
+        rest = &rest[close + 1..];
+
+        let next = match (name, arguments.as_slice()) {
+            ("matrix", &[a, b, c, d, e, f]) => Transform::new(a, b, c, d, e, f),
+            ("translate", &[x]) => Transform::translation(x, 0.0),
+            ("translate", &[x, y]) => Transform::translation(x, y),
+            ("scale", &[s]) => Transform::scaling(s, s),
+            ("scale", &[x, y]) => Transform::scaling(x, y),
+            ("rotate", &[angle]) => Transform::rotation(angle.to_radians()),
+            ("rotate", &[angle, cx, cy]) => Transform::translation(cx, cy)
+                .multiply(&Transform::rotation(angle.to_radians()))
+                .multiply(&Transform::translation(-cx, -cy)),
+            ("skewX", &[angle]) => Transform::shearing(angle.to_radians(), 0.0),
+            ("skewY", &[angle]) => Transform::shearing(0.0, angle.to_radians()),
+            _ => continue,
+        };
+
+        transform = transform.multiply(&next);
+    }
+
+    transform
+}
+
+/// Parses the ``d`` attribute of an SVG ``<path>`` element
+pub fn parse_path_data(data: &str) -> Result<Path, SvgError> {
+    match parse_path_data_until_error(data) {
+        (path, None) => Ok(path),
+        (_, Some(error)) => Err(error),
+    }
+}
+
+/// Parses as much of ``data`` as possible: the path has all of the commands
+/// before the first error, if there is one
+pub fn parse_path_data_until_error(data: &str) -> (Path, Option<SvgError>) {
+    let mut path = Path::new();
+    let error = parse_path_commands(data, &mut path).err();
+    (path, error)
+}
+
+fn parse_path_commands(data: &str, path: &mut Path) -> Result<(), SvgError> {
+    let mut parser = PathDataParser::new(data);
+
+    let mut command = None;
+    // Needed by the smooth curve commands (S and T), which mirror the previous control point
+    let mut last_cubic_control: Option<[f32; 2]> = None;
+    let mut last_quad_control: Option<[f32; 2]> = None;
+
+    loop {
+        parser.skip_separators();
+        if parser.is_done() {
+            break;
+        }
+
+        // Commands can be omitted when repeated, a new number means "same command again"
+        if let Some(letter) = parser.command() {
+            command = Some(letter);
+        } else if command.is_none() {
+            return Err(parser.error("expected a command"));
+        }
+
+        let letter = command.unwrap();
+        let relative = letter.is_ascii_lowercase();
+        let current = path.current_point().unwrap_or([0.0, 0.0]);
+        let absolute = |point: [f32; 2]| match relative {
+            true => [current[0] + point[0], current[1] + point[1]],
+            false => point,
+        };
+
+        let mut cubic_control = None;
+        let mut quad_control = None;
+
+        match letter.to_ascii_uppercase() {
+            'M' => {
+                path.move_to(absolute(parser.point()?));
+                // Coordinates following a move are implicit lines
+                command = Some(if relative { 'l' } else { 'L' });
+            }
+            'L' => {
+                path.line_to(absolute(parser.point()?));
+            }
+            'H' => {
+                let x = parser.number()?;
+                let x = if relative { current[0] + x } else { x };
+                path.line_to([x, current[1]]);
+            }
+            'V' => {
+                let y = parser.number()?;
+                let y = if relative { current[1] + y } else { y };
+                path.line_to([current[0], y]);
+            }
+            'C' => {
+                let control_1 = absolute(parser.point()?);
+                let control_2 = absolute(parser.point()?);
+                let to = absolute(parser.point()?);
+                path.cubic_to(control_1, control_2, to);
+                cubic_control = Some(control_2);
+            }
+            'S' => {
+                let control_1 = reflect(last_cubic_control, current);
+                let control_2 = absolute(parser.point()?);
+                let to = absolute(parser.point()?);
+                path.cubic_to(control_1, control_2, to);
+                cubic_control = Some(control_2);
+            }
+            'Q' => {
+                let control = absolute(parser.point()?);
+                let to = absolute(parser.point()?);
+                path.quad_to(control, to);
+                quad_control = Some(control);
+            }
+            'T' => {
+                let control = reflect(last_quad_control, current);
+                let to = absolute(parser.point()?);
+                path.quad_to(control, to);
+                quad_control = Some(control);
+            }
+            'A' => {
+                let rx = parser.number()?;
+                let ry = parser.number()?;
+                let rotation = parser.number()?;
+                let large_arc = parser.flag()?;
+                let sweep = parser.flag()?;
+                let to = absolute(parser.point()?);
+                path.arc_to([rx, ry], rotation.to_radians(), large_arc, sweep, to);
+            }
+            'Z' => {
+                path.close();
+                // Z doesn't take any argument, so it can't be implicitly repeated
+                command = None;
+            }
+            _ => return Err(parser.error("unknown command")),
+        }
+
+        last_cubic_control = cubic_control;
+        last_quad_control = quad_control;
+    }
+
+    Ok(())
+}
+
+/// Mirrors ``control`` around ``point``, or returns ``point`` if there is no previous control
+fn reflect(control: Option<[f32; 2]>, point: [f32; 2]) -> [f32; 2] {
+    match control {
+        Some([x, y]) => [2.0 * point[0] - x, 2.0 * point[1] - y],
+        None => point,
+    }
+}
+
+/// A tiny tokenizer for the SVG path grammar (which is also used for number lists)
+struct PathDataParser<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> PathDataParser<'a> {
+    fn new(data: &'a str) -> Self {
+        Self {
+            data: data.as_bytes(),
+            position: 0,
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.position >= self.data.len()
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.position).copied()
+    }
+
+    fn error(&self, message: &str) -> SvgError {
+        SvgError::PathData {
+            position: self.position,
+            message: message.to_string(),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r' | b'\x0C')) {
+            self.position += 1;
+        }
+    }
+
+    /// Skips whitespace and at most one comma
+    fn skip_separators(&mut self) {
+        self.skip_whitespace();
+        if self.peek() == Some(b',') {
+            self.position += 1;
+            self.skip_whitespace();
+        }
+    }
+
+    fn command(&mut self) -> Option<char> {
+        match self.peek() {
+            Some(byte) if byte.is_ascii_alphabetic() && byte != b'e' && byte != b'E' => {
+                self.position += 1;
+                Some(byte as char)
+            }
+            _ => None,
+        }
+    }
+
+    fn number(&mut self) -> Result<f32, SvgError> {
+        self.skip_separators();
+        let start = self.position;
+
+        if matches!(self.peek(), Some(b'+' | b'-')) {
+            self.position += 1;
+        }
+
+        let mut digits = 0;
+        while matches!(self.peek(), Some(b'0'..=b'9')) {
+            self.position += 1;
+            digits += 1;
+        }
+        // Numbers like "0.5.5" are actually two numbers: "0.5" and ".5"
+        if self.peek() == Some(b'.') {
+            self.position += 1;
+            while matches!(self.peek(), Some(b'0'..=b'9')) {
+                self.position += 1;
+                digits += 1;
+            }
+        }
+
+        if digits == 0 {
+            self.position = start;
+            return Err(self.error("expected a number"));
+        }
+
+        // Only consume the exponent if it is followed by digits
+        if matches!(self.peek(), Some(b'e' | b'E')) {
+            let mantissa_end = self.position;
+            self.position += 1;
+            if matches!(self.peek(), Some(b'+' | b'-')) {
+                self.position += 1;
+            }
+            if matches!(self.peek(), Some(b'0'..=b'9')) {
+                while matches!(self.peek(), Some(b'0'..=b'9')) {
+                    self.position += 1;
+                }
+            } else {
+                self.position = mantissa_end;
+            }
+        }
+
+        let text = std::str::from_utf8(&self.data[start..self.position]).unwrap();
+        text.parse().map_err(|_| {
+            self.position = start;
+            self.error("invalid number")
+        })
+    }
+
+    fn point(&mut self) -> Result<[f32; 2], SvgError> {
+        Ok([self.number()?, self.number()?])
+    }
+
+    /// Arc flags are a single 0 or 1, and don't need to be separated from what follows them
+    fn flag(&mut self) -> Result<bool, SvgError> {
+        self.skip_separators();
+        match self.peek() {
+            Some(b'0') => {
+                self.position += 1;
+                Ok(false)
+            }
+            Some(b'1') => {
+                self.position += 1;
+                Ok(true)
+            }
+            _ => Err(self.error("expected an arc flag (0 or 1)")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::path::PathCommand::{self, *};
+    use crate::tessellation::polygon::signed_area;
+
+    fn commands(data: &str) -> Vec<PathCommand> {
+        parse_path_data(data).unwrap().commands().to_vec()
+    }
+
+    fn assert_close(actual: [f32; 2], expected: [f32; 2]) {
+        assert!(
+            (actual[0] - expected[0]).abs() < 1e-4 && (actual[1] - expected[1]).abs() < 1e-4,
+            "expected {expected:?}, got {actual:?}"
+        );
+    }
+
+    /// The shapes of a 100x100 document with ``body`` as its content
+    fn shapes(body: &str) -> Vec<Shape> {
+        let text = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">{body}</svg>"#
+        );
+        parse_svg(&text).unwrap().shapes
+    }
+
+    fn filled_area(shape: &Shape) -> f32 {
+        let mesh = shape.path.fill_mesh(0.01, shape.style.fill_rule);
+        mesh.indices
+            .chunks_exact(3)
+            .map(|triangle| {
+                let corners =
+                    [0, 1, 2].map(|corner| mesh.vertices[triangle[corner] as usize].position);
+                signed_area(&corners).abs()
+            })
+            .sum()
+    }
+
+    #[test]
+    fn implicit_repeated_commands() {
+        // Coordinates after a move are lines, other commands just repeat
+        assert_eq!(
+            commands("M0 0 10 0 10 10 L 20 20 30 30"),
+            [
+                MoveTo([0.0, 0.0]),
+                LineTo([10.0, 0.0]),
+                LineTo([10.0, 10.0]),
+                LineTo([20.0, 20.0]),
+                LineTo([30.0, 30.0]),
+            ]
+        );
+        assert_eq!(
+            commands("M0,0Q1,1,2,0,3,-1,4,0"),
+            [
+                MoveTo([0.0, 0.0]),
+                QuadTo {
+                    control: [1.0, 1.0],
+                    to: [2.0, 0.0]
+                },
+                QuadTo {
+                    control: [3.0, -1.0],
+                    to: [4.0, 0.0]
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn compact_numbers() {
+        assert_eq!(
+            commands("M.5.5-1-2L1e1,2E-1"),
+            [
+                MoveTo([0.5, 0.5]),
+                LineTo([-1.0, -2.0]),
+                LineTo([10.0, 0.2])
+            ]
+        );
+    }
+
+    #[test]
+    fn relative_commands() {
+        assert_eq!(
+            commands("m10 10 l5 0 0 5 z m1 1 h2 v2 H0 V0"),
+            [
+                MoveTo([10.0, 10.0]),
+                LineTo([15.0, 10.0]),
+                LineTo([15.0, 15.0]),
+                Close,
+                // Relative to the start of the closed sub-path
+                MoveTo([11.0, 11.0]),
+                LineTo([13.0, 11.0]),
+                LineTo([13.0, 13.0]),
+                LineTo([0.0, 13.0]),
+                LineTo([0.0, 0.0]),
+            ]
+        );
+        assert_eq!(
+            commands("M10 10 c1 1 2 1 3 0 s2 -1 3 0"),
+            [
+                MoveTo([10.0, 10.0]),
+                CubicTo {
+                    control_1: [11.0, 11.0],
+                    control_2: [12.0, 11.0],
+                    to: [13.0, 10.0]
+                },
+                // The first control point mirrors the previous one
+                CubicTo {
+                    control_1: [14.0, 9.0],
+                    control_2: [15.0, 9.0],
+                    to: [16.0, 10.0]
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn arcs() {
+        let arc = commands("M0 0 A5 5 0 0 1 10 0");
+        // A half circle, as two quarters ending exactly on the end point
+        assert_eq!(arc.len(), 3);
+        assert!(matches!(arc[2], CubicTo { to, .. } if to == [10.0, 0.0]));
+
+        // Flags don't need separators, and relative arcs end relative to their start
+        assert_eq!(
+            commands("M0 0 A5,5,0,1,1,10,0"),
+            commands("M0 0 A5 5 0 1110 0")
+        );
+        assert_eq!(
+            commands("M10 10 a5 5 0 0 0 10 0").last(),
+            commands("M10 10 A5 5 0 0 0 20 10").last()
+        );
+
+        assert!(parse_path_data("M0 0 A5 5 0 2 1 10 0").is_err());
+    }
+
+    #[test]
+    fn errors_keep_the_commands_before_them() {
+        let (path, error) = parse_path_data_until_error("M0 0 L10 0 L10 10 L20 X");
+        assert_eq!(
+            path.commands(),
+            [
+                MoveTo([0.0, 0.0]),
+                LineTo([10.0, 0.0]),
+                LineTo([10.0, 10.0])
+            ]
+        );
+        assert!(matches!(
+            error,
+            Some(SvgError::PathData { position: 22, .. })
+        ));
+        assert!(parse_path_data("M0 0 L10 0 L10 10 L20 X").is_err());
+
+        // One broken path doesn't stop the rest of the document from loading
+        let shapes =
+            shapes(r#"<path d="M0 0 L10 0 L10 10 # broken"/><rect width="5" height="5"/>"#);
+        assert_eq!(shapes.len(), 2);
+        assert_eq!(shapes[0].path.commands().len(), 3);
+    }
+
+    #[test]
+    fn transform_lists() {
+        let transform = parse_transform("translate(10 20) scale(2)");
+        assert_close(transform.apply([1.0, 1.0]), [12.0, 22.0]);
+
+        let transform = parse_transform("rotate(90, 10, 10)");
+        assert_close(transform.apply([20.0, 10.0]), [10.0, 20.0]);
+
+        let transform = parse_transform("matrix(1,0,0,1,5,5),translate(1)");
+        assert_close(transform.apply([0.0, 0.0]), [6.0, 5.0]);
+
+        let transform = parse_transform("skewX(45) scale(1, -1)");
+        assert_close(transform.apply([0.0, 1.0]), [-1.0, -1.0]);
+
+        // Unknown and malformed transforms are skipped
+        let transform = parse_transform("perspective(3) translate(1, 2, 3) translate(4, 5)");
+        assert_close(transform.apply([0.0, 0.0]), [4.0, 5.0]);
+    }
+
+    #[test]
+    fn fill_rule() {
+        // Two squares going the same way, one inside the other
+        let d = "M0 0 H10 V10 H0 Z M2 2 H8 V8 H2 Z";
+
+        let non_zero = &shapes(&format!(r#"<path d="{d}"/>"#))[0];
+        assert_eq!(non_zero.style.fill_rule, FillRule::NonZero);
+        assert!((filled_area(non_zero) - 100.0).abs() < 1e-3);
+
+        let even_odd = &shapes(&format!(r#"<g fill-rule="evenodd"><path d="{d}"/></g>"#))[0];
+        assert_eq!(even_odd.style.fill_rule, FillRule::EvenOdd);
+        assert!((filled_area(even_odd) - 64.0).abs() < 1e-3);
+
+        // Going the other way, the inner square is a hole with both rules
+        let d = "M0 0 H10 V10 H0 Z M2 2 V8 H8 V2 Z";
+        let reversed = &shapes(&format!(r#"<path d="{d}"/>"#))[0];
+        assert!((filled_area(reversed) - 64.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn blend_mode_attribute() {
+        let shapes = shapes(
+            r#"<rect width="5" height="5" mix-blend-mode="multiply"/>
+            <rect width="5" height="5" mix-blend-mode="multiply" style="mix-blend-mode: screen"/>
+            <g mix-blend-mode="darken"><circle r="5"/></g>"#,
+        );
+        let blend_modes: Vec<BlendMode> =
+            shapes.iter().map(|shape| shape.style.blend_mode).collect();
+        assert_eq!(
+            blend_modes,
+            [BlendMode::Multiply, BlendMode::Screen, BlendMode::Min]
+        );
+    }
+}
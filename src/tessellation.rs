@@ -0,0 +1,189 @@
+//! CPU side helpers that turn high level shape descriptions into vertices.
+//!
+//! Nothing in here talks to the GPU, so the results can be inspected (and tested)
+//! without creating a window or an OpenGL context.
+
+pub mod ellipse;
+pub mod polygon;
+pub mod stroke;
+
+use std::collections::HashMap;
+
+use crate::path::Path;
+use crate::shapes::Vertex;
+
+/// A list of vertices plus the indices that link them together as a ``TrianglesList``
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Mesh {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+}
+
+impl Mesh {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds all of the triangles of ``other`` to this mesh
+    pub fn append(&mut self, other: Mesh) {
+        let offset = self.vertices.len() as u32;
+        self.vertices.extend(other.vertices);
+        self.indices
+            .extend(other.indices.into_iter().map(|index| index + offset));
+    }
+
+    /// Number of triangles described by the indices
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    /// The edges that belong to a single triangle, chained into closed polygons: the outline
+    /// of the area covered by the mesh, holes included (with the even-odd rule).
+    /// Vertices at the same position are the same point, even if the triangles don't share them
+    pub fn outline(&self) -> Path {
+        let mut first_index = HashMap::new();
+        let indices: Vec<u32> = self
+            .indices
+            .iter()
+            .map(|&index| {
+                let [x, y] = self.vertices[index as usize].position;
+                *first_index
+                    .entry([x.to_bits(), y.to_bits()])
+                    .or_insert(index)
+            })
+            .collect();
+
+        // Edges shared by two triangles are inside the mesh, whatever their winding
+        let mut edge_count: HashMap<[u32; 2], usize> = HashMap::new();
+        for triangle in indices.chunks_exact(3) {
+            for [from, to] in triangle_edges(triangle) {
+                *edge_count.entry([from.min(to), from.max(to)]).or_default() += 1;
+            }
+        }
+        let mut neighbours: HashMap<u32, Vec<u32>> = HashMap::new();
+        for triangle in indices.chunks_exact(3) {
+            for [from, to] in triangle_edges(triangle) {
+                if edge_count[&[from.min(to), from.max(to)]] == 1 {
+                    neighbours.entry(from).or_default().push(to);
+                    neighbours.entry(to).or_default().push(from);
+                }
+            }
+        }
+
+        // Sorted, so that the outline doesn't depend on the order of the hash map
+        let mut starts: Vec<u32> = neighbours.keys().copied().collect();
+        starts.sort_unstable();
+
+        let mut outline = Path::new();
+        for start in starts {
+            while let Some(mut current) = take_edge(&mut neighbours, start) {
+                let mut points = vec![self.vertices[start as usize].position];
+                while current != start {
+                    points.push(self.vertices[current as usize].position);
+                    match take_edge(&mut neighbours, current) {
+                        Some(next) => current = next,
+                        None => break,
+                    }
+                }
+                if points.len() > 2 {
+                    outline.polygon(&points);
+                }
+            }
+        }
+        outline
+    }
+}
+
+/// The three edges of ``triangle``, without the degenerate ones
+fn triangle_edges(triangle: &[u32]) -> impl Iterator<Item = [u32; 2]> + '_ {
+    (0..3)
+        .map(|corner| [triangle[corner], triangle[(corner + 1) % 3]])
+        .filter(|[from, to]| from != to)
+}
+
+/// Removes one of the edges starting at ``from``, and returns its other end
+fn take_edge(neighbours: &mut HashMap<u32, Vec<u32>>, from: u32) -> Option<u32> {
+    let to = neighbours.get_mut(&from)?.pop()?;
+    if let Some(back) = neighbours.get_mut(&to) {
+        if let Some(position) = back.iter().position(|&index| index == from) {
+            back.swap_remove(position);
+        }
+    }
+    Some(to)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::path::PathCommand;
+
+    fn mesh(positions: &[[f32; 2]], indices: &[u32]) -> Mesh {
+        Mesh {
+            vertices: positions.iter().map(|&[x, y]| Vertex::new(x, y)).collect(),
+            indices: indices.to_vec(),
+        }
+    }
+
+    fn polygon_count(path: &Path) -> usize {
+        path.commands()
+            .iter()
+            .filter(|command| **command == PathCommand::Close)
+            .count()
+    }
+
+    #[test]
+    fn outline_skips_the_shared_edges() {
+        let square = mesh(
+            &[[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]],
+            &[0, 1, 2, 0, 2, 3],
+        );
+        let mut expected = Path::new();
+        expected.polygon(&[[0.0, 0.0], [0.0, 1.0], [1.0, 1.0], [1.0, 0.0]]);
+        assert_eq!(square.outline(), expected);
+    }
+
+    #[test]
+    fn outline_merges_vertices_at_the_same_position() {
+        // The same square, but each triangle has its own vertices
+        let square = mesh(
+            &[
+                [0.0, 0.0],
+                [1.0, 0.0],
+                [1.0, 1.0],
+                [0.0, 0.0],
+                [1.0, 1.0],
+                [0.0, 1.0],
+            ],
+            &[0, 1, 2, 3, 4, 5],
+        );
+        let outline = square.outline();
+        assert_eq!(polygon_count(&outline), 1);
+        assert_eq!(outline.commands().len(), 5);
+    }
+
+    #[test]
+    fn outline_of_a_ring_has_a_hole() {
+        // A square with a square hole, made of 8 triangles
+        let ring = mesh(
+            &[
+                [0.0, 0.0],
+                [3.0, 0.0],
+                [3.0, 3.0],
+                [0.0, 3.0],
+                [1.0, 1.0],
+                [2.0, 1.0],
+                [2.0, 2.0],
+                [1.0, 2.0],
+            ],
+            &[
+                0, 1, 5, 0, 5, 4, 1, 2, 6, 1, 6, 5, 2, 3, 7, 2, 7, 6, 3, 0, 4, 3, 4, 7,
+            ],
+        );
+        assert_eq!(polygon_count(&ring.outline()), 2);
+    }
+
+    #[test]
+    fn outline_of_an_empty_mesh_is_empty() {
+        assert!(Mesh::new().outline().is_empty());
+    }
+}
@@ -0,0 +1,199 @@
+use std::f32::consts::TAU;
+
+use super::Mesh;
+use crate::shapes::Vertex;
+
+/// Even tiny circles get at least this many segments, otherwise they turn into squares
+pub const MIN_SEGMENTS: u32 = 8;
+
+/// Huge circles are capped so that they don't generate an absurd number of vertices
+pub const MAX_SEGMENTS: u32 = 1024;
+
+/// Maximum distance (in pixels) between the ideal curve and its polygonal approximation
+pub const DEFAULT_TOLERANCE: f32 = 0.25;
+
+/// Number of segments needed to approximate a circle of ``radius`` pixels
+/// so that no point of the polygon is further than ``tolerance`` pixels from the real curve
+pub fn segment_count(radius: f32, tolerance: f32) -> u32 {
+    if radius <= tolerance || tolerance <= 0.0 {
+        return MIN_SEGMENTS;
+    }
+
+    // The distance between the middle of a chord and its arc (the sagitta)
+    // is r * (1 - cos(θ / 2)), so we solve for the biggest angle θ
+    // that keeps that distance below the tolerance
+    let max_angle = 2.0 * (1.0 - tolerance / radius).acos();
+    let segments = (TAU / max_angle).ceil() as u32;
+
+    segments.clamp(MIN_SEGMENTS, MAX_SEGMENTS)
+}
+
+/// An axis aligned ellipse (a circle when both radii are the same)
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Ellipse {
+    pub center: [f32; 2],
+    pub radii: [f32; 2],
+    pub segments: u32,
+}
+
+impl Ellipse {
+    pub fn new(center: [f32; 2], radii: [f32; 2], segments: u32) -> Self {
+        Self {
+            center,
+            radii,
+            segments: segments.max(3),
+        }
+    }
+
+    pub fn circle(center: [f32; 2], radius: f32, segments: u32) -> Self {
+        Self::new(center, [radius, radius], segments)
+    }
+
+    /// Picks the number of segments based on how big the ellipse will be on screen.
+    /// ``pixels_per_unit`` is how many pixels one unit of our coordinates covers on each axis
+    /// (for normalized device coordinates that's half of the window size)
+    pub fn with_adaptive_segments(mut self, pixels_per_unit: [f32; 2], tolerance: f32) -> Self {
+        let radius_x = self.radii[0].abs() * pixels_per_unit[0];
+        let radius_y = self.radii[1].abs() * pixels_per_unit[1];
+        self.segments = segment_count(radius_x.max(radius_y), tolerance);
+        self
+    }
+
+    /// Point on the ellipse at ``angle`` radians, counter-clockwise starting from +X
+    pub fn point_at(&self, angle: f32) -> [f32; 2] {
+        [
+            self.center[0] + self.radii[0] * angle.cos(),
+            self.center[1] + self.radii[1] * angle.sin(),
+        ]
+    }
+
+    /// The vertices along the ellipse, in counter-clockwise order, without repeating the first one.
+    /// Draw them as a ``LineLoop`` for the outline, or as a ``TriangleFan`` for the fill
+    pub fn outline(&self) -> Vec<Vertex> {
+        (0..self.segments)
+            .map(|i| {
+                let angle = TAU * i as f32 / self.segments as f32;
+                let [x, y] = self.point_at(angle);
+                Vertex::new(x, y)
+            })
+            .collect()
+    }
+
+    /// Vertices for a ``TriangleFan`` centered on the ellipse:
+    /// the center first, then the outline, then the first outline vertex again to close it
+    pub fn fan(&self) -> Vec<Vertex> {
+        let outline = self.outline();
+        let mut vertices = Vec::with_capacity(outline.len() + 2);
+
+        vertices.push(Vertex {
+            position: self.center,
+        });
+        vertices.extend_from_slice(&outline);
+        vertices.push(outline[0]);
+
+        vertices
+    }
+
+    /// The interior of the ellipse as an indexed ``TrianglesList``,
+    /// where every triangle shares the center vertex (index 0)
+    pub fn fill(&self) -> Mesh {
+        let mut mesh = Mesh::new();
+
+        mesh.vertices.push(Vertex {
+            position: self.center,
+        });
+        mesh.vertices.extend(self.outline());
+
+        let segments = self.segments;
+        for i in 0..segments {
+            let current = 1 + i;
+            let next = 1 + (i + 1) % segments;
+            mesh.indices.extend_from_slice(&[0, current, next]);
+        }
+
+        mesh
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The biggest distance between the circle and the middle of its segments
+    fn max_sagitta(radius: f32, segments: u32) -> f32 {
+        radius * (1.0 - (TAU / segments as f32 / 2.0).cos())
+    }
+
+    #[test]
+    fn segment_count_keeps_the_error_under_the_tolerance() {
+        for radius in [2.0, 10.0, 50.0, 200.0, 1000.0] {
+            let segments = segment_count(radius, DEFAULT_TOLERANCE);
+            assert!(max_sagitta(radius, segments) <= DEFAULT_TOLERANCE * 1.001);
+            // And not many more segments than needed
+            if segments > MIN_SEGMENTS {
+                assert!(max_sagitta(radius, segments - 1) > DEFAULT_TOLERANCE);
+            }
+        }
+    }
+
+    #[test]
+    fn segment_count_grows_with_the_radius() {
+        let counts: Vec<u32> = [5.0, 20.0, 80.0, 320.0]
+            .iter()
+            .map(|&radius| segment_count(radius, DEFAULT_TOLERANCE))
+            .collect();
+        assert!(
+            counts.windows(2).all(|pair| pair[0] < pair[1]),
+            "{counts:?}"
+        );
+    }
+
+    #[test]
+    fn segment_count_stays_within_its_bounds() {
+        assert_eq!(segment_count(0.1, DEFAULT_TOLERANCE), MIN_SEGMENTS);
+        assert_eq!(segment_count(1.0, 0.0), MIN_SEGMENTS);
+        assert_eq!(segment_count(1e7, DEFAULT_TOLERANCE), MAX_SEGMENTS);
+    }
+
+    #[test]
+    fn adaptive_segments_use_the_biggest_radius_on_screen() {
+        let ellipse = Ellipse::new([0.0, 0.0], [0.1, -0.5], 3)
+            .with_adaptive_segments([400.0, 300.0], DEFAULT_TOLERANCE);
+        assert_eq!(ellipse.segments, segment_count(150.0, DEFAULT_TOLERANCE));
+    }
+
+    #[test]
+    fn outline_goes_counter_clockwise_from_positive_x() {
+        let ellipse = Ellipse::new([1.0, 2.0], [2.0, 1.0], 4);
+        let points: Vec<[f32; 2]> = ellipse.outline().iter().map(|v| v.position).collect();
+        let expected = [[3.0, 2.0], [1.0, 3.0], [-1.0, 2.0], [1.0, 1.0]];
+        for (point, expected) in points.iter().zip(expected) {
+            assert!((point[0] - expected[0]).abs() < 1e-5);
+            assert!((point[1] - expected[1]).abs() < 1e-5);
+        }
+        assert_eq!(points.len(), 4);
+
+        // Fewer than 3 segments wouldn't cover anything
+        assert_eq!(Ellipse::circle([0.0, 0.0], 1.0, 1).segments, 3);
+    }
+
+    #[test]
+    fn fan_and_fill_share_the_center() {
+        let ellipse = Ellipse::circle([0.5, 0.5], 1.0, 6);
+
+        let fan = ellipse.fan();
+        assert_eq!(fan.len(), 8);
+        assert_eq!(fan[0].position, [0.5, 0.5]);
+        assert_eq!(fan[1], fan[7]);
+
+        let fill = ellipse.fill();
+        assert_eq!(fill.vertices.len(), 7);
+        assert_eq!(fill.triangle_count(), 6);
+        // The last triangle closes the fan, back on the first outline vertex
+        assert_eq!(fill.indices[15..], [0, 6, 1]);
+        assert!(fill
+            .indices
+            .chunks_exact(3)
+            .all(|triangle| triangle[0] == 0));
+    }
+}
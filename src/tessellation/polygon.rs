@@ -0,0 +1,582 @@
+//! Triangulation of arbitrary simple polygons (concave ones included), optionally with holes.
+//!
+//! Contours follow the same convention used by earcut: all of the points live in one list,
+//! the outer contour comes first and ``hole_indices`` tells where each hole starts.
+//! Holes are first connected to the outer contour with a bridge edge, turning the polygon
+//! into a single (weakly simple) contour that is then split into triangles by ear clipping.
+
+use super::Mesh;
+use crate::shapes::Vertex;
+
+// Points closer than this are considered the same point
+const EPSILON: f64 = 1e-9;
+
+/// Which parts of overlapping contours are inside the shape, like SVG's ``fill-rule``
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub enum FillRule {
+    /// Inside an odd number of contours, whatever their direction
+    #[default]
+    EvenOdd,
+    /// Inside contours going more often one way than the other: a contour
+    /// inside another one going the same way doesn't make a hole
+    NonZero,
+}
+
+/// Splits ``points`` into the ranges of each contour: the outer one first, followed by the holes
+pub fn contour_ranges(point_count: usize, hole_indices: &[usize]) -> Vec<std::ops::Range<usize>> {
+    let mut starts = vec![0];
+    starts.extend(
+        hole_indices
+            .iter()
+            .copied()
+            .filter(|&start| start > 0 && start < point_count),
+    );
+    starts.sort_unstable();
+    starts.dedup();
+
+    let mut ranges = Vec::with_capacity(starts.len());
+    for (i, &start) in starts.iter().enumerate() {
+        let end = starts.get(i + 1).copied().unwrap_or(point_count);
+        ranges.push(start..end);
+    }
+    ranges
+}
+
+/// Joins an outline and its holes into a single list of vertices,
+/// returning it together with the start index of each hole
+pub fn join_contours(outline: &[Vertex], holes: &[Vec<Vertex>]) -> (Vec<Vertex>, Vec<usize>) {
+    let mut vertices = outline.to_vec();
+    let mut hole_indices = Vec::with_capacity(holes.len());
+
+    for hole in holes {
+        hole_indices.push(vertices.len());
+        vertices.extend_from_slice(hole);
+    }
+
+    (vertices, hole_indices)
+}
+
+/// Signed area of the contour, positive when its points are in counter-clockwise order
+pub fn signed_area(points: &[[f32; 2]]) -> f32 {
+    let mut area = 0.0;
+    for i in 0..points.len() {
+        let [x0, y0] = points[i];
+        let [x1, y1] = points[(i + 1) % points.len()];
+        area += x0 * y1 - x1 * y0;
+    }
+    area * 0.5
+}
+
+/// Triangulates the polygon, returning the indices (into ``points``) of a ``TrianglesList``.
+/// ``hole_indices`` contains the index of the first point of each hole.
+/// Contours can be given in any winding order
+pub fn triangulate(points: &[[f32; 2]], hole_indices: &[usize]) -> Vec<u32> {
+    let points: Vec<[f64; 2]> = points.iter().map(|&[x, y]| [x as f64, y as f64]).collect();
+
+    let mut contours: Vec<Vec<usize>> = contour_ranges(points.len(), hole_indices)
+        .into_iter()
+        .map(|range| dedup_contour(&points, range.collect()))
+        .collect();
+
+    let mut outer = contours.remove(0);
+    if outer.len() < 3 {
+        return Vec::new();
+    }
+
+    // The outer contour goes counter-clockwise, holes go clockwise
+    if contour_area(&points, &outer) < 0.0 {
+        outer.reverse();
+    }
+
+    let mut holes: Vec<Vec<usize>> = contours
+        .into_iter()
+        .filter(|hole| hole.len() >= 3)
+        .map(|mut hole| {
+            if contour_area(&points, &hole) > 0.0 {
+                hole.reverse();
+            }
+            hole
+        })
+        .collect();
+
+    // Bridging the holes from right to left guarantees that a bridge
+    // never has to cross a hole that hasn't been merged yet
+    holes.sort_by(|a, b| {
+        rightmost_x(&points, b)
+            .partial_cmp(&rightmost_x(&points, a))
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    let mut polygon = outer;
+    for hole in &holes {
+        bridge_hole(&points, &mut polygon, hole);
+    }
+
+    ear_clip(&points, polygon)
+}
+
+/// Convenience wrapper around ``triangulate`` that produces a ready to upload mesh
+pub fn fill_polygon(vertices: &[Vertex], hole_indices: &[usize]) -> Mesh {
+    let points: Vec<[f32; 2]> = vertices.iter().map(|vertex| vertex.position).collect();
+
+    Mesh {
+        vertices: vertices.to_vec(),
+        indices: triangulate(&points, hole_indices),
+    }
+}
+
+/// Triangulates any number of contours, nested inside each other.
+/// This is what we need to fill paths with several sub-paths, like letters or map outlines.
+/// With the even-odd rule, a contour nested inside an odd number of other contours is a hole.
+/// Contours crossing each other aren't merged, so their triangles overlap
+pub fn fill_contours(contours: &[Vec<[f32; 2]>], fill_rule: FillRule) -> Mesh {
+    let contours: Vec<&Vec<[f32; 2]>> = contours.iter().filter(|c| c.len() >= 3).collect();
+
+    let contains = |outer: &[[f32; 2]], inner: &[[f32; 2]]| point_in_contour(inner[0], outer);
+    // The contours each contour is nested in
+    let parents: Vec<Vec<usize>> = contours
+        .iter()
+        .enumerate()
+        .map(|(i, contour)| {
+            (0..contours.len())
+                .filter(|&j| i != j && contains(contours[j], contour))
+                .collect()
+        })
+        .collect();
+    let depths: Vec<usize> = parents.iter().map(Vec::len).collect();
+
+    // The winding number right inside a contour: how many contours go around
+    // that area counter-clockwise, minus how many go around it clockwise
+    let direction = |contour: &[[f32; 2]]| signed_area(contour).signum() as i32;
+    let is_filled = |i: usize| match fill_rule {
+        FillRule::EvenOdd => depths[i].is_multiple_of(2),
+        FillRule::NonZero => {
+            let winding: i32 = parents[i].iter().map(|&j| direction(contours[j])).sum();
+            winding + direction(contours[i]) != 0
+        }
+    };
+
+    let mut mesh = Mesh::new();
+
+    // Each contour fills the area between itself and the contours right inside of it,
+    // which fill the area inside of them themselves (if they are filled)
+    for (i, outline) in contours.iter().enumerate() {
+        if !is_filled(i) {
+            continue;
+        }
+
+        // The holes of this contour are the ones sitting exactly one level deeper inside it
+        let holes: Vec<Vec<Vertex>> = contours
+            .iter()
+            .enumerate()
+            .filter(|&(j, hole)| depths[j] == depths[i] + 1 && contains(outline, hole))
+            .map(|(_, hole)| hole.iter().map(|&position| Vertex { position }).collect())
+            .collect();
+
+        let outline: Vec<Vertex> = outline
+            .iter()
+            .map(|&position| Vertex { position })
+            .collect();
+        let (vertices, hole_indices) = join_contours(&outline, &holes);
+        mesh.append(fill_polygon(&vertices, &hole_indices));
+    }
+
+    mesh
+}
+
+/// Even-odd test of ``point`` against a closed contour
+pub fn point_in_contour(point: [f32; 2], contour: &[[f32; 2]]) -> bool {
+    let [x, y] = point;
+    let mut inside = false;
+
+    for i in 0..contour.len() {
+        let [x0, y0] = contour[i];
+        let [x1, y1] = contour[(i + 1) % contour.len()];
+
+        if (y0 > y) != (y1 > y) && x < x0 + (y - y0) * (x1 - x0) / (y1 - y0) {
+            inside = !inside;
+        }
+    }
+
+    inside
+}
+
+fn same_point(a: [f64; 2], b: [f64; 2]) -> bool {
+    (a[0] - b[0]).abs() <= EPSILON && (a[1] - b[1]).abs() <= EPSILON
+}
+
+/// Drops consecutive duplicated points (including the last one matching the first one)
+fn dedup_contour(points: &[[f64; 2]], mut contour: Vec<usize>) -> Vec<usize> {
+    contour.dedup_by(|a, b| same_point(points[*a], points[*b]));
+    while contour.len() > 1 && same_point(points[contour[0]], points[contour[contour.len() - 1]]) {
+        contour.pop();
+    }
+    contour
+}
+
+fn contour_area(points: &[[f64; 2]], contour: &[usize]) -> f64 {
+    let mut area = 0.0;
+    for i in 0..contour.len() {
+        let [x0, y0] = points[contour[i]];
+        let [x1, y1] = points[contour[(i + 1) % contour.len()]];
+        area += x0 * y1 - x1 * y0;
+    }
+    area * 0.5
+}
+
+fn rightmost_x(points: &[[f64; 2]], contour: &[usize]) -> f64 {
+    contour
+        .iter()
+        .map(|&index| points[index][0])
+        .fold(f64::MIN, f64::max)
+}
+
+/// Twice the signed area of the triangle, positive when ``a``, ``b``, ``c`` turn left
+fn orient(a: [f64; 2], b: [f64; 2], c: [f64; 2]) -> f64 {
+    (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0])
+}
+
+/// True if ``p`` is inside the counter-clockwise triangle ``a``, ``b``, ``c`` or on its edges
+fn point_in_triangle(p: [f64; 2], a: [f64; 2], b: [f64; 2], c: [f64; 2]) -> bool {
+    orient(a, b, p) >= 0.0 && orient(b, c, p) >= 0.0 && orient(c, a, p) >= 0.0
+}
+
+/// Connects ``hole`` to ``polygon`` with a pair of coincident edges,
+/// so that the two contours can be walked as one
+fn bridge_hole(points: &[[f64; 2]], polygon: &mut Vec<usize>, hole: &[usize]) {
+    // Start from the rightmost point of the hole...
+    let hole_start = (0..hole.len())
+        .max_by(|&a, &b| {
+            points[hole[a]][0]
+                .partial_cmp(&points[hole[b]][0])
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })
+        .unwrap();
+    let m = points[hole[hole_start]];
+
+    let Some(target) = find_bridge_target(points, polygon, m) else {
+        return;
+    };
+
+    // ...and splice the hole in right after the vertex it connects to,
+    // coming back to that same vertex once the hole has been walked around
+    let mut spliced = Vec::with_capacity(hole.len() + 2);
+    for i in 0..=hole.len() {
+        spliced.push(hole[(hole_start + i) % hole.len()]);
+    }
+    spliced.push(polygon[target]);
+
+    polygon.splice(target + 1..target + 1, spliced);
+}
+
+/// Finds the position in ``polygon`` of a vertex that can be connected to ``m``
+/// with a segment that doesn't cross any edge (David Eberly's method)
+fn find_bridge_target(points: &[[f64; 2]], polygon: &[usize], m: [f64; 2]) -> Option<usize> {
+    // Cast a ray from m towards +X and find the closest edge it hits
+    let mut closest_x = f64::MAX;
+    let mut candidate = None;
+    let mut hits_vertex = false;
+
+    for i in 0..polygon.len() {
+        let next = (i + 1) % polygon.len();
+        let a = points[polygon[i]];
+        let b = points[polygon[next]];
+
+        // The outline is counter-clockwise and the holes merged so far are clockwise,
+        // so the edges we can see by looking right from inside the polygon go upwards
+        if a[1] > m[1] || b[1] < m[1] || (a[1] - b[1]).abs() <= EPSILON {
+            continue;
+        }
+
+        let x = a[0] + (m[1] - a[1]) * (b[0] - a[0]) / (b[1] - a[1]);
+        if x < m[0] - EPSILON || x >= closest_x {
+            continue;
+        }
+
+        closest_x = x;
+        // Hitting a vertex head on means it is directly visible,
+        // otherwise the endpoint of the edge with the biggest X is our best guess
+        if same_point([x, m[1]], a) {
+            candidate = Some(i);
+            hits_vertex = true;
+        } else if same_point([x, m[1]], b) {
+            candidate = Some(next);
+            hits_vertex = true;
+        } else {
+            candidate = Some(if a[0] > b[0] { i } else { next });
+            hits_vertex = false;
+        }
+    }
+
+    if hits_vertex {
+        return candidate;
+    }
+
+    let candidate = candidate?;
+    let p = points[polygon[candidate]];
+    let intersection = [closest_x, m[1]];
+
+    // A vertex inside the triangle formed by m, the intersection and the candidate
+    // could hide the candidate: pick the one closest in angle to the ray instead
+    let (t0, t1, t2) = if m[1] < p[1] {
+        (m, intersection, p)
+    } else {
+        (intersection, m, p)
+    };
+
+    let mut best = candidate;
+    let mut best_tan = f64::MAX;
+
+    for (i, &index) in polygon.iter().enumerate() {
+        let v = points[index];
+        if v[0] < m[0] || same_point(v, p) || !point_in_triangle(v, t0, t1, t2) {
+            continue;
+        }
+
+        let tan = (m[1] - v[1]).abs() / (v[0] - m[0]).max(EPSILON);
+        let previous = points[polygon[(i + polygon.len() - 1) % polygon.len()]];
+        let next = points[polygon[(i + 1) % polygon.len()]];
+
+        // Only reflex vertices can block the view
+        let reflex = orient(previous, v, next) < 0.0;
+        if reflex && (tan < best_tan || (tan == best_tan && v[0] > points[polygon[best]][0])) {
+            best = i;
+            best_tan = tan;
+        }
+    }
+
+    Some(best)
+}
+
+/// Repeatedly cuts off "ears" (convex corners with no other vertex inside them)
+/// of the counter-clockwise ``polygon`` until only one triangle is left
+fn ear_clip(points: &[[f64; 2]], mut polygon: Vec<usize>) -> Vec<u32> {
+    let mut indices = Vec::with_capacity(polygon.len().saturating_sub(2) * 3);
+    let mut i = 0;
+    let mut attempts = 0;
+
+    while polygon.len() > 3 {
+        let count = polygon.len();
+        let previous = polygon[(i + count - 1) % count];
+        let current = polygon[i % count];
+        let next = polygon[(i + 1) % count];
+
+        if is_ear(points, &polygon, previous, current, next) {
+            indices.extend_from_slice(&[previous as u32, current as u32, next as u32]);
+            polygon.remove(i % count);
+            attempts = 0;
+            // Stay on the previous vertex, since it might have just become an ear
+            i = (i + count - 2) % (count - 1);
+            continue;
+        }
+
+        attempts += 1;
+        i = (i + 1) % count;
+
+        // A whole lap without finding any ear: the polygon is degenerate
+        // (self intersecting, or with collinear spikes), so we force our way through
+        if attempts >= count {
+            let forced = (0..count)
+                .find(|&j| {
+                    let a = points[polygon[(j + count - 1) % count]];
+                    let b = points[polygon[j]];
+                    let c = points[polygon[(j + 1) % count]];
+                    orient(a, b, c).abs() <= EPSILON
+                })
+                .or_else(|| {
+                    (0..count).find(|&j| {
+                        let a = points[polygon[(j + count - 1) % count]];
+                        let b = points[polygon[j]];
+                        let c = points[polygon[(j + 1) % count]];
+                        orient(a, b, c) > 0.0
+                    })
+                });
+
+            let Some(j) = forced else {
+                break;
+            };
+
+            let a = polygon[(j + count - 1) % count];
+            let b = polygon[j];
+            let c = polygon[(j + 1) % count];
+            // Collinear corners have no area, so they are simply dropped
+            if orient(points[a], points[b], points[c]).abs() > EPSILON {
+                indices.extend_from_slice(&[a as u32, b as u32, c as u32]);
+            }
+            polygon.remove(j);
+            attempts = 0;
+            i = 0;
+        }
+    }
+
+    if polygon.len() == 3
+        && orient(points[polygon[0]], points[polygon[1]], points[polygon[2]]) > 0.0
+    {
+        indices.extend(polygon.iter().map(|&index| index as u32));
+    }
+
+    indices
+}
+
+fn is_ear(
+    points: &[[f64; 2]],
+    polygon: &[usize],
+    previous: usize,
+    current: usize,
+    next: usize,
+) -> bool {
+    let a = points[previous];
+    let b = points[current];
+    let c = points[next];
+
+    // Reflex and flat corners can't be ears
+    if orient(a, b, c) <= EPSILON {
+        return false;
+    }
+
+    polygon.iter().all(|&index| {
+        let p = points[index];
+        // Duplicated vertices introduced by the hole bridges sit exactly on the corners
+        same_point(p, a) || same_point(p, b) || same_point(p, c) || !point_in_triangle(p, a, b, c)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(min: f32, max: f32) -> Vec<[f32; 2]> {
+        vec![[min, min], [max, min], [max, max], [min, max]]
+    }
+
+    /// Triangulates the outline and its holes, checks that the triangles all go
+    /// counter-clockwise and cover the polygon exactly once, and returns how many there are
+    fn triangle_count(outline: &[[f32; 2]], holes: &[Vec<[f32; 2]>], area: f32) -> usize {
+        let mut points = outline.to_vec();
+        let mut hole_indices = Vec::new();
+        for hole in holes {
+            hole_indices.push(points.len());
+            points.extend_from_slice(hole);
+        }
+
+        let indices = triangulate(&points, &hole_indices);
+        assert_eq!(indices.len() % 3, 0);
+
+        let mut total_area = 0.0;
+        for triangle in indices.chunks_exact(3) {
+            let corners = [0, 1, 2].map(|corner| points[triangle[corner] as usize]);
+            let triangle_area = signed_area(&corners);
+            assert!(triangle_area > 0.0, "{corners:?} isn't counter-clockwise");
+            total_area += triangle_area;
+
+            // Inside the outline, and outside of the holes
+            let centroid = [0, 1].map(|axis| corners.iter().map(|p| p[axis]).sum::<f32>() / 3.0);
+            assert!(point_in_contour(centroid, outline));
+            assert!(holes.iter().all(|hole| !point_in_contour(centroid, hole)));
+        }
+
+        // Overlapping triangles would add up to more than the area of the polygon
+        assert!(
+            (total_area - area).abs() < 1e-3,
+            "expected an area of {area}, got {total_area}"
+        );
+        indices.len() / 3
+    }
+
+    #[test]
+    fn concave_polygon() {
+        // A square with a notch at the top
+        let arrow = [[0.0, 0.0], [4.0, 0.0], [4.0, 4.0], [2.0, 2.0], [0.0, 4.0]];
+        assert_eq!(triangle_count(&arrow, &[], 12.0), 3);
+
+        // A comb, with many reflex corners
+        let comb = [
+            [0.0, 0.0],
+            [5.0, 0.0],
+            [5.0, 3.0],
+            [4.0, 3.0],
+            [4.0, 1.0],
+            [3.0, 1.0],
+            [3.0, 3.0],
+            [2.0, 3.0],
+            [2.0, 1.0],
+            [1.0, 1.0],
+            [1.0, 3.0],
+            [0.0, 3.0],
+        ];
+        assert_eq!(triangle_count(&comb, &[], 11.0), 10);
+    }
+
+    #[test]
+    fn one_hole() {
+        // A hole adds two vertices (its bridge) to the outline, so two triangles
+        let holes = [square(3.0, 6.0)];
+        assert_eq!(triangle_count(&square(0.0, 10.0), &holes, 91.0), 8);
+    }
+
+    #[test]
+    fn two_holes() {
+        let holes = [square(1.0, 3.0), square(6.0, 9.0)];
+        assert_eq!(triangle_count(&square(0.0, 10.0), &holes, 87.0), 14);
+
+        // Holes side by side, where a bridge could cross the other hole
+        let holes = [
+            vec![[2.0, 4.0], [4.0, 4.0], [4.0, 6.0], [2.0, 6.0]],
+            vec![[6.0, 4.0], [8.0, 4.0], [8.0, 6.0], [6.0, 6.0]],
+        ];
+        assert_eq!(triangle_count(&square(0.0, 10.0), &holes, 92.0), 14);
+    }
+
+    #[test]
+    fn collinear_vertices() {
+        // Extra points in the middle of the edges, which can't be the tip of an ear
+        let outline = [
+            [0.0, 0.0],
+            [1.0, 0.0],
+            [2.0, 0.0],
+            [2.0, 1.0],
+            [2.0, 2.0],
+            [1.0, 2.0],
+            [0.0, 2.0],
+            [0.0, 1.0],
+        ];
+        triangle_count(&outline, &[], 4.0);
+
+        // Completely flat polygons have no triangles at all
+        let flat = [[0.0, 0.0], [1.0, 0.0], [2.0, 0.0]];
+        assert_eq!(triangle_count(&flat, &[], 0.0), 0);
+    }
+
+    #[test]
+    fn any_winding_order() {
+        let outline = [[0.0, 0.0], [4.0, 0.0], [4.0, 4.0], [2.0, 2.0], [0.0, 4.0]];
+        let clockwise: Vec<[f32; 2]> = outline.iter().rev().copied().collect();
+        assert!(signed_area(&outline) > 0.0);
+        assert!(signed_area(&clockwise) < 0.0);
+        assert_eq!(triangle_count(&clockwise, &[], 12.0), 3);
+
+        // Holes going the same way as the outline, or the opposite way
+        let hole = square(5.0, 7.0);
+        let reversed_hole: Vec<[f32; 2]> = hole.iter().rev().copied().collect();
+        let outline = square(0.0, 10.0);
+        assert_eq!(triangle_count(&outline, &[hole], 96.0), 8);
+        assert_eq!(triangle_count(&outline, &[reversed_hole], 96.0), 8);
+    }
+
+    #[test]
+    fn nested_contours_alternate_with_even_odd() {
+        // A square in a hole of a square: the inner one is filled again
+        let contours = [square(0.0, 10.0), square(2.0, 8.0), square(4.0, 6.0)];
+        let mesh = fill_contours(&contours, FillRule::EvenOdd);
+        let area: f32 = mesh
+            .indices
+            .chunks_exact(3)
+            .map(|triangle| {
+                signed_area(
+                    &[0, 1, 2].map(|corner| mesh.vertices[triangle[corner] as usize].position),
+                )
+            })
+            .sum();
+        assert!((area - (100.0 - 36.0 + 4.0)).abs() < 1e-3);
+    }
+}
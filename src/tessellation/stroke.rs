@@ -0,0 +1,665 @@
+//! Turns polylines into triangles with an actual width.
+//!
+//! We can't rely on ``glLineWidth``: core profile drivers and software rasterizers
+//! are free to clamp wide lines to 1px, so strokes are generated as regular geometry instead.
+
+use std::f32::consts::PI;
+
+use super::Mesh;
+use crate::shapes::Vertex;
+
+/// Shape used where two segments of a stroke meet
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub enum LineJoin {
+    /// Extends the outer edges until they meet, falling back to ``Bevel``
+    /// when the tip would be longer than the miter limit
+    #[default]
+    Miter,
+    Round,
+    Bevel,
+}
+
+/// Shape used at the two ends of an open stroke
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub enum LineCap {
+    /// The stroke stops exactly at the end point
+    #[default]
+    Butt,
+    Round,
+    /// Like ``Butt``, but extended by half of the stroke width
+    Square,
+}
+
+/// Same default as SVG and the HTML canvas
+pub const DEFAULT_MITER_LIMIT: f32 = 4.0;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct StrokeOptions {
+    pub width: f32,
+    pub join: LineJoin,
+    pub cap: LineCap,
+    /// Maximum ratio between the length of a miter and the stroke width
+    pub miter_limit: f32,
+    /// Maximum distance between round joins/caps and their polygonal approximation,
+    /// expressed in the same units as the points being stroked
+    pub tolerance: f32,
+}
+
+impl Default for StrokeOptions {
+    fn default() -> Self {
+        Self {
+            width: 1.0,
+            join: LineJoin::default(),
+            cap: LineCap::default(),
+            miter_limit: DEFAULT_MITER_LIMIT,
+            tolerance: super::ellipse::DEFAULT_TOLERANCE,
+        }
+    }
+}
+
+impl StrokeOptions {
+    pub fn new(width: f32) -> Self {
+        Self {
+            width,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_join(mut self, join: LineJoin) -> Self {
+        self.join = join;
+        self
+    }
+
+    pub fn with_cap(mut self, cap: LineCap) -> Self {
+        self.cap = cap;
+        self
+    }
+
+    pub fn with_miter_limit(mut self, miter_limit: f32) -> Self {
+        self.miter_limit = miter_limit;
+        self
+    }
+
+    pub fn with_tolerance(mut self, tolerance: f32) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+}
+
+// Points closer than this are considered the same point
+const EPSILON: f32 = 1e-6;
+
+/// Generates the triangles covering a stroke of ``options.width`` along ``points``.
+/// When ``closed`` is true, the last point is connected back to the first one
+/// and no caps are generated.
+///
+/// The triangles don't overlap, so translucent strokes are painted evenly, except where
+/// the polyline crosses itself, or turns sharply next to a segment too short for the
+/// inner side of the turn to be cut: there, the segments overlap each other
+pub fn stroke_polyline(points: &[[f32; 2]], closed: bool, options: &StrokeOptions) -> Mesh {
+    let mut mesh = Mesh::new();
+    let half_width = options.width * 0.5;
+
+    if half_width <= 0.0 {
+        return mesh;
+    }
+
+    let mut points = dedup_points(points);
+    if closed && points.len() > 1 && same_point(points[0], points[points.len() - 1]) {
+        points.pop();
+    }
+
+    match points.len() {
+        0 => return mesh,
+        // A single point is drawn as a dot, if the cap has any area
+        1 => {
+            add_dot(&mut mesh, points[0], half_width, options);
+            return mesh;
+        }
+        _ => (),
+    }
+
+    let closed = closed && points.len() > 2;
+    let segment_count = if closed {
+        points.len()
+    } else {
+        points.len() - 1
+    };
+
+    // Joins go on every vertex shared by two segments
+    let join_indices = if closed {
+        0..points.len()
+    } else {
+        1..points.len() - 1
+    };
+    let neighbours = |i: usize| {
+        let previous = points[(i + points.len() - 1) % points.len()];
+        let next = points[(i + 1) % points.len()];
+        (previous, points[i], next)
+    };
+
+    // How much the left and right edges of the segments get shortened at each point
+    let mut trims = vec![[0.0; 2]; points.len()];
+    for i in join_indices.clone() {
+        let (previous, point, next) = neighbours(i);
+        trims[i] = inner_trims(previous, point, next, half_width);
+    }
+
+    for i in 0..segment_count {
+        let j = (i + 1) % points.len();
+        add_segment(
+            &mut mesh, points[i], points[j], half_width, trims[i], trims[j],
+        );
+    }
+
+    for i in join_indices {
+        let (previous, point, next) = neighbours(i);
+        let trim = trims[i][0].max(trims[i][1]);
+        add_join(&mut mesh, previous, point, next, half_width, trim, options);
+    }
+
+    if !closed {
+        let last = points.len() - 1;
+        add_cap(&mut mesh, points[0], points[1], half_width, options);
+        add_cap(
+            &mut mesh,
+            points[last],
+            points[last - 1],
+            half_width,
+            options,
+        );
+    }
+
+    mesh
+}
+
+fn same_point(a: [f32; 2], b: [f32; 2]) -> bool {
+    (a[0] - b[0]).abs() <= EPSILON && (a[1] - b[1]).abs() <= EPSILON
+}
+
+fn dedup_points(points: &[[f32; 2]]) -> Vec<[f32; 2]> {
+    let mut deduped: Vec<[f32; 2]> = Vec::with_capacity(points.len());
+    for &point in points {
+        if !deduped.last().is_some_and(|&last| same_point(last, point)) {
+            deduped.push(point);
+        }
+    }
+    deduped
+}
+
+fn sub(a: [f32; 2], b: [f32; 2]) -> [f32; 2] {
+    [a[0] - b[0], a[1] - b[1]]
+}
+
+fn add_scaled(point: [f32; 2], direction: [f32; 2], amount: f32) -> [f32; 2] {
+    [
+        point[0] + direction[0] * amount,
+        point[1] + direction[1] * amount,
+    ]
+}
+
+fn normalize(vector: [f32; 2]) -> [f32; 2] {
+    let length = vector[0].hypot(vector[1]);
+    if length <= EPSILON {
+        return [0.0, 0.0];
+    }
+    [vector[0] / length, vector[1] / length]
+}
+
+/// Unit vector pointing to the left of the direction going from ``from`` to ``to``
+fn left_normal(from: [f32; 2], to: [f32; 2]) -> [f32; 2] {
+    let [dx, dy] = normalize(sub(to, from));
+    [-dy, dx]
+}
+
+fn cross(a: [f32; 2], b: [f32; 2]) -> f32 {
+    a[0] * b[1] - a[1] * b[0]
+}
+
+fn dot(a: [f32; 2], b: [f32; 2]) -> f32 {
+    a[0] * b[0] + a[1] * b[1]
+}
+
+fn push_vertex(mesh: &mut Mesh, position: [f32; 2]) -> u32 {
+    mesh.vertices.push(Vertex { position });
+    (mesh.vertices.len() - 1) as u32
+}
+
+fn push_triangle(mesh: &mut Mesh, a: [f32; 2], b: [f32; 2], c: [f32; 2]) {
+    let a = push_vertex(mesh, a);
+    let b = push_vertex(mesh, b);
+    let c = push_vertex(mesh, c);
+    mesh.indices.extend_from_slice(&[a, b, c]);
+}
+
+/// Where the polyline turns, the edges of both segments on the inner side of the turn
+/// cross each other before reaching the point. Returns how far before the point they
+/// cross, as ``[left, right]``, or zeros if they cross too far to be cut there
+fn inner_trims(previous: [f32; 2], point: [f32; 2], next: [f32; 2], half_width: f32) -> [f32; 2] {
+    let incoming = sub(point, previous);
+    let outgoing = sub(next, point);
+    let turn = cross(normalize(incoming), normalize(outgoing));
+    let cos_angle = dot(normalize(incoming), normalize(outgoing));
+
+    if turn.abs() <= EPSILON || 1.0 + cos_angle <= EPSILON {
+        return [0.0; 2];
+    }
+
+    // half_width * tan(θ / 2), where θ is how much the polyline turns.
+    // Each segment gives at most half of its length, the other half going to its other end
+    let trim = half_width * turn.abs() / (1.0 + cos_angle);
+    let shortest = incoming[0]
+        .hypot(incoming[1])
+        .min(outgoing[0].hypot(outgoing[1]));
+    if trim > shortest * 0.5 {
+        return [0.0; 2];
+    }
+
+    if turn > 0.0 {
+        [trim, 0.0]
+    } else {
+        [0.0, trim]
+    }
+}
+
+/// ``from_trims`` and ``to_trims`` shorten the left and right edges of the segment,
+/// see ``inner_trims``
+fn add_segment(
+    mesh: &mut Mesh,
+    from: [f32; 2],
+    to: [f32; 2],
+    half_width: f32,
+    from_trims: [f32; 2],
+    to_trims: [f32; 2],
+) {
+    let normal = left_normal(from, to);
+    let direction = normalize(sub(to, from));
+    let from_left = add_scaled(from, normal, half_width);
+    let from_right = add_scaled(from, normal, -half_width);
+    let to_left = add_scaled(to, normal, half_width);
+    let to_right = add_scaled(to, normal, -half_width);
+
+    let first = push_vertex(mesh, add_scaled(from_left, direction, from_trims[0]));
+    push_vertex(mesh, add_scaled(from_right, direction, from_trims[1]));
+    push_vertex(mesh, add_scaled(to_left, direction, -to_trims[0]));
+    push_vertex(mesh, add_scaled(to_right, direction, -to_trims[1]));
+
+    mesh.indices
+        .extend_from_slice(&[first, first + 1, first + 2, first + 2, first + 1, first + 3]);
+}
+
+/// Number of segments needed to approximate an arc of ``angle`` radians
+fn arc_segment_count(radius: f32, angle: f32, tolerance: f32) -> u32 {
+    let max_angle = if radius > tolerance && tolerance > 0.0 {
+        2.0 * (1.0 - tolerance / radius).acos()
+    } else {
+        PI / 4.0
+    };
+    ((angle.abs() / max_angle).ceil() as u32).max(1)
+}
+
+/// Adds a fan of triangles around ``center``, sweeping ``angle`` radians starting from ``start``
+fn add_arc(
+    mesh: &mut Mesh,
+    center: [f32; 2],
+    start: [f32; 2],
+    angle: f32,
+    options: &StrokeOptions,
+) {
+    let radius = start[0].hypot(start[1]);
+    let segments = arc_segment_count(radius, angle, options.tolerance);
+    let start_angle = start[1].atan2(start[0]);
+
+    let center_index = push_vertex(mesh, center);
+    let mut previous = push_vertex(mesh, add_scaled(center, start, 1.0));
+    for i in 1..=segments {
+        let theta = start_angle + angle * i as f32 / segments as f32;
+        let current = push_vertex(
+            mesh,
+            [
+                center[0] + radius * theta.cos(),
+                center[1] + radius * theta.sin(),
+            ],
+        );
+        mesh.indices
+            .extend_from_slice(&[center_index, previous, current]);
+        previous = current;
+    }
+}
+
+fn add_join(
+    mesh: &mut Mesh,
+    previous: [f32; 2],
+    point: [f32; 2],
+    next: [f32; 2],
+    half_width: f32,
+    trim: f32,
+    options: &StrokeOptions,
+) {
+    let incoming = normalize(sub(point, previous));
+    let outgoing = normalize(sub(next, point));
+    let turn = cross(incoming, outgoing);
+
+    // Going straight on: the two segments already line up perfectly
+    if turn.abs() <= EPSILON && dot(incoming, outgoing) > 0.0 {
+        return;
+    }
+
+    // The gap to fill is on the outer side of the turn, which is the right
+    // side (opposite of the left normal) when turning left, and vice versa
+    let side = if turn > 0.0 { -1.0 } else { 1.0 };
+    let normal_in = left_normal(previous, point);
+    let normal_out = left_normal(point, next);
+    let outer_in = [normal_in[0] * side, normal_in[1] * side];
+    let outer_out = [normal_out[0] * side, normal_out[1] * side];
+
+    let corner_in = add_scaled(point, outer_in, half_width);
+    let corner_out = add_scaled(point, outer_out, half_width);
+
+    // The inner edges of the segments stop where they cross, so the space
+    // between that crossing and the outer corners belongs to the join
+    if trim > 0.0 {
+        let inner = add_scaled(add_scaled(point, outer_in, -half_width), incoming, -trim);
+        push_triangle(mesh, inner, corner_in, point);
+        push_triangle(mesh, inner, point, corner_out);
+    }
+
+    match options.join {
+        LineJoin::Bevel => push_triangle(mesh, point, corner_in, corner_out),
+        LineJoin::Miter => {
+            let miter = normalize([outer_in[0] + outer_out[0], outer_in[1] + outer_out[1]]);
+            let cos_half_angle = dot(miter, outer_in);
+
+            // The miter length relative to the stroke width is 1 / sin(θ / 2),
+            // where θ is the angle between the two segments
+            if cos_half_angle <= EPSILON || 1.0 / cos_half_angle > options.miter_limit {
+                push_triangle(mesh, point, corner_in, corner_out);
+                return;
+            }
+
+            let tip = add_scaled(point, miter, half_width / cos_half_angle);
+            push_triangle(mesh, point, corner_in, tip);
+            push_triangle(mesh, point, tip, corner_out);
+        }
+        LineJoin::Round => {
+            let start = [outer_in[0] * half_width, outer_in[1] * half_width];
+            let angle = cross(outer_in, outer_out).atan2(dot(outer_in, outer_out));
+            add_arc(mesh, point, start, angle, options);
+        }
+    }
+}
+
+/// Adds the cap at ``end``, where ``neighbour`` is the next point along the polyline
+fn add_cap(
+    mesh: &mut Mesh,
+    end: [f32; 2],
+    neighbour: [f32; 2],
+    half_width: f32,
+    options: &StrokeOptions,
+) {
+    // Pointing away from the polyline
+    let outward = normalize(sub(end, neighbour));
+    let normal = [-outward[1], outward[0]];
+
+    match options.cap {
+        LineCap::Butt => (),
+        LineCap::Square => {
+            let left = add_scaled(end, normal, half_width);
+            let right = add_scaled(end, normal, -half_width);
+            let far_left = add_scaled(left, outward, half_width);
+            let far_right = add_scaled(right, outward, half_width);
+            push_triangle(mesh, left, right, far_left);
+            push_triangle(mesh, far_left, right, far_right);
+        }
+        LineCap::Round => {
+            // Half a turn, from the left side of the end point to its right side
+            let start = [normal[0] * half_width, normal[1] * half_width];
+            add_arc(mesh, end, start, -PI, options);
+        }
+    }
+}
+
+fn add_dot(mesh: &mut Mesh, center: [f32; 2], half_width: f32, options: &StrokeOptions) {
+    match options.cap {
+        LineCap::Butt => (),
+        LineCap::Square => {
+            let [x, y] = center;
+            let h = half_width;
+            push_triangle(mesh, [x - h, y - h], [x + h, y - h], [x + h, y + h]);
+            push_triangle(mesh, [x - h, y - h], [x + h, y + h], [x - h, y + h]);
+        }
+        LineCap::Round => add_arc(mesh, center, [half_width, 0.0], 2.0 * PI, options),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CORNER: [[f32; 2]; 3] = [[0.0, 0.0], [10.0, 0.0], [10.0, 10.0]];
+    const SQUARE: [[f32; 2]; 4] = [[0.0, 0.0], [10.0, 0.0], [10.0, 10.0], [0.0, 10.0]];
+
+    fn options(join: LineJoin, cap: LineCap) -> StrokeOptions {
+        StrokeOptions::new(2.0)
+            .with_join(join)
+            .with_cap(cap)
+            .with_tolerance(0.001)
+    }
+
+    fn triangles(mesh: &Mesh) -> impl Iterator<Item = [[f32; 2]; 3]> + '_ {
+        mesh.indices.chunks_exact(3).map(|triangle| {
+            let position = |corner: usize| mesh.vertices[triangle[corner] as usize].position;
+            [position(0), position(1), position(2)]
+        })
+    }
+
+    /// Sum of the areas of the triangles, counting overlaps twice
+    fn area(mesh: &Mesh) -> f32 {
+        triangles(mesh)
+            .map(|[a, b, c]| cross(sub(b, a), sub(c, a)).abs() * 0.5)
+            .sum()
+    }
+
+    /// ``[min_x, min_y, max_x, max_y]``
+    fn bounds(mesh: &Mesh) -> [f32; 4] {
+        mesh.vertices.iter().fold(
+            [f32::MAX, f32::MAX, f32::MIN, f32::MIN],
+            |[min_x, min_y, max_x, max_y], vertex| {
+                let [x, y] = vertex.position;
+                [min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y)]
+            },
+        )
+    }
+
+    fn assert_close(actual: f32, expected: f32, epsilon: f32) {
+        assert!(
+            (actual - expected).abs() < epsilon,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    fn has_vertex(mesh: &Mesh, point: [f32; 2]) -> bool {
+        mesh.vertices
+            .iter()
+            .any(|vertex| same_point(vertex.position, point))
+    }
+
+    #[test]
+    fn miter_join_reaches_the_outer_corner() {
+        let mesh = stroke_polyline(&CORNER, false, &options(LineJoin::Miter, LineCap::Butt));
+        assert!(has_vertex(&mesh, [11.0, -1.0]));
+        assert_eq!(bounds(&mesh), [0.0, -1.0, 11.0, 10.0]);
+        // Two segments of 10 by 2 sharing a corner, plus the square corner of the miter
+        assert_close(area(&mesh), 40.0, 1e-3);
+    }
+
+    #[test]
+    fn bevel_join_cuts_the_corner() {
+        let mesh = stroke_polyline(&CORNER, false, &options(LineJoin::Bevel, LineCap::Butt));
+        assert!(!has_vertex(&mesh, [11.0, -1.0]));
+        assert_close(area(&mesh), 39.5, 1e-3);
+    }
+
+    #[test]
+    fn round_join_stays_within_half_the_width() {
+        let mesh = stroke_polyline(&CORNER, false, &options(LineJoin::Round, LineCap::Butt));
+        for vertex in &mesh.vertices {
+            let [x, y] = vertex.position;
+            if x > 10.0 && y < 0.0 {
+                assert!((x - 10.0).hypot(y) <= 1.0 + 1e-4);
+            }
+        }
+        // A quarter of a disc fills the corner
+        assert_close(area(&mesh), 39.0 + PI / 4.0, 1e-2);
+    }
+
+    #[test]
+    fn sharp_miter_falls_back_to_bevel() {
+        let points = [[0.0, 0.0], [10.0, 0.0], [0.0, 1.0]];
+        let bevel = stroke_polyline(&points, false, &options(LineJoin::Bevel, LineCap::Butt));
+        let miter = stroke_polyline(&points, false, &options(LineJoin::Miter, LineCap::Butt));
+        assert_eq!(miter, bevel);
+
+        let unlimited = options(LineJoin::Miter, LineCap::Butt).with_miter_limit(1000.0);
+        let long_miter = stroke_polyline(&points, false, &unlimited);
+        assert!(bounds(&long_miter)[2] > 20.0);
+    }
+
+    #[test]
+    fn joins_dont_overlap_the_segments() {
+        // An equilateral triangle: the outline is the triangle grown by half the width,
+        // minus the triangle shrunk by half the width. Their areas go with the square
+        // of the distance from the center to the sides
+        let side = 20.0;
+        let height = side * 3f32.sqrt() / 2.0;
+        let triangle = [[0.0, 0.0], [side, 0.0], [side / 2.0, height]];
+        let inradius = height / 3.0;
+        let triangle_area = side * height / 2.0;
+        let expected = triangle_area * ((inradius + 1.0).powi(2) - (inradius - 1.0).powi(2))
+            / inradius.powi(2);
+
+        let miter = stroke_polyline(&triangle, true, &options(LineJoin::Miter, LineCap::Butt));
+        assert_close(area(&miter), expected, 1e-2);
+
+        // Bevels cut the three corners of the grown triangle, where the stroke turns by 120°
+        let turn = 2.0 * PI / 3.0;
+        let corner = (turn / 2.0).tan() - turn.sin() / 2.0;
+        let bevel = stroke_polyline(&triangle, true, &options(LineJoin::Bevel, LineCap::Butt));
+        assert_close(area(&bevel), expected - 3.0 * corner, 1e-2);
+
+        let round = stroke_polyline(&triangle, true, &options(LineJoin::Round, LineCap::Butt));
+        assert!(area(&bevel) < area(&round) && area(&round) < area(&miter));
+    }
+
+    #[test]
+    fn short_segments_keep_their_inner_side() {
+        // The second segment is shorter than the width, so its inner edge isn't cut
+        let points = [[0.0, 0.0], [10.0, 0.0], [10.0, 1.0]];
+        let mesh = stroke_polyline(&points, false, &options(LineJoin::Bevel, LineCap::Butt));
+        assert_eq!(mesh.triangle_count(), 5);
+        assert!(has_vertex(&mesh, [9.0, 1.0]));
+    }
+
+    #[test]
+    fn butt_cap_stops_at_the_end_points() {
+        let mesh = stroke_polyline(
+            &[[0.0, 0.0], [10.0, 0.0]],
+            false,
+            &options(LineJoin::Miter, LineCap::Butt),
+        );
+        assert_eq!(bounds(&mesh), [0.0, -1.0, 10.0, 1.0]);
+        assert_close(area(&mesh), 20.0, 1e-4);
+    }
+
+    #[test]
+    fn square_cap_extends_by_half_the_width() {
+        let mesh = stroke_polyline(
+            &[[0.0, 0.0], [10.0, 0.0]],
+            false,
+            &options(LineJoin::Miter, LineCap::Square),
+        );
+        assert_eq!(bounds(&mesh), [-1.0, -1.0, 11.0, 1.0]);
+        assert_close(area(&mesh), 24.0, 1e-4);
+    }
+
+    #[test]
+    fn round_cap_adds_half_discs() {
+        let mesh = stroke_polyline(
+            &[[0.0, 0.0], [10.0, 0.0]],
+            false,
+            &options(LineJoin::Miter, LineCap::Round),
+        );
+        let [min_x, _, max_x, _] = bounds(&mesh);
+        assert_close(min_x, -1.0, 1e-4);
+        assert_close(max_x, 11.0, 1e-4);
+        assert_close(area(&mesh), 20.0 + PI, 1e-2);
+    }
+
+    #[test]
+    fn closed_outlines_have_joins_everywhere_and_no_caps() {
+        let options = options(LineJoin::Miter, LineCap::Square);
+        let closed = stroke_polyline(&SQUARE, true, &options);
+        // 4 segments of 2 triangles, and 4 miters of 4 triangles each
+        assert_eq!(closed.triangle_count(), 24);
+        assert_eq!(bounds(&closed), [-1.0, -1.0, 11.0, 11.0]);
+        // Nothing overlaps: a 12 by 12 square without the 8 by 8 one inside
+        assert_close(area(&closed), 80.0, 1e-3);
+
+        let open = stroke_polyline(&SQUARE, false, &options);
+        // 3 segments, 2 miters and 2 caps
+        assert_eq!(open.triangle_count(), 18);
+        // Without caps, nothing reaches past the start of the first segment
+        let open_butt = stroke_polyline(&SQUARE, false, &options.with_cap(LineCap::Butt));
+        assert!(has_vertex(&closed, [-1.0, -1.0]));
+        assert!(!has_vertex(&open_butt, [-1.0, -1.0]));
+
+        // Repeating the first point doesn't add a segment of length 0
+        let repeated = [SQUARE.as_slice(), &[SQUARE[0]]].concat();
+        assert_eq!(stroke_polyline(&repeated, true, &options), closed);
+    }
+
+    #[test]
+    fn repeated_points_are_ignored() {
+        let options = options(LineJoin::Round, LineCap::Round);
+        let repeated = [
+            [0.0, 0.0],
+            [0.0, 0.0],
+            [10.0, 0.0],
+            [10.0, 0.0],
+            [10.0, 10.0],
+        ];
+        assert_eq!(
+            stroke_polyline(&repeated, false, &options),
+            stroke_polyline(&CORNER, false, &options)
+        );
+    }
+
+    #[test]
+    fn fewer_than_two_points() {
+        assert_eq!(
+            stroke_polyline(&[], false, &StrokeOptions::new(2.0)).triangle_count(),
+            0
+        );
+
+        // A single point is a dot shaped like the cap
+        let point = [[5.0, 5.0]];
+        let butt = stroke_polyline(&point, false, &options(LineJoin::Miter, LineCap::Butt));
+        assert_eq!(butt.triangle_count(), 0);
+        let square = stroke_polyline(&point, false, &options(LineJoin::Miter, LineCap::Square));
+        assert_close(area(&square), 4.0, 1e-4);
+        let round = stroke_polyline(&point, false, &options(LineJoin::Miter, LineCap::Round));
+        assert_close(area(&round), PI, 1e-2);
+
+        // Two copies of the same point are a single point
+        assert_eq!(
+            stroke_polyline(&[[5.0, 5.0], [5.0, 5.0]], false, &StrokeOptions::new(2.0)),
+            stroke_polyline(&point, false, &StrokeOptions::new(2.0))
+        );
+    }
+
+    #[test]
+    fn zero_width_strokes_are_empty() {
+        let mesh = stroke_polyline(&CORNER, true, &StrokeOptions::new(0.0));
+        assert_eq!(mesh.triangle_count(), 0);
+    }
+}
@@ -0,0 +1,329 @@
+//! Rendering frames far bigger than a texture (or a window) can be, for print.
+//!
+//! The frame is drawn once, at the scale factor of the final image so that curves are
+//! flattened finely enough, then rendered tile by tile: each tile is an ``OffscreenTarget``,
+//! with an extra transform after the projection that zooms on its part of the frame.
+//! Since the sketch still draws in the coordinates of its canvas, it doesn't have to change.
+//! Tiles are written to disk one row at a time, so a 20000x20000 image never has to fit in
+//! memory (only a row of tiles does).
+
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, Write};
+use std::path::PathBuf;
+
+use glium::CapabilitiesSource;
+
+use crate::canvas::Canvas;
+use crate::offscreen::{tiff_error, OffscreenError, OffscreenTarget, RgbaImage};
+use crate::recording::FrameFormat;
+use crate::sketch::Sketch;
+use crate::transform::Transform;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TiledRender {
+    /// A PNG or a TIFF, depending on the extension
+    pub output: PathBuf,
+    /// Width of the image, in pixels. The height follows the aspect ratio of the canvas
+    pub width: u32,
+    /// Size of the (square) tiles, in pixels. It's reduced to what the context supports
+    pub tile_size: u32,
+}
+
+impl TiledRender {
+    pub fn new(output: impl Into<PathBuf>, width: u32) -> Self {
+        Self {
+            output: output.into(),
+            width,
+            tile_size: 2048,
+        }
+    }
+
+    pub fn with_tile_size(mut self, tile_size: u32) -> Self {
+        self.tile_size = tile_size;
+        self
+    }
+}
+
+/// Draws ``sketch`` as it is now (it isn't updated) into the image described by ``settings``,
+/// with the anti-aliasing of the canvas. The canvas gets its size back afterwards.
+/// Like in ``run_sketch``, the drawing counts as a frame of the canvas
+pub fn render_tiled(
+    sketch: &mut impl Sketch,
+    canvas: &mut Canvas,
+    settings: &TiledRender,
+) -> Result<(), OffscreenError> {
+    let format = FrameFormat::from_path(&settings.output)
+        .filter(|format| *format != FrameFormat::Exr)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "tiled renders are saved as .png, .tif or .tiff",
+            )
+        })?;
+
+    let (logical_width, logical_height) = (canvas.width(), canvas.height());
+    let (physical_width, physical_height, scale_factor) = (
+        logical_width * canvas.scale_factor(),
+        logical_height * canvas.scale_factor(),
+        canvas.scale_factor(),
+    );
+    let width = settings.width.max(1);
+    let image_scale_factor = width as f32 / logical_width;
+    let height = (logical_height * image_scale_factor).round().max(1.0) as u32;
+
+    // Resizing to the final image only changes the scale factor: the sketch sees
+    // the same logical size, but shapes are tessellated for the final resolution
+    canvas.resize(width as f32, height as f32, image_scale_factor);
+    canvas.begin_frame();
+    sketch.draw(canvas);
+
+    let max_texture_size = canvas.context().get_capabilities().max_texture_size as u32;
+    let tile_size = settings.tile_size.clamp(1, max_texture_size.max(1));
+    let result = write_bands(&settings.output, format, width, height, tile_size, |top| {
+        render_band(canvas, [width, height], tile_size, top)
+    });
+    canvas.end_frame();
+
+    canvas.resize(physical_width, physical_height, scale_factor);
+    result
+}
+
+/// Renders the row of tiles starting at the pixel row ``top``, and returns its pixels
+/// (top row first). The last row and column of tiles are cropped to the image
+fn render_band(
+    canvas: &mut Canvas,
+    [width, height]: [u32; 2],
+    tile_size: u32,
+    top: u32,
+) -> Result<Vec<u8>, OffscreenError> {
+    let band_height = tile_size.min(height - top);
+    let mut band = vec![0; width as usize * band_height as usize * 4];
+    let target = OffscreenTarget::new(
+        canvas.context(),
+        tile_size,
+        tile_size,
+        canvas.multisampling(),
+    )?;
+
+    for left in (0..width).step_by(tile_size as usize) {
+        let viewport = tile_viewport([width, height], tile_size, [left, top]);
+        target.draw(|surface| {
+            glium::Surface::clear_color(surface, 0.0, 0.0, 0.0, 0.0);
+            canvas.render_viewport(surface, &viewport)
+        })?;
+        let tile = target.read_pixels(canvas.color_pipeline());
+        copy_tile(&tile, &mut band, width, left);
+    }
+
+    Ok(band)
+}
+
+/// Maps the tile whose top left corner is at ``[left, top]`` pixels in the image
+/// (of ``width`` by ``height`` pixels) to normalized device coordinates
+fn tile_viewport([width, height]: [u32; 2], tile_size: u32, [left, top]: [u32; 2]) -> Transform {
+    let (width, height, tile_size) = (width as f32, height as f32, tile_size as f32);
+    // The tile, in normalized device coordinates of the whole image (Y points up)
+    let tile_left = -1.0 + 2.0 * left as f32 / width;
+    let tile_top = 1.0 - 2.0 * top as f32 / height;
+    let tile_width = 2.0 * tile_size / width;
+    let tile_height = 2.0 * tile_size / height;
+
+    let scale_x = 2.0 / tile_width;
+    let scale_y = 2.0 / tile_height;
+    Transform::new(
+        scale_x,
+        0.0,
+        0.0,
+        scale_y,
+        -1.0 - tile_left * scale_x,
+        1.0 - tile_top * scale_y,
+    )
+}
+
+/// Copies the visible part of ``tile`` into ``band``, at the column ``left``
+fn copy_tile(tile: &RgbaImage, band: &mut [u8], width: u32, left: u32) {
+    let row_length = width as usize * 4;
+    let copied_length = (tile.width.min(width - left) * 4) as usize;
+    let tile_row_length = tile.width as usize * 4;
+
+    for (band_row, tile_row) in band
+        .chunks_exact_mut(row_length)
+        .zip(tile.data.chunks_exact(tile_row_length))
+    {
+        let start = left as usize * 4;
+        band_row[start..start + copied_length].copy_from_slice(&tile_row[..copied_length]);
+    }
+}
+
+/// Writes the image as it's rendered: ``render_band`` is called with the first row
+/// of each band of ``band_height`` rows, from the top to the bottom
+fn write_bands(
+    path: &std::path::Path,
+    format: FrameFormat,
+    width: u32,
+    height: u32,
+    band_height: u32,
+    mut render_band: impl FnMut(u32) -> Result<Vec<u8>, OffscreenError>,
+) -> Result<(), OffscreenError> {
+    let file = BufWriter::new(File::create(path)?);
+    let bands = (0..height).step_by(band_height as usize);
+
+    if format == FrameFormat::Tiff {
+        // Plain TIFFs use 32 bits offsets: BigTIFF is needed past a couple of GB
+        let size = width as u64 * height as u64 * 4;
+        return if size <= u32::MAX as u64 / 2 {
+            let encoder = tiff::encoder::TiffEncoder::new(file).map_err(tiff_error)?;
+            write_tiff(encoder, width, height, band_height, bands, render_band)
+        } else {
+            let encoder = tiff::encoder::TiffEncoder::new_big(file).map_err(tiff_error)?;
+            write_tiff(encoder, width, height, band_height, bands, render_band)
+        };
+    }
+
+    let mut encoder = png::Encoder::new(file, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_srgb(png::SrgbRenderingIntent::Perceptual);
+    let mut writer = encoder.write_header().map_err(io::Error::from)?;
+    let mut stream = writer.stream_writer().map_err(io::Error::from)?;
+    for top in bands {
+        stream.write_all(&render_band(top)?)?;
+    }
+    stream.finish().map_err(io::Error::from)?;
+    writer.finish().map_err(io::Error::from)?;
+    Ok(())
+}
+
+fn write_tiff<W: Write + Seek, K: tiff::encoder::TiffKind>(
+    mut encoder: tiff::encoder::TiffEncoder<W, K>,
+    width: u32,
+    height: u32,
+    band_height: u32,
+    bands: impl Iterator<Item = u32>,
+    mut render_band: impl FnMut(u32) -> Result<Vec<u8>, OffscreenError>,
+) -> Result<(), OffscreenError> {
+    let mut image = encoder
+        .new_image::<tiff::encoder::colortype::RGBA8>(width, height)
+        .map_err(tiff_error)?;
+    // One strip per band, so each band is written as soon as it's rendered
+    image.rows_per_strip(band_height).map_err(tiff_error)?;
+    for top in bands {
+        image.write_strip(&render_band(top)?).map_err(tiff_error)?;
+    }
+    image.finish().map_err(tiff_error)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: [f32; 2], expected: [f32; 2]) {
+        assert!(
+            (actual[0] - expected[0]).abs() < 1e-4 && (actual[1] - expected[1]).abs() < 1e-4,
+            "expected {expected:?}, got {actual:?}"
+        );
+    }
+
+    /// The normalized device coordinates of a pixel of the whole image
+    fn ndc([width, height]: [u32; 2], [x, y]: [f32; 2]) -> [f32; 2] {
+        [-1.0 + 2.0 * x / width as f32, 1.0 - 2.0 * y / height as f32]
+    }
+
+    #[test]
+    fn first_tile_covers_the_top_left_corner() {
+        let size = [300, 200];
+        let viewport = tile_viewport(size, 128, [0, 0]);
+        assert_close(viewport.apply(ndc(size, [0.0, 0.0])), [-1.0, 1.0]);
+        assert_close(viewport.apply(ndc(size, [128.0, 128.0])), [1.0, -1.0]);
+    }
+
+    #[test]
+    fn last_tile_sticks_out_of_the_bottom_right_corner() {
+        let size = [300, 200];
+        let viewport = tile_viewport(size, 128, [256, 128]);
+        assert_close(viewport.apply(ndc(size, [256.0, 128.0])), [-1.0, 1.0]);
+        // Only 44x72 pixels of the tile are in the image
+        assert_close(
+            viewport.apply(ndc(size, [300.0, 200.0])),
+            [-1.0 + 2.0 * 44.0 / 128.0, 1.0 - 2.0 * 72.0 / 128.0],
+        );
+    }
+
+    /// A ``width`` by ``height`` image whose pixels hold their own coordinates
+    fn numbered_image(width: u32, height: u32, [left, top]: [u32; 2]) -> RgbaImage {
+        let mut image = RgbaImage::new(width, height);
+        for (index, pixel) in image.data.chunks_exact_mut(4).enumerate() {
+            let (x, y) = (index as u32 % width, index as u32 / width);
+            pixel.copy_from_slice(&[(left + x) as u8, (top + y) as u8, 0, 255]);
+        }
+        image
+    }
+
+    #[test]
+    fn last_column_of_tiles_is_cropped() {
+        // 5 pixels wide, in tiles of 3: the second tile only has 2 visible columns
+        let (width, tile_size) = (5, 3);
+        let mut band = vec![0; width as usize * tile_size as usize * 4];
+        for left in (0..width).step_by(tile_size as usize) {
+            let tile = numbered_image(tile_size, tile_size, [left, 0]);
+            copy_tile(&tile, &mut band, width, left);
+        }
+        assert_eq!(band, numbered_image(width, tile_size, [0, 0]).data);
+    }
+
+    #[test]
+    fn last_row_of_tiles_is_cropped() {
+        // Only the first row of the tile is in a band of 1 row
+        let width = 4;
+        let mut band = vec![0; width as usize * 4];
+        copy_tile(&numbered_image(4, 4, [0, 6]), &mut band, width, 0);
+        assert_eq!(band, numbered_image(width, 1, [0, 6]).data);
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("glium-101-{}-{name}", std::process::id()))
+    }
+
+    /// Writes a 5x7 image in bands of 3 rows, the last one being a single row
+    fn write_numbered(path: &std::path::Path, format: FrameFormat) -> Vec<u8> {
+        let (width, height, band_height) = (5, 7, 3);
+        let mut tops = Vec::new();
+        write_bands(path, format, width, height, band_height, |top| {
+            tops.push(top);
+            let rows = band_height.min(height - top);
+            Ok(numbered_image(width, rows, [0, top]).data)
+        })
+        .unwrap();
+        assert_eq!(tops, [0, 3, 6]);
+        numbered_image(width, height, [0, 0]).data
+    }
+
+    #[test]
+    fn bands_make_a_valid_png() {
+        let path = temp_path("bands.png");
+        let expected = write_numbered(&path, FrameFormat::Png);
+
+        let decoder = png::Decoder::new(File::open(&path).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        assert_eq!((info.width, info.height), (5, 7));
+        assert_eq!(pixels, expected);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn bands_make_a_valid_tiff() {
+        let path = temp_path("bands.tif");
+        let expected = write_numbered(&path, FrameFormat::Tiff);
+
+        let mut decoder = tiff::decoder::Decoder::new(File::open(&path).unwrap()).unwrap();
+        assert_eq!(decoder.dimensions().unwrap(), (5, 7));
+        let tiff::decoder::DecodingResult::U8(pixels) = decoder.read_image().unwrap() else {
+            panic!("expected 8 bits channels");
+        };
+        assert_eq!(pixels, expected);
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,190 @@
+//! 2D affine transforms.
+
+/// A 2D affine transform, using the same layout as the SVG ``matrix(a, b, c, d, e, f)``:
+///
+/// ```text
+/// | a c e |   | x |
+/// | b d f | * | y |
+/// | 0 0 1 |   | 1 |
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Transform {
+    pub a: f32,
+    pub b: f32,
+    pub c: f32,
+    pub d: f32,
+    pub e: f32,
+    pub f: f32,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Transform {
+    pub fn new(a: f32, b: f32, c: f32, d: f32, e: f32, f: f32) -> Self {
+        Self { a, b, c, d, e, f }
+    }
+
+    pub fn identity() -> Self {
+        Self::new(1.0, 0.0, 0.0, 1.0, 0.0, 0.0)
+    }
+
+    pub fn translation(x: f32, y: f32) -> Self {
+        Self::new(1.0, 0.0, 0.0, 1.0, x, y)
+    }
+
+    pub fn scaling(x: f32, y: f32) -> Self {
+        Self::new(x, 0.0, 0.0, y, 0.0, 0.0)
+    }
+
+    /// Counter-clockwise rotation (when Y points up) of ``angle`` radians around the origin
+    pub fn rotation(angle: f32) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Self::new(cos, sin, -sin, cos, 0.0, 0.0)
+    }
+
+    /// Shears X by ``x_angle`` radians and Y by ``y_angle`` radians
+    pub fn shearing(x_angle: f32, y_angle: f32) -> Self {
+        Self::new(1.0, y_angle.tan(), x_angle.tan(), 1.0, 0.0, 0.0)
+    }
+
+    /// The transform that applies ``other`` first, and then ``self``
+    pub fn multiply(&self, other: &Transform) -> Self {
+        Self {
+            a: self.a * other.a + self.c * other.b,
+            b: self.b * other.a + self.d * other.b,
+            c: self.a * other.c + self.c * other.d,
+            d: self.b * other.c + self.d * other.d,
+            e: self.a * other.e + self.c * other.f + self.e,
+            f: self.b * other.e + self.d * other.f + self.f,
+        }
+    }
+
+    /// The transform that applies ``self`` first, and then ``other``
+    pub fn then(&self, other: &Transform) -> Self {
+        other.multiply(self)
+    }
+
+    pub fn apply(&self, point: [f32; 2]) -> [f32; 2] {
+        let [x, y] = point;
+        [
+            self.a * x + self.c * y + self.e,
+            self.b * x + self.d * y + self.f,
+        ]
+    }
+
+    /// Like ``apply``, but ignoring the translation (useful for directions and sizes)
+    pub fn apply_vector(&self, vector: [f32; 2]) -> [f32; 2] {
+        let [x, y] = vector;
+        [self.a * x + self.c * y, self.b * x + self.d * y]
+    }
+
+    pub fn determinant(&self) -> f32 {
+        self.a * self.d - self.b * self.c
+    }
+
+    /// How much lengths are scaled by the transform, on average.
+    /// Used to scale stroke widths, which can't be non-uniformly scaled
+    pub fn average_scale(&self) -> f32 {
+        self.determinant().abs().sqrt()
+    }
+
+    /// ``None`` when the transform squashes everything onto a line or a point
+    pub fn inverse(&self) -> Option<Self> {
+        // The determinant is the area of the parallelogram spanned by the columns: it's
+        // compared to their lengths, so that tiny (or huge) but valid scales are invertible
+        let determinant = self.determinant();
+        let column_lengths = self.a.hypot(self.b) * self.c.hypot(self.d);
+        if determinant.abs() <= f32::EPSILON * column_lengths || !determinant.is_normal() {
+            return None;
+        }
+
+        let a = self.d / determinant;
+        let b = -self.b / determinant;
+        let c = -self.c / determinant;
+        let d = self.a / determinant;
+
+        let inverse = Self {
+            a,
+            b,
+            c,
+            d,
+            e: -(a * self.e + c * self.f),
+            f: -(b * self.e + d * self.f),
+        };
+        let values = [
+            inverse.a, inverse.b, inverse.c, inverse.d, inverse.e, inverse.f,
+        ];
+        values
+            .iter()
+            .all(|value| value.is_finite())
+            .then_some(inverse)
+    }
+
+    /// Column major matrix, as expected by a GLSL ``mat3`` uniform
+    pub fn to_mat3(&self) -> [[f32; 3]; 3] {
+        [
+            [self.a, self.b, 0.0],
+            [self.c, self.d, 0.0],
+            [self.e, self.f, 1.0],
+        ]
+    }
+
+    pub fn is_identity(&self) -> bool {
+        *self == Self::identity()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordinates::CoordinateMode;
+
+    fn assert_close(actual: [f32; 2], expected: [f32; 2], tolerance: f32) {
+        assert!(
+            (actual[0] - expected[0]).abs() <= tolerance
+                && (actual[1] - expected[1]).abs() <= tolerance,
+            "{actual:?} != {expected:?}"
+        );
+    }
+
+    #[test]
+    fn inverse_undoes_the_transform() {
+        let transform = Transform::translation(3.0, -2.0)
+            .multiply(&Transform::rotation(0.7))
+            .multiply(&Transform::scaling(2.0, 0.5));
+        let inverse = transform.inverse().unwrap();
+        assert_close(inverse.apply(transform.apply([1.5, 4.0])), [1.5, 4.0], 1e-5);
+    }
+
+    #[test]
+    fn large_pixel_projections_are_invertible() {
+        // The determinant is 4 / (7680 * 4320), well under f32::EPSILON
+        let projection = CoordinateMode::Pixels.projection(7680.0, 4320.0);
+        let inverse = projection.inverse().unwrap();
+        assert_close(inverse.apply([1.0, -1.0]), [7680.0, 4320.0], 1e-2);
+        assert_close(
+            inverse.apply(projection.apply([100.0, 200.0])),
+            [100.0, 200.0],
+            1e-2,
+        );
+    }
+
+    #[test]
+    fn small_scales_are_invertible() {
+        let inverse = Transform::scaling(1e-4, 1e-4).inverse().unwrap();
+        assert_close(inverse.apply([1e-4, 2e-4]), [1.0, 2.0], 1e-4);
+    }
+
+    #[test]
+    fn degenerate_transforms_have_no_inverse() {
+        assert_eq!(Transform::scaling(0.0, 1.0).inverse(), None);
+        assert_eq!(Transform::scaling(0.0, 0.0).inverse(), None);
+        // Both columns point the same way
+        assert_eq!(Transform::new(1.0, 2.0, 2.0, 4.0, 0.0, 0.0).inverse(), None);
+        assert_eq!(Transform::scaling(f32::NAN, 1.0).inverse(), None);
+    }
+}
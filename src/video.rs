@@ -0,0 +1,386 @@
+//! Encoding recordings as videos and animated images, instead of image sequences.
+//!
+//! Videos are encoded by ``ffmpeg``, which has to be installed: frames are streamed to it as
+//! raw RGBA pixels, so nothing is written to disk besides the video itself.
+//! Short loops can also be saved as animated GIFs or PNGs, encoded in Rust.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+
+use crate::offscreen::RgbaImage;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct VideoSettings {
+    /// The container is picked by ffmpeg from the extension (``.mp4``, ``.mov``, ``.webm``...)
+    pub output: PathBuf,
+    /// The name of an ffmpeg encoder, like ``libx264``, ``libvpx-vp9`` or ``prores_ks``
+    pub codec: String,
+    /// Constant rate factor: lower is better looking and bigger.
+    /// ``None`` lets the codec decide, for the ones that don't support it
+    pub crf: Option<u8>,
+    /// ``yuv420p`` plays everywhere, ``yuva444p10le`` keeps the alpha with ProRes 4444
+    pub pixel_format: String,
+    /// The ffmpeg executable, looked up in the ``PATH`` unless it's a path
+    pub ffmpeg: PathBuf,
+}
+
+impl VideoSettings {
+    /// H.264 at a high quality, in a format every player and website accepts
+    pub fn new(output: impl Into<PathBuf>) -> Self {
+        Self {
+            output: output.into(),
+            codec: "libx264".to_string(),
+            crf: Some(18),
+            pixel_format: "yuv420p".to_string(),
+            ffmpeg: PathBuf::from("ffmpeg"),
+        }
+    }
+
+    pub fn with_codec(mut self, codec: &str) -> Self {
+        self.codec = codec.to_string();
+        self
+    }
+
+    pub fn with_crf(mut self, crf: Option<u8>) -> Self {
+        self.crf = crf;
+        self
+    }
+
+    pub fn with_pixel_format(mut self, pixel_format: &str) -> Self {
+        self.pixel_format = pixel_format.to_string();
+        self
+    }
+
+    pub fn with_ffmpeg(mut self, ffmpeg: impl Into<PathBuf>) -> Self {
+        self.ffmpeg = ffmpeg.into();
+        self
+    }
+}
+
+/// An ffmpeg process, encoding the frames written to its standard input
+pub struct FfmpegEncoder {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    width: u32,
+    height: u32,
+}
+
+impl FfmpegEncoder {
+    /// Starts ffmpeg. All of the frames must be ``width`` by ``height`` pixels
+    pub fn new(
+        settings: &VideoSettings,
+        width: u32,
+        height: u32,
+        frame_rate: f32,
+    ) -> io::Result<Self> {
+        let mut command = Command::new(&settings.ffmpeg);
+        command
+            .args(["-y", "-hide_banner", "-loglevel", "error"])
+            .args(["-f", "rawvideo", "-pixel_format", "rgba"])
+            .args(["-video_size", &format!("{width}x{height}")])
+            .args(["-framerate", &frame_rate.to_string()])
+            .args(["-i", "-"])
+            // Most pixel formats of videos (like yuv420p) need even sizes
+            .args(["-vf", "pad=ceil(iw/2)*2:ceil(ih/2)*2"])
+            .args(["-c:v", &settings.codec, "-pix_fmt", &settings.pixel_format]);
+        if let Some(crf) = settings.crf {
+            command.args(["-crf", &crf.to_string()]);
+        }
+        command.arg(&settings.output).stdin(Stdio::piped());
+
+        let mut child = command.spawn().map_err(|error| {
+            if error.kind() == io::ErrorKind::NotFound {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!(
+                        "{} was not found: install ffmpeg, or set VideoSettings::ffmpeg to its path",
+                        settings.ffmpeg.display()
+                    ),
+                )
+            } else {
+                error
+            }
+        })?;
+
+        Ok(Self {
+            stdin: child.stdin.take(),
+            child,
+            width,
+            height,
+        })
+    }
+
+    pub fn write_frame(&mut self, image: &RgbaImage) -> io::Result<()> {
+        check_size(image, self.width, self.height)?;
+
+        // Writing fails when ffmpeg has given up (on an unknown codec for instance),
+        // in which case its exit status says more than the broken pipe
+        let stdin = self.stdin.as_mut().expect("frame written after finish");
+        match stdin.write_all(&image.data) {
+            Ok(()) => Ok(()),
+            Err(error) => match self.child.try_wait()? {
+                Some(status) if !status.success() => Err(ffmpeg_failed(status)),
+                _ => Err(error),
+            },
+        }
+    }
+
+    /// Waits for ffmpeg to write the end of the video
+    pub fn finish(mut self) -> io::Result<()> {
+        self.close()
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        // Closing the standard input tells ffmpeg there are no more frames
+        if self.stdin.take().is_none() {
+            return Ok(());
+        }
+        let status = self.child.wait()?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(ffmpeg_failed(status))
+        }
+    }
+}
+
+impl Drop for FfmpegEncoder {
+    fn drop(&mut self) {
+        // Leaves a playable video behind, and no zombie process
+        let _ = self.close();
+    }
+}
+
+fn ffmpeg_failed(status: std::process::ExitStatus) -> io::Error {
+    io::Error::other(format!("ffmpeg failed ({status}), see its output above"))
+}
+
+/// An animated GIF, looping forever. Each frame is quantized to its own 256 colors palette,
+/// and fully transparent pixels stay transparent
+pub struct GifEncoder {
+    encoder: gif::Encoder<BufWriter<File>>,
+    width: u32,
+    height: u32,
+    /// In hundredths of a second, the only unit GIF knows
+    delay: u16,
+}
+
+impl GifEncoder {
+    pub fn new(
+        path: impl AsRef<Path>,
+        width: u32,
+        height: u32,
+        frame_rate: f32,
+    ) -> io::Result<Self> {
+        let too_big = |_| io::Error::other("GIFs are at most 65535 pixels wide and high");
+        let gif_width = u16::try_from(width).map_err(too_big)?;
+        let gif_height = u16::try_from(height).map_err(too_big)?;
+
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = gif::Encoder::new(file, gif_width, gif_height, &[]).map_err(gif_error)?;
+        encoder
+            .set_repeat(gif::Repeat::Infinite)
+            .map_err(gif_error)?;
+
+        Ok(Self {
+            encoder,
+            width,
+            height,
+            delay: (100.0 / frame_rate.max(1.0)).round().max(1.0) as u16,
+        })
+    }
+
+    pub fn write_frame(&mut self, image: &RgbaImage) -> io::Result<()> {
+        check_size(image, self.width, self.height)?;
+
+        // Quantizing is by far the slowest part: 10 is the compromise suggested by the crate
+        let mut pixels = image.data.clone();
+        let mut frame =
+            gif::Frame::from_rgba_speed(self.width as u16, self.height as u16, &mut pixels, 10);
+        frame.delay = self.delay;
+        // Otherwise the transparent pixels would show the previous frames
+        frame.dispose = gif::DisposalMethod::Background;
+        self.encoder.write_frame(&frame).map_err(gif_error)
+    }
+
+    pub fn finish(self) -> io::Result<()> {
+        self.encoder.into_inner().map_err(gif_error)?.flush()
+    }
+}
+
+fn gif_error(error: gif::EncodingError) -> io::Error {
+    match error {
+        gif::EncodingError::Io(error) => error,
+        error => io::Error::other(error.to_string()),
+    }
+}
+
+/// An animated PNG, looping forever: full colors and alpha, but bigger than a GIF.
+/// Frames are written as they arrive. The number of frames comes before them in the file,
+/// so it's only filled in by ``finish``
+pub struct ApngEncoder {
+    path: PathBuf,
+    writer: png::Writer<BufWriter<File>>,
+    width: u32,
+    height: u32,
+    frames: u32,
+}
+
+impl ApngEncoder {
+    pub fn new(
+        path: impl Into<PathBuf>,
+        width: u32,
+        height: u32,
+        frame_rate: f32,
+    ) -> io::Result<Self> {
+        let path = path.into();
+        let file = BufWriter::new(File::create(&path)?);
+        let mut encoder = png::Encoder::new(file, width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_srgb(png::SrgbRenderingIntent::Perceptual);
+        // As many frames as possible for now, see ``patch_frame_count``
+        encoder.set_animated(u32::MAX, 0)?;
+        // The delay is a fraction of a second, so 1 / 29.97 can't be exact
+        encoder.set_frame_delay(100, (frame_rate * 100.0).round().max(1.0) as u16)?;
+
+        Ok(Self {
+            path,
+            writer: encoder.write_header()?,
+            width,
+            height,
+            frames: 0,
+        })
+    }
+
+    pub fn write_frame(&mut self, image: &RgbaImage) -> io::Result<()> {
+        check_size(image, self.width, self.height)?;
+        self.writer.write_image_data(&image.data)?;
+        self.frames += 1;
+        Ok(())
+    }
+
+    pub fn finish(self) -> io::Result<()> {
+        let Self {
+            path,
+            writer,
+            frames,
+            ..
+        } = self;
+        writer.finish()?;
+
+        if frames == 0 {
+            // An animation without frames isn't a valid PNG
+            return std::fs::remove_file(path);
+        }
+        patch_frame_count(&path, frames)
+    }
+}
+
+/// Writes ``frames`` into the animation control (acTL) chunk of the PNG at ``path``
+fn patch_frame_count(path: &Path, frames: u32) -> io::Result<()> {
+    let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+
+    // After the signature, each chunk is made of the length of its data, its type,
+    // its data and the CRC of its type and data. acTL comes before the first frame
+    let mut offset = 8;
+    loop {
+        let mut header = [0; 8];
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut header)?;
+        let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+
+        if &header[4..] == b"acTL" {
+            // The frame count, then the number of plays
+            let mut data = [0; 8];
+            file.read_exact(&mut data)?;
+            data[..4].copy_from_slice(&frames.to_be_bytes());
+
+            let mut crc = crc32fast::Hasher::new();
+            crc.update(b"acTL");
+            crc.update(&data);
+            file.seek(SeekFrom::Start(offset + 8))?;
+            file.write_all(&data)?;
+            file.write_all(&crc.finalize().to_be_bytes())?;
+            return Ok(());
+        }
+
+        offset += 12 + length as u64;
+    }
+}
+
+/// Videos can't change size midway, which happens when the window is resized
+fn check_size(image: &RgbaImage, width: u32, height: u32) -> io::Result<()> {
+    if (image.width, image.height) == (width, height) {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "the frame is {}x{} pixels, but the recording started at {width}x{height}",
+                image.width, image.height
+            ),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(value: u8) -> RgbaImage {
+        let mut image = RgbaImage::new(3, 2);
+        image.data.fill(value);
+        image
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("glium-101-{}-{name}", std::process::id()))
+    }
+
+    #[test]
+    fn apng_frames_are_streamed_and_counted() {
+        let path = temp_path("streamed.png");
+        let mut encoder = ApngEncoder::new(&path, 3, 2, 30.0).unwrap();
+        for value in [10, 20, 30] {
+            encoder.write_frame(&frame(value)).unwrap();
+        }
+        encoder.finish().unwrap();
+
+        let decoder = png::Decoder::new(File::open(&path).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let animation = reader.info().animation_control.unwrap();
+        assert_eq!((animation.num_frames, animation.num_plays), (3, 0));
+
+        // The CRC of the patched chunk is checked while decoding
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        for value in [10, 20, 30] {
+            reader.next_frame(&mut pixels).unwrap();
+            assert!(pixels.iter().all(|&pixel| pixel == value));
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn apng_frames_keep_their_size() {
+        let path = temp_path("resized.png");
+        let mut encoder = ApngEncoder::new(&path, 3, 2, 30.0).unwrap();
+        encoder.write_frame(&frame(0)).unwrap();
+        let error = encoder.write_frame(&RgbaImage::new(2, 2)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        encoder.finish().unwrap();
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn empty_apngs_are_not_left_behind() {
+        let path = temp_path("empty.png");
+        ApngEncoder::new(&path, 3, 2, 30.0)
+            .unwrap()
+            .finish()
+            .unwrap();
+        assert!(!path.exists());
+    }
+}